@@ -0,0 +1,158 @@
+use {
+    crate::{Particle, Species},
+    cgmath::{prelude::*, Vector3},
+    rayon::prelude::*,
+    std::fmt,
+};
+
+// what a central mass swallowed so far, the particles coming closer than the capture
+// radius give it their mass and momentum and are made inactive
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Accretor {
+    pub id: u32,
+    pub galaxy: u32,
+    // captured particles, massless ones included
+    pub particles: u64,
+    pub mass: f64,
+    // the simulated times of the first and the latest capture
+    pub first: Option<f64>,
+    pub last: Option<f64>,
+}
+
+impl Accretor {
+    fn new(p: &Particle) -> Self {
+        Self {
+            id: p.id,
+            galaxy: p.galaxy(),
+            particles: 0,
+            mass: 0.0,
+            first: None,
+            last: None,
+        }
+    }
+
+    // the mean accretion rate in kg/s over `time` seconds
+    pub fn rate(&self, time: f64) -> f64 {
+        if time > 0.0 {
+            self.mass / time
+        } else {
+            0.0
+        }
+    }
+
+    // the mean number of particles captured per second over `time` seconds
+    pub fn particle_rate(&self, time: f64) -> f64 {
+        if time > 0.0 {
+            self.particles as f64 / time
+        } else {
+            0.0
+        }
+    }
+}
+
+// the central masses, the particles at least `mass` kg heavy or the black holes
+// without it
+fn is_accretor(p: &Particle, mass: Option<f64>) -> bool {
+    p.mass > 0.0 && mass.map_or(p.species() == Species::BlackHole, |mass| p.mass >= mass)
+}
+
+// the test particles a central mass can swallow, those lighter than `mass` or the
+// massless ones without it, massive stars are left to the collisions
+fn is_captured(p: &Particle, mass: Option<f64>) -> bool {
+    p.is_active() && mass.map_or(p.mass == 0.0, |mass| p.mass < mass)
+}
+
+// empty statistics for every central mass
+pub fn accretors(particles: &[Particle], mass: Option<f64>) -> Vec<Accretor> {
+    let mut accretors: Vec<Accretor> = particles
+        .iter()
+        .filter(|p| is_accretor(p, mass))
+        .map(Accretor::new)
+        .collect();
+    accretors.sort_by_key(|a| a.id);
+    accretors
+}
+
+// hands every test particle within `radius` of a central mass to the nearest one, in
+// the order of their ids so the sums do not depend on the order of the particles,
+// and returns how many were captured. central masses meeting each other are left to
+// the collisions
+pub fn accrete(
+    particles: &mut [Particle],
+    radius: f64,
+    mass: Option<f64>,
+    accretors: &mut Vec<Accretor>,
+    time: f64,
+) -> usize {
+    let centers: Vec<usize> = (0..particles.len())
+        .filter(|&i| is_accretor(&particles[i], mass))
+        .collect();
+    if centers.is_empty() {
+        return 0;
+    }
+    let pos = |p: &Particle| Vector3::from(p.pos).map(|x| x as f64);
+    let vel = |p: &Particle| Vector3::from(p.vel).map(|x| x as f64);
+    let mut captures: Vec<(usize, usize)> = particles
+        .par_iter()
+        .enumerate()
+        .filter(|(_, p)| is_captured(p, mass))
+        .filter_map(|(i, p)| {
+            centers
+                .iter()
+                .map(|&c| {
+                    (
+                        (pos(&particles[c]) - pos(p)).magnitude(),
+                        particles[c].id,
+                        c,
+                    )
+                })
+                .filter(|&(distance, _, _)| distance < radius)
+                .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)))
+                .map(|(_, _, c)| (i, c))
+        })
+        .collect();
+    captures.sort_by_key(|&(i, _)| particles[i].id);
+
+    for &(i, c) in &captures {
+        let p = particles[i];
+        let center = &mut particles[c];
+        let mass = center.mass + p.mass;
+        if mass > 0.0 {
+            center.vel = ((vel(center) * center.mass + vel(&p) * p.mass) / mass)
+                .map(|x| x as f32)
+                .into();
+        }
+        center.mass = mass;
+        let id = center.id;
+        // the captured particle keeps its mass negated, a massless one gets -1 kg
+        particles[i].mass = if p.mass > 0.0 { -p.mass } else { -1.0 };
+
+        let index = match accretors.iter().position(|a| a.id == id) {
+            Some(index) => index,
+            None => {
+                accretors.push(Accretor::new(&particles[c]));
+                accretors.len() - 1
+            }
+        };
+        let accretor = &mut accretors[index];
+        accretor.particles += 1;
+        accretor.mass += p.mass;
+        accretor.first.get_or_insert(time);
+        accretor.last = Some(time);
+    }
+    captures.len()
+}
+
+impl fmt::Display for Accretor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "central mass {} of galaxy {} accreted {} particles, {:.4e} kg",
+            self.id, self.galaxy, self.particles, self.mass
+        )?;
+        if let (Some(first), Some(last)) = (self.first, self.last) {
+            write!(f, " from t {:.4e} s to {:.4e} s", first, last)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,726 @@
+use {
+    crate::render,
+    cgmath::{prelude::*, Vector3},
+    clap::{Args, Parser, Subcommand, ValueEnum},
+    nbodysim::{
+        collision::{self, Collision},
+        diagnostics::{self, Monitor},
+        export::{self, Series},
+        gadget,
+        gen::{self, SeededRng},
+        init_galaxy_with,
+        integrator::Integrator,
+        scenario::{Backend, FileError, Scenario},
+        sim::Snapshot,
+        snapshot::{self, Checkpoint},
+        softening::{Kernel, Softening},
+        solver::Solver,
+        timestep::Timestep,
+        Galaxy, Particle, Simulation,
+    },
+    rand::Rng,
+    std::{error::Error, fs, path::PathBuf, time::Instant},
+};
+
+#[derive(Parser)]
+#[command(version, about = "Galaxy collision n-body simulator")]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Simulate a scenario in a window (the default without a subcommand)
+    Run {
+        /// Scenario file (.ron or .json), the builtin galaxy collision if omitted
+        scenario: Option<PathBuf>,
+        /// Continue from a .nbody checkpoint instead, with its scenario
+        #[arg(long, conflicts_with = "scenario")]
+        restart: Option<PathBuf>,
+        #[command(flatten)]
+        overrides: Overrides,
+    },
+    /// Simulate a scenario without a window and write particle snapshots
+    Headless {
+        /// Scenario file (.ron or .json), the builtin galaxy collision if omitted
+        scenario: Option<PathBuf>,
+        /// Continue from a .nbody checkpoint instead, with its scenario
+        #[arg(long, conflicts_with = "scenario")]
+        restart: Option<PathBuf>,
+        /// Number of substeps to simulate, counted from the start of the run when restarting
+        #[arg(long)]
+        steps: u32,
+        /// Directory the snapshots and checkpoints are written to
+        #[arg(long)]
+        out: PathBuf,
+        /// Write a snapshot every this many substeps, only the last one if omitted
+        #[arg(long)]
+        every: Option<u32>,
+        /// Snapshot format, csv, vtk, vtu and ply are for external tools and vtk and vtu
+        /// also write a run.pvd series
+        #[arg(
+            long,
+            default_value = "json",
+            value_parser = ["json", "ron", "csv", "vtk", "vtu", "ply"]
+        )]
+        format: String,
+        #[command(flatten)]
+        overrides: Overrides,
+    },
+    /// Generate the initial particles of a galaxy setup and write them to a snapshot
+    Generate {
+        kind: Kind,
+        /// Number of stars per galaxy
+        #[arg(long, default_value_t = 100000)]
+        n: u32,
+        /// Output snapshot (.ron or .json)
+        #[arg(short, long)]
+        out: PathBuf,
+        /// Softening term added to the squared distance, in m²
+        #[arg(long, default_value_t = 1E20)]
+        calibrate: f64,
+        /// Softening kernel the orbital speeds are computed with
+        /// [calibrate, plummer, spline, wendland, none]
+        #[arg(long, default_value = "calibrate")]
+        kernel: Kernel,
+        /// Softening length in m, replaces --calibrate with its square
+        #[arg(long)]
+        epsilon: Option<f64>,
+        /// Seed for the random number generator, random if omitted
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Measure the steps per second of the direct and tiled gpu kernels
+    Bench {
+        /// Particle counts to measure, every particle is massive
+        #[arg(long, value_delimiter = ',', default_values_t = [1024, 4096, 16384, 65536])]
+        n: Vec<u32>,
+        /// Substeps timed for each particle count and kernel
+        #[arg(long, default_value_t = 20)]
+        steps: u32,
+        /// Seed for the random number generator, random if omitted
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Convert a snapshot or checkpoint, to csv, vtk, vtu, ply or gadget-2 for external tools
+    Export {
+        /// Snapshot file (.ron, .json, .nbody or gadget-2)
+        snapshot: PathBuf,
+        /// Output file, the format follows the extension (.ron, .json, .csv, .vtk, .vtu or
+        /// .ply) unless --gadget is given
+        #[arg(short, long)]
+        out: PathBuf,
+        /// Write a gadget-2 snapshot of format 1 or 2, in kpc, 10^10 solar masses and km/s
+        #[arg(long)]
+        gadget: Option<gadget::Format>,
+    },
+    /// Print a summary of a particle snapshot or checkpoint
+    Info {
+        /// Snapshot file (.ron, .json, .nbody or gadget-2)
+        snapshot: PathBuf,
+    },
+}
+
+#[derive(Args)]
+struct Overrides {
+    /// Timestep of one substep, in seconds
+    #[arg(long)]
+    motion: Option<f32>,
+    /// Softening term added to the squared distance, in m²
+    #[arg(long)]
+    calibrate: Option<f64>,
+    /// How the pull is softened [calibrate, plummer, spline, wendland, none]
+    #[arg(long)]
+    kernel: Option<Kernel>,
+    /// Softening length in m, replaces the calibrate term with its square
+    #[arg(long)]
+    epsilon: Option<f64>,
+    /// Simulation substeps per rendered frame
+    #[arg(long)]
+    substeps: Option<u32>,
+    /// Seed for the random number generator
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Window size in pixels
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size)]
+    window: Option<[u32; 2]>,
+    /// Where the physics runs [gpu, cpu]
+    #[arg(long)]
+    backend: Option<Backend>,
+    /// How particles are advanced [euler, leapfrog, rk4, yoshida, hermite]
+    #[arg(long)]
+    integrator: Option<Integrator>,
+    /// How substeps are split [fixed, adaptive, block]
+    #[arg(long)]
+    timestep: Option<Timestep>,
+    /// Accuracy of the adaptive and block timesteps, smaller is more accurate
+    #[arg(long)]
+    eta: Option<f64>,
+    /// Finest block timestep level, each level halves the step
+    #[arg(long)]
+    max_level: Option<u8>,
+    /// How the forces are summed [direct, tiled, barnes-hut]
+    #[arg(long)]
+    solver: Option<Solver>,
+    /// Opening angle of the Barnes-Hut tree, smaller is more accurate
+    #[arg(long)]
+    theta: Option<f64>,
+    /// Add quadrupole moments to the Barnes-Hut tree nodes
+    #[arg(long)]
+    quadrupole: bool,
+    /// What massive particles do when they touch [none, merge, bounce]
+    #[arg(long)]
+    collision: Option<Collision>,
+    /// Radius massive particles touch at in m, the softening length of their species by default
+    #[arg(long)]
+    collision_radius: Option<f64>,
+    /// Grow heavier particles to spheres of this density in kg/m³
+    #[arg(long)]
+    collision_density: Option<f64>,
+    /// Also append the collisions to this csv file
+    #[arg(long)]
+    collision_csv: Option<PathBuf>,
+    /// Central masses swallow the particles coming closer than this radius in m
+    #[arg(long)]
+    accretion_radius: Option<f64>,
+    /// Particles at least this heavy in kg swallow lighter ones, instead of black holes massless ones
+    #[arg(long)]
+    accretion_mass: Option<f64>,
+    /// Print energy, momentum and virial diagnostics every this many substeps
+    #[arg(long)]
+    diagnostics_every: Option<u32>,
+    /// Also append the diagnostics to this csv file
+    #[arg(long)]
+    diagnostics_csv: Option<PathBuf>,
+    /// Write a .nbody checkpoint every this many substeps
+    #[arg(long)]
+    checkpoint_every: Option<u32>,
+    /// Start from the particles of a snapshot, checkpoint or gadget-2 file
+    #[arg(long)]
+    initial: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Kind {
+    /// A single disk galaxy at the origin
+    Disk,
+    /// The two colliding disk galaxies of the builtin scenario
+    Collision,
+    /// A star cluster in equilibrium, a Plummer sphere at the origin
+    Plummer,
+}
+
+impl Cli {
+    pub fn execute(self) -> Result<(), Box<dyn Error>> {
+        match self.command {
+            None => run(Scenario::builtin(), None),
+            Some(Command::Run {
+                scenario,
+                restart,
+                overrides,
+            }) => {
+                let (scenario, checkpoint) = overrides.start(scenario, restart)?;
+                run(scenario, checkpoint)
+            }
+            Some(Command::Headless {
+                scenario,
+                restart,
+                steps,
+                out,
+                every,
+                format,
+                overrides,
+            }) => {
+                let (scenario, checkpoint) = overrides.start(scenario, restart)?;
+                headless(scenario, checkpoint, steps, out, every, &format)
+            }
+            Some(Command::Generate {
+                kind,
+                n,
+                out,
+                calibrate,
+                kernel,
+                epsilon,
+                seed,
+            }) => {
+                let calibrate = epsilon.map_or(calibrate, |e| e * e);
+                let particles = init_galaxy_with(
+                    Softening::new(kernel, calibrate),
+                    &kind.galaxies(n),
+                    &mut seeded(seed),
+                );
+                snapshot::save(&out, &particles)?;
+                println!("wrote {} particles to {}", particles.len(), out.display());
+                Ok(())
+            }
+            Some(Command::Bench { n, steps, seed }) => bench(&n, steps, seed),
+            Some(Command::Export {
+                snapshot,
+                out,
+                gadget,
+            }) => {
+                let (particles, time) = if snapshot::is_checkpoint(&snapshot) {
+                    let checkpoint = Checkpoint::read(&snapshot)?;
+                    (checkpoint.particles, checkpoint.time)
+                } else {
+                    (snapshot::load(&snapshot)?, 0.0)
+                };
+                match gadget {
+                    Some(format) => {
+                        gadget::write(&out, &particles, format, &gadget::Units::default(), time)?
+                    }
+                    None if export::Format::from_path(&out).is_some() => {
+                        export::write(&out, &particles)?
+                    }
+                    None => snapshot::save(&out, &particles)?,
+                }
+                println!("wrote {} particles to {}", particles.len(), out.display());
+                Ok(())
+            }
+            Some(Command::Info { snapshot }) => {
+                if snapshot::is_checkpoint(&snapshot) {
+                    let checkpoint = Checkpoint::read(&snapshot)?;
+                    println!("checkpoint:     version {}", snapshot::VERSION);
+                    println!("substeps:       {}", checkpoint.substeps);
+                    println!("time:           {:e} s", checkpoint.time);
+                    println!(
+                        "settings:       {:?} backend, {:?} integrator, {:?} timestep, {:?} solver",
+                        checkpoint.scenario.backend,
+                        checkpoint.scenario.integrator,
+                        checkpoint.scenario.timestep,
+                        checkpoint.scenario.solver
+                    );
+                    println!("accretors:      {}", checkpoint.accretors.len());
+                    info(&checkpoint.particles);
+                } else {
+                    info(&snapshot::load(&snapshot)?);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Overrides {
+    // the scenario to run and, when restarting, the checkpoint it continues from
+    fn start(
+        self,
+        path: Option<PathBuf>,
+        restart: Option<PathBuf>,
+    ) -> Result<(Scenario, Option<Checkpoint>), Box<dyn Error>> {
+        match restart {
+            Some(restart) => {
+                let mut checkpoint = Checkpoint::read(&restart)?;
+                self.apply(&mut checkpoint.scenario);
+                Ok((checkpoint.scenario.clone(), Some(checkpoint)))
+            }
+            None => Ok((self.load(path)?, None)),
+        }
+    }
+
+    fn load(self, path: Option<PathBuf>) -> Result<Scenario, Box<dyn Error>> {
+        let mut scenario = match path {
+            Some(path) => Scenario::load(path)?,
+            None => Scenario::builtin(),
+        };
+        self.apply(&mut scenario);
+        Ok(scenario)
+    }
+
+    fn apply(self, scenario: &mut Scenario) {
+        if let Some(motion) = self.motion {
+            scenario.motion = motion;
+        }
+        if let Some(calibrate) = self.calibrate {
+            scenario.calibrate = calibrate;
+        }
+        if let Some(kernel) = self.kernel {
+            scenario.kernel = kernel;
+        }
+        if self.epsilon.is_some() {
+            scenario.epsilon = self.epsilon;
+        }
+        if let Some(substeps) = self.substeps {
+            scenario.substeps = substeps;
+        }
+        if self.seed.is_some() {
+            scenario.seed = self.seed;
+        }
+        if self.window.is_some() {
+            scenario.window_size = self.window;
+        }
+        if let Some(backend) = self.backend {
+            scenario.backend = backend;
+        }
+        if let Some(integrator) = self.integrator {
+            scenario.integrator = integrator;
+        }
+        if let Some(timestep) = self.timestep {
+            scenario.timestep = timestep;
+        }
+        if let Some(eta) = self.eta {
+            scenario.eta = eta;
+        }
+        if let Some(max_level) = self.max_level {
+            scenario.max_level = max_level;
+        }
+        if let Some(solver) = self.solver {
+            scenario.solver = solver;
+        }
+        if let Some(theta) = self.theta {
+            scenario.theta = theta;
+        }
+        if self.quadrupole {
+            scenario.quadrupole = true;
+        }
+        if let Some(collision) = self.collision {
+            scenario.collision = collision;
+        }
+        if self.collision_radius.is_some() {
+            scenario.collision_radius = self.collision_radius;
+        }
+        if self.collision_density.is_some() {
+            scenario.collision_density = self.collision_density;
+        }
+        if self.collision_csv.is_some() {
+            scenario.collision_csv = self.collision_csv;
+        }
+        if self.accretion_radius.is_some() {
+            scenario.accretion_radius = self.accretion_radius;
+        }
+        if self.accretion_mass.is_some() {
+            scenario.accretion_mass = self.accretion_mass;
+        }
+        if self.diagnostics_every.is_some() {
+            scenario.diagnostics_every = self.diagnostics_every;
+        }
+        if self.diagnostics_csv.is_some() {
+            scenario.diagnostics_csv = self.diagnostics_csv;
+        }
+        if self.checkpoint_every.is_some() {
+            scenario.checkpoint_every = self.checkpoint_every;
+        }
+        if self.initial.is_some() {
+            scenario.initial = self.initial;
+        }
+    }
+}
+
+impl Kind {
+    fn galaxies(self, n: u32) -> Vec<Galaxy> {
+        match self {
+            Kind::Disk => vec![Galaxy::Init {
+                center_pos: [0.0, 0.0, 0.0],
+                center_vel: [0.0, 0.0, 0.0],
+                center_mass: 1e35,
+                amount: n,
+                normal: [0.0, 0.0, 1.0],
+            }],
+            Kind::Plummer => vec![Galaxy::Plummer {
+                center_pos: [0.0, 0.0, 0.0],
+                center_vel: [0.0, 0.0, 0.0],
+                mass: 1e35,
+                amount: n,
+                radius: 2e10,
+            }],
+            Kind::Collision => Scenario::builtin()
+                .galaxies
+                .into_iter()
+                .map(|mut galaxy| {
+                    if let Galaxy::Init { amount, .. }
+                    | Galaxy::Disk { amount, .. }
+                    | Galaxy::Bulge { amount, .. } = &mut galaxy
+                    {
+                        *amount = n;
+                    }
+                    galaxy
+                })
+                .collect(),
+        }
+    }
+}
+
+fn parse_size(s: &str) -> Result<[u32; 2], String> {
+    let (width, height) = s
+        .split_once('x')
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got `{}`", s))?;
+    let width: u32 = width.parse().map_err(|e| format!("width: {}", e))?;
+    let height: u32 = height.parse().map_err(|e| format!("height: {}", e))?;
+    if width == 0 || height == 0 {
+        return Err("window size must not be zero".to_string());
+    }
+    Ok([width, height])
+}
+
+fn seeded(seed: Option<u64>) -> SeededRng {
+    gen::seeded(seed.unwrap_or_else(random_seed))
+}
+
+// printed so the run can be reproduced
+fn random_seed() -> u64 {
+    let seed = rand::random();
+    println!("seed {}", seed);
+    seed
+}
+
+// the particles of the checkpoint, or else generated from the galaxies of the scenario
+fn particles(
+    scenario: &mut Scenario,
+    checkpoint: Option<&Checkpoint>,
+) -> Result<Vec<Particle>, FileError> {
+    Ok(match (checkpoint, &scenario.initial) {
+        (Some(checkpoint), _) => checkpoint.particles.clone(),
+        (None, Some(initial)) => {
+            let mut particles = snapshot::load_with(initial, &scenario.units, scenario.calibrate)?;
+            // an ε given in the scenario replaces the one stored in the file
+            if scenario.epsilon.is_some() || !scenario.species_epsilon.is_empty() {
+                Softening::for_scenario(scenario).apply(&mut particles);
+            }
+            particles
+        }
+        (None, None) => {
+            // keep the seed so checkpoints can tell how the run was set up
+            let seed = *scenario.seed.get_or_insert_with(random_seed);
+            init_galaxy_with(
+                Softening::for_scenario(scenario),
+                &scenario.galaxies,
+                &mut gen::seeded(seed),
+            )
+        }
+    })
+}
+
+fn run(mut scenario: Scenario, checkpoint: Option<Checkpoint>) -> Result<(), Box<dyn Error>> {
+    let particles = particles(&mut scenario, checkpoint.as_ref())?;
+    let monitor = Monitor::for_scenario(&scenario)?;
+    let log = collision::Log::for_scenario(&scenario)?;
+    pollster::block_on(render::run(
+        particles,
+        &scenario,
+        monitor,
+        log,
+        checkpoint.as_ref(),
+    ));
+    Ok(())
+}
+
+fn headless(
+    mut scenario: Scenario,
+    checkpoint: Option<Checkpoint>,
+    steps: u32,
+    out: PathBuf,
+    every: Option<u32>,
+    format: &str,
+) -> Result<(), Box<dyn Error>> {
+    if every == Some(0) || scenario.checkpoint_every == Some(0) {
+        return Err("--every and --checkpoint-every must be at least 1".into());
+    }
+    fs::create_dir_all(&out)?;
+    let particles = particles(&mut scenario, checkpoint.as_ref())?;
+    let mut sim = pollster::block_on(Simulation::new(&scenario, particles));
+    for warning in sim.warnings() {
+        eprintln!("warning: {}", warning);
+    }
+    if let Some(checkpoint) = &checkpoint {
+        sim.resume(checkpoint);
+    }
+    let first = sim.substeps();
+    let steps = steps as u64;
+    if first >= steps {
+        return Err(format!("the checkpoint is already at substep {}", first).into());
+    }
+    let mut monitor = Monitor::for_scenario(&scenario)?;
+    let mut log = collision::Log::for_scenario(&scenario)?;
+    let export = format.parse::<export::Format>().ok();
+    let mut series = export
+        .filter(|export| export.has_series())
+        .map(|_| Series::new(out.join("run.pvd")));
+    let mut save = |snapshot: Snapshot| {
+        let name = PathBuf::from(format!("step_{:06}.{}", snapshot.substeps, format));
+        let path = out.join(&name);
+        if export.is_none() {
+            return snapshot::save(&path, &snapshot.particles);
+        }
+        export::write(&path, &snapshot.particles)?;
+        match &mut series {
+            Some(series) => series.push(snapshot.time, &name),
+            None => Ok(()),
+        }
+    };
+    let start = sim.diagnostics();
+    if let Some(monitor) = &mut monitor {
+        monitor.record(first, sim.time(), &start)?;
+    }
+    // step in batches between snapshots, checkpoints and diagnostics so the gpu is
+    // not synchronised every substep, the batches line up with the substeps since the
+    // start of the run so a restart splits them exactly like the original run did
+    let every = every.unwrap_or(steps as u32);
+    sim.set_snapshot_every(Some(every));
+    let every = every as u64;
+    let checkpoint_every = scenario.checkpoint_every.map(|every| every as u64);
+    let until = |step: u64, every: u64| every - step % every;
+    while sim.substeps() < steps {
+        let step = sim.substeps();
+        let mut batch = until(step, every).min(steps - step);
+        if let Some(checkpoint_every) = checkpoint_every {
+            batch = batch.min(until(step, checkpoint_every));
+        }
+        if let Some(monitor) = &monitor {
+            batch = batch.min(monitor.remaining(step) as u64);
+        }
+        if step + batch == steps {
+            sim.request_snapshot();
+        }
+        sim.step(batch as u32);
+        for snapshot in sim.wait_snapshots() {
+            save(snapshot)?;
+        }
+        if let Some(log) = &mut log {
+            let events = sim.collisions();
+            for event in &events {
+                println!("{}", event);
+            }
+            log.record(&events)?;
+        }
+        let step = sim.substeps();
+        if let Some(checkpoint_every) = checkpoint_every {
+            if step.is_multiple_of(checkpoint_every) || step == steps {
+                sim.checkpoint()
+                    .write(&snapshot::checkpoint_path(&out, step))?;
+            }
+        }
+        if let Some(monitor) = &mut monitor {
+            if monitor.due(step) {
+                monitor.record(step, sim.time(), &sim.diagnostics())?;
+            }
+        }
+    }
+    let end = sim.diagnostics();
+    println!(
+        "simulated {} particles for {} steps ({:e} s) on the {:?} backend with {:?} into {}",
+        sim.gpu_info.particles,
+        steps - first,
+        sim.time(),
+        sim.backend(),
+        sim.integrator(),
+        out.display()
+    );
+    println!(
+        "energy of the massive particles: {:e} J -> {:e} J, relative drift {:e}",
+        start.energy.total(),
+        end.energy.total(),
+        (end.energy.total() - start.energy.total()) / start.energy.total().abs()
+    );
+    if scenario.accretion_radius.is_some() {
+        for accretor in sim.accretors() {
+            println!(
+                "{}, {:.4e} kg/s and {:.4e} particles/s on average",
+                accretor,
+                accretor.rate(sim.time()),
+                accretor.particle_rate(sim.time())
+            );
+        }
+    }
+    Ok(())
+}
+
+fn bench(counts: &[u32], steps: u32, seed: Option<u64>) -> Result<(), Box<dyn Error>> {
+    let mut rng = seeded(seed);
+    println!(
+        "{:>8} {:>14} {:>14} {:>8}",
+        "n", "direct step/s", "tiled step/s", "speedup"
+    );
+    for &n in counts {
+        // all massive, the kernels only sum over massive particles so this times every pair
+        let particles: Vec<Particle> = (0..n)
+            .map(|_| {
+                let pos = [(); 3].map(|_| rng.gen_range(-1e11..1e11));
+                Particle::new(pos, [0.0; 3], 1e30, 1E20)
+            })
+            .collect();
+        let mut rates = Vec::new();
+        for solver in [Solver::Direct, Solver::Tiled] {
+            let scenario = Scenario {
+                backend: Backend::Gpu,
+                solver,
+                ..Scenario::default()
+            };
+            let mut sim = pollster::block_on(Simulation::new(&scenario, particles.clone()));
+            if sim.backend() != Backend::Gpu {
+                return Err("the benchmark needs a gpu with 64-bit float support".into());
+            }
+            // the first submission also pays for pipeline creation
+            sim.step(1);
+            let start = Instant::now();
+            sim.step(steps);
+            rates.push(steps as f64 / start.elapsed().as_secs_f64());
+        }
+        println!(
+            "{:>8} {:>14.2} {:>14.2} {:>7.2}x",
+            n,
+            rates[0],
+            rates[1],
+            rates[1] / rates[0]
+        );
+    }
+    Ok(())
+}
+
+fn info(particles: &[Particle]) {
+    let mut massive = 0;
+    let mut inactive = 0;
+    let mut total_mass = 0.0;
+    let mut center = [0.0f64; 3];
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    let mut max_speed = 0.0f32;
+    for p in particles {
+        if !p.is_active() {
+            inactive += 1;
+            continue;
+        }
+        if p.mass() > 0.0 {
+            massive += 1;
+        }
+        total_mass += p.mass();
+        let pos = p.pos();
+        for k in 0..3 {
+            center[k] += pos[k] as f64 * p.mass();
+            min[k] = min[k].min(pos[k]);
+            max[k] = max[k].max(pos[k]);
+        }
+        max_speed = max_speed.max(Vector3::from(p.vel()).magnitude());
+    }
+    if total_mass > 0.0 {
+        center = center.map(|c| c / total_mass);
+    }
+
+    println!("particles:      {}", particles.len());
+    println!("  massive:      {}", massive);
+    println!("  massless:     {}", particles.len() - massive - inactive);
+    println!("  inactive:     {}", inactive);
+    println!("total mass:     {:e} kg", total_mass);
+    println!(
+        "center of mass: ({:e}, {:e}, {:e}) m",
+        center[0], center[1], center[2]
+    );
+    println!(
+        "bounds:         ({:e}, {:e}, {:e}) .. ({:e}, {:e}, {:e}) m",
+        min[0], min[1], min[2], max[0], max[1], max[2]
+    );
+    println!("max speed:      {:e} m/s", max_speed);
+    println!("galaxy  species       count  mass              center of mass");
+    for group in diagnostics::groups(particles) {
+        println!(
+            "{:<7} {:<10} {:>8}  {:<16.6e}  ({:e}, {:e}, {:e}) m",
+            group.galaxy,
+            format!("{:?}", group.species),
+            group.count,
+            group.mass,
+            group.center[0],
+            group.center[1],
+            group.center[2]
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use {
+    crate::{
+        scenario::{FileError, Scenario},
+        softening::Softening,
+        Particle, Partition,
+    },
+    cgmath::{prelude::*, Vector3},
+    serde::{Deserialize, Serialize},
+    std::{
+        f64::consts::PI,
+        fmt,
+        fs::File,
+        io::{self, BufWriter, Write},
+        path::PathBuf,
+        str::FromStr,
+    },
+};
+
+// what massive particles do when they touch, massless ones pass through everything
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Collision {
+    // they only attract each other
+    None,
+    // the following run on the cpu only, after every substep
+    // the heavier one absorbs the other, conserving mass and momentum, and the other
+    // is made inactive
+    Merge,
+    // they bounce off each other elastically, conserving momentum and kinetic energy
+    Bounce,
+}
+
+// the size of the massive particles
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Radii {
+    // in m, the softening length of each species if omitted
+    pub radius: Option<f64>,
+    pub softening: Softening,
+    // in kg/m³, heavier particles are at least a sphere of this density, so merged
+    // bodies grow
+    pub density: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Event {
+    pub substeps: u64,
+    pub time: f64,
+    // `Merge` or `Bounce`
+    pub collision: Collision,
+    // the ids of the pair, the first one is the survivor of a merge
+    pub ids: [u32; 2],
+    // their masses before the collision
+    pub masses: [f64; 2],
+    // the center of mass of the pair
+    pub pos: [f64; 3],
+    // how fast they approached each other
+    pub speed: f64,
+}
+
+// appends the collisions to an optional csv file as they happen, printing them is up to
+// the frontend
+pub struct Log {
+    csv: Option<(PathBuf, BufWriter<File>)>,
+}
+
+impl Collision {
+    pub fn runs_on_gpu(self) -> bool {
+        self == Collision::None
+    }
+}
+
+impl FromStr for Collision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Collision::None),
+            "merge" => Ok(Collision::Merge),
+            "bounce" => Ok(Collision::Bounce),
+            _ => Err(format!(
+                "unknown collision `{}`, expected one of none, merge, bounce",
+                s
+            )),
+        }
+    }
+}
+
+impl Radii {
+    // the softening length of each species unless the scenario sets a radius
+    pub fn for_scenario(scenario: &Scenario) -> Self {
+        Self {
+            radius: scenario.collision_radius,
+            softening: Softening::for_scenario(scenario),
+            density: scenario.collision_density,
+        }
+    }
+
+    pub fn of(&self, p: &Particle) -> f64 {
+        let radius = self
+            .radius
+            .unwrap_or_else(|| self.softening.calibrate(p.species()).sqrt());
+        match self.density {
+            Some(density) if density > 0.0 => {
+                radius.max(libm::cbrt(3.0 * p.mass / (4.0 * PI * density)))
+            }
+            _ => radius,
+        }
+    }
+}
+
+// resolves the pairs of massive particles closer than the sum of their radii, in the
+// order of their ids so the outcome does not depend on the order of the particles
+pub fn collide(
+    particles: &mut [Particle],
+    collision: Collision,
+    radii: Radii,
+    substeps: u64,
+    time: f64,
+) -> Vec<Event> {
+    if collision == Collision::None {
+        return Vec::new();
+    }
+    let pos = |p: &Particle| Vector3::from(p.pos).map(|x| x as f64);
+    let vel = |p: &Particle| Vector3::from(p.vel).map(|x| x as f64);
+    let touching = |a: &Particle, b: &Particle| {
+        a.mass > 0.0 && b.mass > 0.0 && (pos(b) - pos(a)).magnitude() < radii.of(a) + radii.of(b)
+    };
+
+    // sweep along x, only particles closer in x than the largest pair of radii can touch
+    let mut massive = Partition::new(particles).massive;
+    massive.sort_by(|&a, &b| particles[a].pos[0].total_cmp(&particles[b].pos[0]));
+    let largest = massive
+        .iter()
+        .map(|&i| radii.of(&particles[i]))
+        .fold(0.0, f64::max);
+    let mut pairs = Vec::new();
+    for (k, &i) in massive.iter().enumerate() {
+        let reach = radii.of(&particles[i]) + largest;
+        for &j in &massive[k + 1..] {
+            if (particles[j].pos[0] - particles[i].pos[0]) as f64 > reach {
+                break;
+            }
+            if touching(&particles[i], &particles[j]) {
+                pairs.push(if particles[i].id <= particles[j].id {
+                    (i, j)
+                } else {
+                    (j, i)
+                });
+            }
+        }
+    }
+    pairs.sort_by_key(|&(i, j)| (particles[i].id, particles[j].id));
+
+    let mut events = Vec::new();
+    for (i, j) in pairs {
+        // an earlier collision may have absorbed or moved either of them
+        let (a, b) = (particles[i], particles[j]);
+        if !touching(&a, &b) {
+            continue;
+        }
+        let mass = a.mass + b.mass;
+        let center = (pos(&a) * a.mass + pos(&b) * b.mass) / mass;
+        let relative = vel(&a) - vel(&b);
+        let mut event = Event {
+            substeps,
+            time,
+            collision,
+            ids: [a.id, b.id],
+            masses: [a.mass, b.mass],
+            pos: center.into(),
+            speed: relative.magnitude(),
+        };
+        match collision {
+            Collision::None => unreachable!(),
+            Collision::Merge => {
+                // the lighter one is absorbed, the one with the smaller id on a tie
+                let (survivor, absorbed) = if b.mass > a.mass { (j, i) } else { (i, j) };
+                let momentum = vel(&a) * a.mass + vel(&b) * b.mass;
+                let p = &mut particles[survivor];
+                p.pos = center.map(|x| x as f32).into();
+                p.vel = (momentum / mass).map(|x| x as f32).into();
+                p.mass = mass;
+                let p = &mut particles[absorbed];
+                p.mass = -p.mass;
+                if survivor == j {
+                    event.ids.swap(0, 1);
+                    event.masses.swap(0, 1);
+                }
+            }
+            Collision::Bounce => {
+                let normal = (pos(&b) - pos(&a)).normalize();
+                let approach = relative.dot(normal);
+                // already parting, or on top of each other without a direction
+                if !approach.is_finite() || approach <= 0.0 {
+                    continue;
+                }
+                event.speed = approach;
+                let kick = normal * 2.0 * approach / mass;
+                particles[i].vel = (vel(&a) - kick * b.mass).map(|x| x as f32).into();
+                particles[j].vel = (vel(&b) + kick * a.mass).map(|x| x as f32).into();
+            }
+        }
+        events.push(event);
+    }
+    events
+}
+
+impl Log {
+    // None unless the scenario turns collisions on
+    pub fn for_scenario(scenario: &Scenario) -> Result<Option<Self>, FileError> {
+        if scenario.collision == Collision::None {
+            return Ok(None);
+        }
+        Self::new(scenario.collision_csv.clone()).map(Some)
+    }
+
+    pub fn new(csv: Option<PathBuf>) -> Result<Self, FileError> {
+        let csv = match csv {
+            Some(path) => {
+                let file = File::create(&path).map_err(|e| FileError::Io(path.clone(), e))?;
+                let mut file = BufWriter::new(file);
+                writeln!(
+                    file,
+                    "step,time,collision,id,other,mass,other_mass,x,y,z,speed"
+                )
+                .map_err(|e| FileError::Io(path.clone(), e))?;
+                Some((path, file))
+            }
+            None => None,
+        };
+        Ok(Self { csv })
+    }
+
+    pub fn record(&mut self, events: &[Event]) -> Result<(), FileError> {
+        if let Some((path, file)) = &mut self.csv {
+            let write = |file: &mut BufWriter<File>| -> io::Result<()> {
+                for e in events {
+                    writeln!(
+                        file,
+                        "{},{:e},{:?},{},{},{:e},{:e},{:e},{:e},{:e},{:e}",
+                        e.substeps,
+                        e.time,
+                        e.collision,
+                        e.ids[0],
+                        e.ids[1],
+                        e.masses[0],
+                        e.masses[1],
+                        e.pos[0],
+                        e.pos[1],
+                        e.pos[2],
+                        e.speed
+                    )?;
+                }
+                // keep the file readable while the run is still going
+                file.flush()
+            };
+            write(file).map_err(|e| FileError::Io(path.clone(), e))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.collision {
+            Collision::Merge => "absorbed",
+            _ => "bounced off",
+        };
+        write!(
+            f,
+            "step {:>8}  t {:.4e} s  particle {} ({:.4e} kg) {} particle {} ({:.4e} kg) \
+             at ({:.4e}, {:.4e}, {:.4e}) m, {:.4e} m/s",
+            self.substeps,
+            self.time,
+            self.ids[0],
+            self.masses[0],
+            what,
+            self.ids[1],
+            self.masses[1],
+            self.pos[0],
+            self.pos[1],
+            self.pos[2],
+            self.speed
+        )
+    }
+}
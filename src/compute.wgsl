@@ -11,7 +11,9 @@ struct Gpu_Info {
     matrix : mat4x4<f32>,
     particles : u32,
     motion : f32,
-    _pad : vec2<f32>,
+    massive : u32,
+    // the `softening::Kernel` code
+    softening : u32,
 };
 
 struct DataOld {
@@ -22,42 +24,335 @@ struct DataCurrent {
     data : array<Particle>,
 };
 
+struct Massive {
+    indices : array<u32>,
+};
+
+// `external::GpuPotential`, kind 0 is an unused slot
+struct Potential {
+    kind : u32,
+    center : vec4<f64>,
+    params : vec4<f64>,
+    tensor : array<vec4<f64>, 3>,
+};
+
+struct External {
+    data : array<Potential>,
+};
+
 @group(0) @binding(0) var<uniform> gpu_info : Gpu_Info;
 @group(0) @binding(1) var<storage, read> dataOld : DataOld;
 @group(0) @binding(2) var<storage, read_write> dataCurrent : DataCurrent;
+// the particles with a positive mass, in any order, see `Partition`
+@group(0) @binding(3) var<storage, read> massive : Massive;
+@group(0) @binding(4) var<storage, read> potentials : External;
 
 fn length2(v : vec3<f64>) -> f64 {
     return v.x * v.x + v.y * v.y + v.z * v.z;
 }
 
+// y³/3 + y⁵/5 + ..., atanh(y) - y to full precision for |y| <= 0.2
+fn atanh_tail(y : f64) -> f64 {
+    let y2 : f64 = y * y;
+    var term : f64 = y * y2;
+    var series : f64 = f64(0.0);
+    for (var n : u32 = 1u; n < 13u; n = n + 1u) {
+        series = series + term / f64(2u * n + 1u);
+        term = term * y2;
+    }
+    return series;
+}
+
+// the builtin log only exists for 32-bit floats, ln(1 + x) = 2 atanh(x / (2 + x))
+// for x >= 0, taking square roots of 1 + x until the series converges quickly
+fn log1p64(x : f64) -> f64 {
+    var w : f64 = f64(1.0) + x;
+    var y : f64 = x / (f64(2.0) + x);
+    var scale : f64 = f64(2.0);
+    for (var k : u32 = 0u; k < 64u && w > f64(1.5); k = k + 1u) {
+        w = sqrt(w);
+        y = (w - f64(1.0)) / (w + f64(1.0));
+        scale = scale * f64(2.0);
+    }
+    return scale * (y + atanh_tail(y));
+}
+
+// g(r) of `softening::Kernel::force` for the kernel of `gpu_info.softening`, the
+// pull towards a mass m at offset d is G m g(r) d. there are no f64 literals, so
+// the constants are written as fractions of exact f32 values
+fn softened(r : f64, calibrate : f64) -> f64 {
+    let one : f64 = f64(1.0);
+    switch gpu_info.softening {
+        // plummer
+        case 1u: {
+            let s : f64 = r * r + calibrate;
+            if (s > f64(0.0)) {
+                return one / (s * sqrt(s));
+            }
+            return f64(0.0);
+        }
+        // the cubic spline, newtonian beyond 2.8 ε
+        case 2u: {
+            let h : f64 = f64(14.0) / f64(5.0) * sqrt(calibrate);
+            if (r < h) {
+                let u : f64 = r / h;
+                let h3 : f64 = h * h * h;
+                if (u < f64(0.5)) {
+                    return (f64(32.0) / f64(3.0) + u * u * (f64(32.0) * u - f64(192.0) / f64(5.0))) / h3;
+                }
+                return (f64(64.0) / f64(3.0) - f64(48.0) * u + f64(192.0) / f64(5.0) * u * u
+                - f64(32.0) / f64(3.0) * u * u * u - one / (f64(15.0) * u * u * u)) / h3;
+            }
+        }
+        // wendland C2, newtonian beyond 3 ε
+        case 3u: {
+            let h : f64 = f64(3.0) * sqrt(calibrate);
+            if (r < h) {
+                let u : f64 = r / h;
+                return (f64(14.0) + u * u * (f64(-84.0) + u * (f64(140.0) + u * (f64(-90.0) +
+                f64(21.0) * u)))) / (h * h * h);
+            }
+        }
+        default: {}
+    }
+    if (r > f64(0.0)) {
+        return one / (r * r * r);
+    }
+    return f64(0.0);
+}
+
+// the pull towards `mass` at offset `diff` without G, m / (r² + calibrate) along the
+// direction for the original kernel
+fn pull(diff : vec3<f64>, mass : f64, calibrate : f64) -> vec3<f64> {
+    if (gpu_info.softening == 0u) {
+        return normalize(diff) * mass / (length2(diff) + calibrate);
+    }
+    return diff * (mass * softened(sqrt(length2(diff)), calibrate));
+}
+
+// the softened pull of every massive particle, without G, a pair is softened by the
+// larger calibrate of the two
+fn gravity(i : u32) -> vec3<f64> {
+    var temp : vec3<f64> = vec3<f64>(vec3<f32>(0.0, 0.0, 0.0));
+    for (var m : u32 = 0u; m < gpu_info.massive; m = m + 1u) {
+        let j : u32 = massive.indices[m];
+        if (j == i) {
+            continue;
+        }
+
+        var diff : vec3<f64> = vec3<f64>(dataOld.old[j].pos - dataOld.old[i].pos);
+        temp = temp + pull(diff, dataOld.old[j].mass, max(dataOld.old[i].calibrate,
+        dataOld.old[j].calibrate));
+    }
+    return temp;
+}
+
+// ln(1 + x) - x / (1 + x), the mass within x scale radii of an nfw halo, as a series
+// in y = x / (2 + x) at small x where the difference cancels
+fn nfw_mass(x : f64) -> f64 {
+    if (x < f64(0.5)) {
+        let y : f64 = x / (f64(2.0) + x);
+        return f64(2.0) * (y * y / (f64(1.0) + y) + atanh_tail(y));
+    }
+    return log1p64(x) - x / (f64(1.0) + x);
+}
+
+// `external::Potentials::acceleration`, G is part of the parameters
+fn external_acceleration(pos : vec3<f64>) -> vec3<f64> {
+    var acc : vec3<f64> = vec3<f64>(vec3<f32>(0.0, 0.0, 0.0));
+    for (var k : u32 = 0u; k < arrayLength(&potentials.data); k = k + 1u) {
+        let p : Potential = potentials.data[k];
+        let d : vec3<f64> = pos - p.center.xyz;
+        switch p.kind {
+            // point mass, G m and ε²
+            case 1u: {
+                let s : f64 = length2(d) + p.params.y;
+                if (s > f64(0.0)) {
+                    acc = acc - d * (p.params.x / (s * sqrt(s)));
+                }
+            }
+            // miyamoto-nagai, G m, a and b
+            case 2u: {
+                let zb : f64 = sqrt(d.z * d.z + p.params.z * p.params.z);
+                let az : f64 = p.params.y + zb;
+                let s : f64 = d.x * d.x + d.y * d.y + az * az;
+                var vertical : f64 = f64(1.0);
+                if (zb > f64(0.0)) {
+                    vertical = az / zb;
+                }
+                acc = acc - vec3<f64>(d.x, d.y, d.z * vertical) * (p.params.x / (s * sqrt(s)));
+            }
+            // logarithmic, v², core² and 1 / q²
+            case 3u: {
+                let s : f64 = p.params.y + d.x * d.x + d.y * d.y + d.z * d.z * p.params.z;
+                acc = acc - vec3<f64>(d.x, d.y, d.z * p.params.z) * (p.params.x / s);
+            }
+            // nfw, G m and the scale radius
+            case 4u: {
+                let r : f64 = sqrt(length2(d));
+                if (r > f64(0.0)) {
+                    acc = acc - d * (p.params.x * nfw_mass(r / p.params.y) / (r * r * r));
+                }
+            }
+            // tidal, the rows of the tensor
+            case 5u: {
+                acc = acc + vec3<f64>(dot(p.tensor[0].xyz, d), dot(p.tensor[1].xyz, d),
+                dot(p.tensor[2].xyz, d));
+            }
+            default: {}
+        }
+    }
+    return acc;
+}
+
 // refactor workgroup?
+// semi-implicit euler
 @compute
 @workgroup_size(256)
 fn main(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
     let i: u32 = global_invocation_id.x;
     let G: f64 = f64(6.67408e-11);
 
-    if (dataOld.old[i].mass < f64(0.0)) {
+    // the last workgroup runs past the end unless the count is a multiple of 256
+    if (i >= gpu_info.particles || dataOld.old[i].mass < f64(0.0)) {
         return;
     }
 
     // Gravity
     if (gpu_info.motion > 0.0) {
-        var temp : vec3<f64> = vec3<f64>(vec3<f32>(0.0, 0.0, 0.0));
-        for (var j : u32 = 0u; j < u32(gpu_info.particles); j = j + 1u) {
-            if (j == i) {
+        let acc : vec3<f64> = gravity(i) * G + external_acceleration(vec3<f64>(dataOld.old[i].pos));
+        dataCurrent.data[i].vel = dataCurrent.data[i].vel + vec3<f32>(acc * f64(gpu_info.motion));
+        dataCurrent.data[i].pos = dataCurrent.data[i].pos + dataCurrent.data[i].vel *
+        gpu_info.motion;
+    }
+}
+
+// leapfrog, a batch of substeps is kick_half, drift, (kick, drift)..., kick_half
+fn kick_by(i : u32, dt : f32) {
+    let G: f64 = f64(6.67408e-11);
+
+    if (i >= gpu_info.particles || dataOld.old[i].mass < f64(0.0)) {
+        return;
+    }
+
+    if (gpu_info.motion > 0.0) {
+        let acc : vec3<f64> = gravity(i) * G + external_acceleration(vec3<f64>(dataOld.old[i].pos));
+        dataCurrent.data[i].vel = dataCurrent.data[i].vel + vec3<f32>(acc * f64(dt));
+    }
+}
+
+@compute
+@workgroup_size(256)
+fn kick(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    kick_by(global_invocation_id.x, gpu_info.motion);
+}
+
+@compute
+@workgroup_size(256)
+fn kick_half(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    kick_by(global_invocation_id.x, gpu_info.motion * 0.5);
+}
+
+@compute
+@workgroup_size(256)
+fn drift(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    let i: u32 = global_invocation_id.x;
+
+    if (i >= gpu_info.particles || dataCurrent.data[i].mass < f64(0.0)) {
+        return;
+    }
+
+    if (gpu_info.motion > 0.0) {
+        dataCurrent.data[i].pos = dataCurrent.data[i].pos + dataCurrent.data[i].vel *
+        gpu_info.motion;
+    }
+}
+
+// the tiled variants below sum over the same particles as `gravity`, loading 256 of
+// them at a time into workgroup memory so each is read from the storage buffer once
+// per workgroup instead of once per invocation
+var<workgroup> tile_index : array<u32, 256>;
+var<workgroup> tile_pos : array<vec3<f32>, 256>;
+var<workgroup> tile_mass : array<f64, 256>;
+var<workgroup> tile_calibrate : array<f64, 256>;
+
+// has to be reached by every invocation of the workgroup because of the barriers,
+// including those past the end of the buffer
+fn gravity_tiled(i : u32, local : u32) -> vec3<f64> {
+    let pos : vec3<f32> = dataOld.old[min(i, gpu_info.particles - 1u)].pos;
+    let calibrate : f64 = dataOld.old[min(i, gpu_info.particles - 1u)].calibrate;
+    var temp : vec3<f64> = vec3<f64>(vec3<f32>(0.0, 0.0, 0.0));
+    for (var base : u32 = 0u; base < gpu_info.massive; base = base + 256u) {
+        if (base + local < gpu_info.massive) {
+            let j : u32 = massive.indices[base + local];
+            tile_index[local] = j;
+            tile_pos[local] = dataOld.old[j].pos;
+            tile_mass[local] = dataOld.old[j].mass;
+            tile_calibrate[local] = dataOld.old[j].calibrate;
+        }
+        workgroupBarrier();
+
+        let count : u32 = min(256u, gpu_info.massive - base);
+        for (var k : u32 = 0u; k < count; k = k + 1u) {
+            if (tile_index[k] == i) {
                 continue;
             }
-            if (dataOld.old[j].mass == f64(0.0)) {
-                break;
-            }
+            var diff : vec3<f64> = vec3<f64>(tile_pos[k] - pos);
+            temp = temp + pull(diff, tile_mass[k], max(calibrate, tile_calibrate[k]));
+        }
+        workgroupBarrier();
+    }
+    return temp;
+}
 
-            var diff : vec3<f64> = vec3<f64>(dataOld.old[j].pos - dataOld.old[i].pos);
-            temp = temp + (normalize(diff) * (dataOld.old[j].mass) / (length2(diff) +
-            dataOld.old[j].calibrate));
+@compute
+@workgroup_size(256)
+fn main_tiled(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+) {
+    let i: u32 = global_invocation_id.x;
+    let G: f64 = f64(6.67408e-11);
+
+    if (gpu_info.motion > 0.0) {
+        let temp : vec3<f64> = gravity_tiled(i, local);
+        if (i >= gpu_info.particles || dataOld.old[i].mass < f64(0.0)) {
+            return;
         }
-        dataCurrent.data[i].vel = dataCurrent.data[i].vel + vec3<f32>(temp * G * f64(gpu_info.motion));
+        let acc : vec3<f64> = temp * G + external_acceleration(vec3<f64>(dataOld.old[i].pos));
+        dataCurrent.data[i].vel = dataCurrent.data[i].vel + vec3<f32>(acc * f64(gpu_info.motion));
         dataCurrent.data[i].pos = dataCurrent.data[i].pos + dataCurrent.data[i].vel *
         gpu_info.motion;
     }
 }
+
+fn kick_tiled_by(i : u32, local : u32, dt : f32) {
+    let G: f64 = f64(6.67408e-11);
+
+    if (gpu_info.motion > 0.0) {
+        let temp : vec3<f64> = gravity_tiled(i, local);
+        if (i >= gpu_info.particles || dataOld.old[i].mass < f64(0.0)) {
+            return;
+        }
+        let acc : vec3<f64> = temp * G + external_acceleration(vec3<f64>(dataOld.old[i].pos));
+        dataCurrent.data[i].vel = dataCurrent.data[i].vel + vec3<f32>(acc * f64(dt));
+    }
+}
+
+@compute
+@workgroup_size(256)
+fn kick_tiled(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+) {
+    kick_tiled_by(global_invocation_id.x, local, gpu_info.motion);
+}
+
+@compute
+@workgroup_size(256)
+fn kick_half_tiled(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+) {
+    kick_tiled_by(global_invocation_id.x, local, gpu_info.motion * 0.5);
+}
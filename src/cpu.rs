@@ -0,0 +1,181 @@
+use {
+    crate::{
+        external::Potentials,
+        integrator::Integrator,
+        softening::Kernel,
+        solver::Forces,
+        Particle, Partition,
+    },
+    cgmath::{prelude::*, Vector3},
+    rayon::prelude::*,
+};
+
+mod adaptive;
+mod fourth_order;
+mod octree;
+pub use adaptive::{advance_adaptive, advance_block};
+use {
+    fourth_order::System,
+    octree::{Body, Octree},
+};
+
+// compute.wgsl writes G as an f32 literal before widening it, so do the same
+pub const G: f64 = 6.67408e-11_f32 as f64;
+
+// `steps` substeps, euler and leapfrog run the same passes the gpu backend
+// records in `Gpu::encode_steps`
+pub fn advance(
+    particles: &mut [Particle],
+    integrator: Integrator,
+    forces: Forces,
+    motion: f32,
+    steps: u32,
+) {
+    if motion <= 0.0 || steps == 0 {
+        return;
+    }
+    match integrator {
+        Integrator::Euler => {
+            for _ in 0..steps {
+                euler(particles, forces, motion);
+            }
+        }
+        Integrator::Leapfrog => {
+            kick(particles, forces, motion * 0.5);
+            for step in 0..steps {
+                drift(particles, motion);
+                if step + 1 == steps {
+                    kick(particles, forces, motion * 0.5);
+                } else {
+                    kick(particles, forces, motion);
+                }
+            }
+        }
+        Integrator::Rk4 | Integrator::Yoshida | Integrator::Hermite => {
+            let mut system = System::new(particles, forces);
+            system.advance(integrator, motion as f64, steps);
+            system.write_back(particles);
+        }
+    }
+}
+
+// `main` in compute.wgsl
+fn euler(particles: &mut [Particle], forces: Forces, motion: f32) {
+    let old = particles.to_vec();
+    let field = Field::new(&old, forces);
+    particles.par_iter_mut().enumerate().for_each(|(i, p)| {
+        if old[i].mass < 0.0 {
+            return;
+        }
+        let (acc, _) = field.acceleration(&old, i);
+        let vel = Vector3::from(p.vel) + (acc * motion as f64).map(|x| x as f32);
+        let pos = Vector3::from(p.pos) + vel * motion;
+        p.vel = vel.into();
+        p.pos = pos.into();
+    });
+}
+
+// `kick` and `kick_half` in compute.wgsl
+fn kick(particles: &mut [Particle], forces: Forces, dt: f32) {
+    let old = particles.to_vec();
+    let field = Field::new(&old, forces);
+    particles.par_iter_mut().enumerate().for_each(|(i, p)| {
+        if old[i].mass < 0.0 {
+            return;
+        }
+        let (acc, _) = field.acceleration(&old, i);
+        p.vel = (Vector3::from(p.vel) + (acc * dt as f64).map(|x| x as f32)).into();
+    });
+}
+
+// the acceleration of every particle, zero for inactive ones
+pub fn accelerations(particles: &[Particle], forces: Forces) -> Vec<Vector3<f64>> {
+    let field = Field::new(particles, forces);
+    (0..particles.len())
+        .into_par_iter()
+        .map(|i| {
+            if particles[i].mass < 0.0 {
+                return Vector3::zero();
+            }
+            field.acceleration(particles, i).0
+        })
+        .collect()
+}
+
+// `drift` in compute.wgsl
+fn drift(particles: &mut [Particle], dt: f32) {
+    particles.par_iter_mut().for_each(|p| {
+        if p.mass < 0.0 {
+            return;
+        }
+        p.pos = (Vector3::from(p.pos) + Vector3::from(p.vel) * dt).into();
+    });
+}
+
+// the softened pull of every massive particle, without G, and the distance to the
+// nearest one
+fn gravity(old: &[Particle], massive: &[usize], kernel: Kernel, i: usize) -> (Vector3<f64>, f64) {
+    let pos = Vector3::from(old[i].pos);
+    let mut temp = Vector3::new(0.0, 0.0, 0.0);
+    let mut nearest = f64::INFINITY;
+    for &j in massive {
+        if j == i {
+            continue;
+        }
+        let other = &old[j];
+        let diff = (Vector3::from(other.pos) - pos).map(|x| x as f64);
+        let calibrate = old[i].calibrate.max(other.calibrate);
+        temp += kernel.acceleration(diff, other.mass, calibrate);
+        nearest = nearest.min(diff.magnitude());
+    }
+    (temp, nearest)
+}
+
+// what `gravity` sums over, the massive particles one by one or a tree of them
+enum Sum {
+    Direct(Vec<usize>, Kernel),
+    Tree(Octree),
+}
+
+// the pull on the particles, their own and that of the external potentials
+struct Field {
+    sum: Sum,
+    external: Potentials,
+}
+
+impl Field {
+    fn new(old: &[Particle], forces: Forces) -> Self {
+        let massive = Partition::new(old).massive;
+        let sum = if forces.uses_tree() {
+            let bodies = massive
+                .into_iter()
+                .map(|i| Body {
+                    index: i,
+                    pos: Vector3::from(old[i].pos).map(|x| x as f64),
+                    mass: old[i].mass,
+                    calibrate: old[i].calibrate,
+                })
+                .collect();
+            Sum::Tree(Octree::new(bodies, forces))
+        } else {
+            Sum::Direct(massive, forces.kernel)
+        };
+        Self {
+            sum,
+            external: forces.external,
+        }
+    }
+
+    // the acceleration of particle i and the distance to the nearest massive one
+    fn acceleration(&self, old: &[Particle], i: usize) -> (Vector3<f64>, f64) {
+        let pos = Vector3::from(old[i].pos).map(|x| x as f64);
+        let (temp, nearest) = match &self.sum {
+            Sum::Direct(massive, kernel) => gravity(old, massive, *kernel, i),
+            Sum::Tree(tree) => tree.gravity(pos, old[i].calibrate, i),
+        };
+        if self.external.is_empty() {
+            return (temp * G, nearest);
+        }
+        (temp * G + self.external.acceleration(pos), nearest)
+    }
+}
@@ -0,0 +1,141 @@
+use {
+    super::{advance, drift, Field},
+    crate::{integrator::Integrator, solver::Forces, Particle, Partition},
+    cgmath::{prelude::*, Vector3},
+    rayon::prelude::*,
+};
+
+// `steps` substeps of the shared adaptive step, returns the time they span
+pub fn advance_adaptive(
+    particles: &mut [Particle],
+    integrator: Integrator,
+    forces: Forces,
+    max_dt: f32,
+    eta: f64,
+    steps: u32,
+) -> f64 {
+    let mut time = 0.0;
+    for _ in 0..steps {
+        let active = Partition::new(particles).active;
+        let dt = evaluate(particles, forces, &active, eta)
+            .into_iter()
+            .fold(max_dt as f64, |dt, (_, _, wanted)| dt.min(wanted)) as f32;
+        if dt <= 0.0 {
+            break;
+        }
+        advance(particles, integrator, forces, dt, 1);
+        time += dt as f64;
+    }
+    time
+}
+
+// `steps` substeps of `max_dt` with kick-drift-kick leapfrog, where a particle on
+// level k is kicked every max_dt / 2^k. Everyone drifts straight to the next time a
+// step of the finest occupied level ends, where the particles whose steps end then
+// are kicked. A particle moves to a finer level whenever its step ends, to a coarser
+// one only where the coarser step would end as well, so the levels stay synchronised.
+pub fn advance_block(
+    particles: &mut [Particle],
+    levels: &mut [u8],
+    forces: Forces,
+    max_dt: f32,
+    eta: f64,
+    max_level: u8,
+    steps: u32,
+) -> f64 {
+    if max_dt <= 0.0 || steps == 0 {
+        return 0.0;
+    }
+    let active = Partition::new(particles).active;
+    if active.is_empty() {
+        return steps as f64 * max_dt as f64;
+    }
+    // the time within a step counts in ticks of the finest possible level
+    let ticks = 1u32 << max_level;
+    let span = |level: u8| ticks >> level;
+    let step_of = |level: u8| max_dt as f64 / (1u32 << level) as f64;
+
+    // every step opens with a half kick
+    for (i, acc, wanted) in evaluate(particles, forces, &active, eta) {
+        levels[i] = level(max_dt as f64, wanted, max_level);
+        kick_one(&mut particles[i], acc, step_of(levels[i]) / 2.0);
+    }
+    for step in 0..steps {
+        let mut t = 0;
+        while t < ticks {
+            let finest = active.iter().map(|&i| levels[i]).max().unwrap_or(0);
+            let next = (t / span(finest) + 1) * span(finest);
+            drift(
+                particles,
+                (max_dt as f64 * (next - t) as f64 / ticks as f64) as f32,
+            );
+            t = next;
+            let ending: Vec<usize> = active
+                .iter()
+                .copied()
+                .filter(|&i| t % span(levels[i]) == 0)
+                .collect();
+            if ending.is_empty() {
+                continue;
+            }
+            let last = step + 1 == steps && t == ticks;
+            for (i, acc, wanted) in evaluate(particles, forces, &ending, eta) {
+                // close the finished step, then open the next one unless the batch ends
+                let mut dt = step_of(levels[i]) / 2.0;
+                let mut new = level(max_dt as f64, wanted, max_level);
+                while t % span(new) != 0 {
+                    new += 1;
+                }
+                levels[i] = new;
+                if !last {
+                    dt += step_of(new) / 2.0;
+                }
+                kick_one(&mut particles[i], acc, dt);
+            }
+        }
+    }
+    steps as f64 * max_dt as f64
+}
+
+// the acceleration of each particle and the step η √(L / |a|) it asks for, with L
+// the softening length, or the distance to the nearest massive particle without one
+fn evaluate(
+    particles: &[Particle],
+    forces: Forces,
+    indices: &[usize],
+    eta: f64,
+) -> Vec<(usize, Vector3<f64>, f64)> {
+    let field = Field::new(particles, forces);
+    indices
+        .par_iter()
+        .map(|&i| {
+            let (acc, nearest) = field.acceleration(particles, i);
+            let length = match particles[i].calibrate {
+                calibrate if calibrate > 0.0 => calibrate.sqrt(),
+                _ => nearest,
+            };
+            let magnitude = acc.magnitude();
+            let wanted = if magnitude > 0.0 {
+                eta * (length / magnitude).sqrt()
+            } else {
+                f64::INFINITY
+            };
+            (i, acc, wanted)
+        })
+        .collect()
+}
+
+// the coarsest level whose step is no longer than `wanted`
+fn level(max_dt: f64, wanted: f64, max_level: u8) -> u8 {
+    let mut level = 0;
+    let mut dt = max_dt;
+    while dt > wanted && level < max_level {
+        dt /= 2.0;
+        level += 1;
+    }
+    level
+}
+
+fn kick_one(p: &mut Particle, acc: Vector3<f64>, dt: f64) {
+    p.vel = (Vector3::from(p.vel) + (acc * dt).map(|x| x as f32)).into();
+}
@@ -0,0 +1,220 @@
+use {
+    super::{
+        octree::{Body, Octree},
+        G,
+    },
+    crate::{integrator::Integrator, solver::Forces, Particle, Partition},
+    cgmath::{prelude::*, Vector3},
+    rayon::prelude::*,
+};
+
+// Yoshida's triple jump, w1 = 1 / (2 - ∛2) and w0 = -∛2 / (2 - ∛2)
+const W1: f64 = 1.351_207_191_959_657_8;
+const W0: f64 = -1.702_414_383_919_315_3;
+const YOSHIDA_DRIFT: [f64; 4] = [W1 / 2.0, (W0 + W1) / 2.0, (W0 + W1) / 2.0, W1 / 2.0];
+const YOSHIDA_KICK: [f64; 3] = [W1, W0, W1];
+
+// the particles widened to f64 for the length of one batch of substeps
+pub struct System {
+    pos: Vec<Vector3<f64>>,
+    vel: Vec<Vector3<f64>>,
+    mass: Vec<f64>,
+    calibrate: Vec<f64>,
+    active: Vec<usize>,
+    massive: Vec<usize>,
+    forces: Forces,
+}
+
+impl System {
+    pub fn new(particles: &[Particle], forces: Forces) -> Self {
+        let partition = Partition::new(particles);
+        Self {
+            pos: particles
+                .iter()
+                .map(|p| Vector3::from(p.pos).map(|x| x as f64))
+                .collect(),
+            vel: particles
+                .iter()
+                .map(|p| Vector3::from(p.vel).map(|x| x as f64))
+                .collect(),
+            mass: particles.iter().map(|p| p.mass).collect(),
+            calibrate: particles.iter().map(|p| p.calibrate).collect(),
+            active: partition.active,
+            massive: partition.massive,
+            forces,
+        }
+    }
+
+    pub fn write_back(&self, particles: &mut [Particle]) {
+        for (i, p) in particles.iter_mut().enumerate() {
+            if self.mass[i] >= 0.0 {
+                p.pos = self.pos[i].map(|x| x as f32).into();
+                p.vel = self.vel[i].map(|x| x as f32).into();
+            }
+        }
+    }
+
+    pub fn advance(&mut self, integrator: Integrator, dt: f64, steps: u32) {
+        match integrator {
+            Integrator::Rk4 => {
+                for _ in 0..steps {
+                    self.rk4(dt);
+                }
+            }
+            Integrator::Yoshida => {
+                for _ in 0..steps {
+                    self.yoshida(dt);
+                }
+            }
+            Integrator::Hermite => {
+                let (mut acc, mut jerk) = self.acc_jerk(&self.pos, &self.vel);
+                for _ in 0..steps {
+                    (acc, jerk) = self.hermite(dt, acc, jerk);
+                }
+            }
+            Integrator::Euler | Integrator::Leapfrog => {
+                unreachable!("{:?} mirrors the gpu passes in `cpu::advance`", integrator)
+            }
+        }
+    }
+
+    fn rk4(&mut self, dt: f64) {
+        let offset = |base: &[Vector3<f64>], k: &[Vector3<f64>], h: f64| -> Vec<Vector3<f64>> {
+            base.iter().zip(k).map(|(b, k)| b + k * h).collect()
+        };
+        let k1x = self.vel.clone();
+        let k1v = self.accelerations(&self.pos);
+        let k2x = offset(&self.vel, &k1v, dt / 2.0);
+        let k2v = self.accelerations(&offset(&self.pos, &k1x, dt / 2.0));
+        let k3x = offset(&self.vel, &k2v, dt / 2.0);
+        let k3v = self.accelerations(&offset(&self.pos, &k2x, dt / 2.0));
+        let k4x = offset(&self.vel, &k3v, dt);
+        let k4v = self.accelerations(&offset(&self.pos, &k3x, dt));
+        for &i in &self.active {
+            self.pos[i] += (k1x[i] + k2x[i] * 2.0 + k3x[i] * 2.0 + k4x[i]) * (dt / 6.0);
+            self.vel[i] += (k1v[i] + k2v[i] * 2.0 + k3v[i] * 2.0 + k4v[i]) * (dt / 6.0);
+        }
+    }
+
+    fn yoshida(&mut self, dt: f64) {
+        for stage in 0..4 {
+            for &i in &self.active {
+                self.pos[i] += self.vel[i] * (YOSHIDA_DRIFT[stage] * dt);
+            }
+            if stage < 3 {
+                let acc = self.accelerations(&self.pos);
+                for &i in &self.active {
+                    self.vel[i] += acc[i] * (YOSHIDA_KICK[stage] * dt);
+                }
+            }
+        }
+    }
+
+    // predict, evaluate, correct, returns the acceleration and jerk at the end of the step
+    fn hermite(
+        &mut self,
+        dt: f64,
+        acc: Vec<Vector3<f64>>,
+        jerk: Vec<Vector3<f64>>,
+    ) -> (Vec<Vector3<f64>>, Vec<Vector3<f64>>) {
+        let mut pos = self.pos.clone();
+        let mut vel = self.vel.clone();
+        for &i in &self.active {
+            pos[i] += self.vel[i] * dt + acc[i] * (dt * dt / 2.0) + jerk[i] * (dt * dt * dt / 6.0);
+            vel[i] += acc[i] * dt + jerk[i] * (dt * dt / 2.0);
+        }
+        let (new_acc, new_jerk) = self.acc_jerk(&pos, &vel);
+        for &i in &self.active {
+            let v = self.vel[i]
+                + (acc[i] + new_acc[i]) * (dt / 2.0)
+                + (jerk[i] - new_jerk[i]) * (dt * dt / 12.0);
+            self.pos[i] +=
+                (self.vel[i] + v) * (dt / 2.0) + (acc[i] - new_acc[i]) * (dt * dt / 12.0);
+            self.vel[i] = v;
+        }
+        (new_acc, new_jerk)
+    }
+
+    // G m g(|d|) d, the same softened law as compute.wgsl, and the external pull
+    pub fn accelerations(&self, pos: &[Vector3<f64>]) -> Vec<Vector3<f64>> {
+        if self.forces.uses_tree() {
+            let bodies = self
+                .massive
+                .iter()
+                .map(|&j| Body {
+                    index: j,
+                    pos: pos[j],
+                    mass: self.mass[j],
+                    calibrate: self.calibrate[j],
+                })
+                .collect();
+            let tree = Octree::new(bodies, self.forces);
+            return (0..pos.len())
+                .into_par_iter()
+                .map(|i| {
+                    if self.mass[i] < 0.0 {
+                        return Vector3::zero();
+                    }
+                    tree.gravity(pos[i], self.calibrate[i], i).0 * G
+                        + self.forces.external.acceleration(pos[i])
+                })
+                .collect();
+        }
+        let kernel = self.forces.kernel;
+        (0..pos.len())
+            .into_par_iter()
+            .map(|i| {
+                let mut acc = Vector3::zero();
+                if self.mass[i] < 0.0 {
+                    return acc;
+                }
+                for &j in &self.massive {
+                    if j == i {
+                        continue;
+                    }
+                    let calibrate = self.calibrate[i].max(self.calibrate[j]);
+                    acc += kernel.acceleration(pos[j] - pos[i], self.mass[j], calibrate);
+                }
+                acc * G + self.forces.external.acceleration(pos[i])
+            })
+            .collect()
+    }
+
+    // the time derivative of `accelerations` along the velocities, always summed
+    // directly since the tree has no moments of the velocities
+    pub fn acc_jerk(
+        &self,
+        pos: &[Vector3<f64>],
+        vel: &[Vector3<f64>],
+    ) -> (Vec<Vector3<f64>>, Vec<Vector3<f64>>) {
+        let kernel = self.forces.kernel;
+        (0..pos.len())
+            .into_par_iter()
+            .map(|i| {
+                let mut acc = Vector3::zero();
+                let mut jerk = Vector3::zero();
+                if self.mass[i] < 0.0 {
+                    return (acc, jerk);
+                }
+                for &j in &self.massive {
+                    if j == i {
+                        continue;
+                    }
+                    let d = pos[j] - pos[i];
+                    let dv = vel[j] - vel[i];
+                    let r = d.magnitude();
+                    let calibrate = self.calibrate[i].max(self.calibrate[j]);
+                    let g = self.mass[j] * kernel.force(r, calibrate);
+                    let dg = self.mass[j] * kernel.force_derivative(r, calibrate);
+                    acc += d * g;
+                    jerk += dv * g + d * (dg * d.dot(dv) / r);
+                }
+                let external = &self.forces.external;
+                (
+                    acc * G + external.acceleration(pos[i]),
+                    jerk * G + external.jerk(pos[i], vel[i]),
+                )
+            })
+            .unzip()
+    }
+}
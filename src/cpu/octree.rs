@@ -0,0 +1,222 @@
+use {
+    crate::{softening::Kernel, solver::Forces},
+    cgmath::{prelude::*, Vector3},
+    std::ops::Range,
+};
+
+// nodes with at most this many bodies are not split further
+const LEAF_SIZE: usize = 8;
+// stops splitting bodies at (nearly) the same position
+const MAX_DEPTH: u32 = 32;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Body {
+    pub index: usize,
+    pub pos: Vector3<f64>,
+    pub mass: f64,
+    pub calibrate: f64,
+}
+
+// a Barnes-Hut tree over the massive particles
+pub struct Octree {
+    nodes: Vec<Node>,
+    bodies: Vec<Body>,
+    theta: f64,
+    quadrupole: bool,
+    kernel: Kernel,
+}
+
+struct Node {
+    center: Vector3<f64>,
+    half: f64,
+    bodies: Range<usize>,
+    children: Range<usize>,
+    mass: f64,
+    com: Vector3<f64>,
+    // the largest of its bodies, as a pair of particles is softened by the larger one
+    calibrate: f64,
+    // Σ m (3 x xᵀ - |x|² I) about the center of mass, as xx, yy, zz, xy, xz, yz
+    quad: [f64; 6],
+}
+
+impl Octree {
+    pub fn new(bodies: Vec<Body>, forces: Forces) -> Self {
+        let mut tree = Self {
+            nodes: Vec::new(),
+            bodies,
+            theta: forces.theta,
+            quadrupole: forces.quadrupole,
+            kernel: forces.kernel,
+        };
+        if tree.bodies.is_empty() {
+            return tree;
+        }
+        let mut min = tree.bodies[0].pos;
+        let mut max = tree.bodies[0].pos;
+        for body in &tree.bodies {
+            for k in 0..3 {
+                min[k] = min[k].min(body.pos[k]);
+                max[k] = max[k].max(body.pos[k]);
+            }
+        }
+        let extent = max - min;
+        let half = (extent.x.max(extent.y).max(extent.z) / 2.0).max(1.0);
+        tree.nodes
+            .push(Node::new((min + max) / 2.0, half, 0..tree.bodies.len()));
+        tree.build(0, 0);
+        tree
+    }
+
+    fn build(&mut self, node: usize, depth: u32) {
+        let Range { start, end } = self.nodes[node].bodies.clone();
+        if end - start > LEAF_SIZE && depth < MAX_DEPTH {
+            let center = self.nodes[node].center;
+            let half = self.nodes[node].half / 2.0;
+            self.bodies[start..end].sort_unstable_by_key(|body| octant(center, body.pos));
+            let first = self.nodes.len();
+            let mut from = start;
+            while from < end {
+                let oct = octant(center, self.bodies[from].pos);
+                let to = from
+                    + self.bodies[from..end]
+                        .iter()
+                        .take_while(|body| octant(center, body.pos) == oct)
+                        .count();
+                let offset = Vector3::new(
+                    if oct & 1 == 0 { -half } else { half },
+                    if oct & 2 == 0 { -half } else { half },
+                    if oct & 4 == 0 { -half } else { half },
+                );
+                self.nodes.push(Node::new(center + offset, half, from..to));
+                from = to;
+            }
+            let children = first..self.nodes.len();
+            for child in children.clone() {
+                self.build(child, depth + 1);
+            }
+            self.nodes[node].children = children;
+        }
+        self.moments(node);
+    }
+
+    // the multipoles of a node, from its bodies if it is a leaf or else its children
+    fn moments(&mut self, node: usize) {
+        let children = self.nodes[node].children.clone();
+        let parts: Vec<(f64, Vector3<f64>, f64, [f64; 6])> = if children.is_empty() {
+            self.bodies[self.nodes[node].bodies.clone()]
+                .iter()
+                .map(|b| (b.mass, b.pos, b.calibrate, [0.0; 6]))
+                .collect()
+        } else {
+            children
+                .map(|c| {
+                    let c = &self.nodes[c];
+                    (c.mass, c.com, c.calibrate, c.quad)
+                })
+                .collect()
+        };
+        let mass: f64 = parts.iter().map(|p| p.0).sum();
+        let com = parts.iter().fold(Vector3::zero(), |com, p| com + p.1 * p.0) / mass;
+        let calibrate = parts.iter().map(|p| p.2).fold(0.0, f64::max);
+        let mut quad = [0.0; 6];
+        if self.quadrupole {
+            for &(m, pos, _, q) in &parts {
+                // parallel axis theorem for the quadrupole
+                let x = pos - com;
+                let r2 = x.magnitude2();
+                let outer = [
+                    x.x * x.x,
+                    x.y * x.y,
+                    x.z * x.z,
+                    x.x * x.y,
+                    x.x * x.z,
+                    x.y * x.z,
+                ];
+                for k in 0..6 {
+                    let trace = if k < 3 { r2 } else { 0.0 };
+                    quad[k] += q[k] + m * (3.0 * outer[k] - trace);
+                }
+            }
+        }
+        let node = &mut self.nodes[node];
+        node.mass = mass;
+        node.com = com;
+        node.calibrate = calibrate;
+        node.quad = quad;
+    }
+
+    // the same sum as `cpu::gravity` at `pos` for a particle softened by `calibrate`,
+    // without the body `skip`, and the distance to the nearest body or node used as a whole
+    pub fn gravity(&self, pos: Vector3<f64>, calibrate: f64, skip: usize) -> (Vector3<f64>, f64) {
+        let mut temp = Vector3::zero();
+        let mut nearest = f64::INFINITY;
+        if self.nodes.is_empty() {
+            return (temp, nearest);
+        }
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let diff = node.com - pos;
+            let r = diff.magnitude();
+            let offset = pos - node.center;
+            let inside = offset.x.abs() <= node.half
+                && offset.y.abs() <= node.half
+                && offset.z.abs() <= node.half;
+            if !inside && 2.0 * node.half < self.theta * r {
+                temp += diff * (node.mass * self.kernel.force(r, calibrate.max(node.calibrate)));
+                if self.quadrupole {
+                    temp += quadrupole(&node.quad, -diff, r);
+                }
+                nearest = nearest.min(r);
+            } else if node.children.is_empty() {
+                for body in &self.bodies[node.bodies.clone()] {
+                    let diff = body.pos - pos;
+                    let r = diff.magnitude();
+                    if body.index == skip || r == 0.0 {
+                        continue;
+                    }
+                    temp +=
+                        diff * (body.mass * self.kernel.force(r, calibrate.max(body.calibrate)));
+                    nearest = nearest.min(r);
+                }
+            } else {
+                stack.extend(node.children.clone());
+            }
+        }
+        (temp, nearest)
+    }
+}
+
+impl Node {
+    fn new(center: Vector3<f64>, half: f64, bodies: Range<usize>) -> Self {
+        Self {
+            center,
+            half,
+            bodies,
+            children: 0..0,
+            mass: 0.0,
+            com: Vector3::zero(),
+            calibrate: 0.0,
+            quad: [0.0; 6],
+        }
+    }
+}
+
+fn octant(center: Vector3<f64>, pos: Vector3<f64>) -> usize {
+    (pos.x >= center.x) as usize
+        | ((pos.y >= center.y) as usize) << 1
+        | ((pos.z >= center.z) as usize) << 2
+}
+
+// -∇ of the quadrupole potential -G dᵀ Q d / (2 r⁵), d pointing from the center
+// of mass to the field point, without G and unsoftened since the node is far away
+fn quadrupole(q: &[f64; 6], d: Vector3<f64>, r: f64) -> Vector3<f64> {
+    let qd = Vector3::new(
+        q[0] * d.x + q[3] * d.y + q[4] * d.z,
+        q[3] * d.x + q[1] * d.y + q[5] * d.z,
+        q[4] * d.x + q[5] * d.y + q[2] * d.z,
+    );
+    let r2 = r * r;
+    let r5 = r2 * r2 * r;
+    qd / r5 - d * (2.5 * d.dot(qd) / (r5 * r2))
+}
@@ -0,0 +1,298 @@
+use {
+    crate::{
+        cpu::G,
+        external::Potentials,
+        scenario::{FileError, Scenario},
+        softening::Kernel,
+        Particle, Partition, Species,
+    },
+    cgmath::{prelude::*, Vector3},
+    rayon::prelude::*,
+    std::{
+        collections::BTreeMap,
+        fmt,
+        fs::File,
+        io::{self, BufWriter, Write},
+        path::PathBuf,
+    },
+};
+
+// how many f64 sums `contribution` and diagnostics.wgsl produce per particle
+pub const SUMS: usize = 13;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Energy {
+    pub kinetic: f64,
+    pub potential: f64,
+    // in the external potentials of the scenario
+    pub external: f64,
+}
+
+// totals over the massive particles, massless ones carry no energy or momentum
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Diagnostics {
+    pub mass: f64,
+    pub energy: Energy,
+    pub momentum: [f64; 3],
+    // about the origin
+    pub angular_momentum: [f64; 3],
+    pub center_of_mass: [f64; 3],
+}
+
+// the active particles of one species of one galaxy
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Group {
+    pub galaxy: u32,
+    pub species: Species,
+    pub count: usize,
+    pub mass: f64,
+    // mass weighted, or plain means for massless particles
+    pub center: [f64; 3],
+    pub velocity: [f64; 3],
+}
+
+// prints diagnostics every `every` substeps and appends them to an optional csv file
+pub struct Monitor {
+    every: u64,
+    next: u64,
+    csv: Option<(PathBuf, BufWriter<File>)>,
+}
+
+impl Energy {
+    pub fn total(&self) -> f64 {
+        self.kinetic + self.potential + self.external
+    }
+}
+
+impl Diagnostics {
+    // the sums in the order of `contribution`
+    pub fn from_sums(sums: &[f64; SUMS]) -> Self {
+        let mass = sums[0];
+        let center_of_mass = if mass > 0.0 {
+            [sums[9] / mass, sums[10] / mass, sums[11] / mass]
+        } else {
+            [0.0; 3]
+        };
+        Self {
+            mass,
+            energy: Energy {
+                kinetic: sums[1],
+                potential: sums[2],
+                external: sums[12],
+            },
+            momentum: [sums[3], sums[4], sums[5]],
+            angular_momentum: [sums[6], sums[7], sums[8]],
+            center_of_mass,
+        }
+    }
+
+    // 2K / |W|, 1 for a system in virial equilibrium
+    pub fn virial_ratio(&self) -> f64 {
+        2.0 * self.energy.kinetic / self.energy.potential.abs()
+    }
+}
+
+// the cpu counterpart of the reduction in diagnostics.wgsl, with the default kernel
+pub fn measure(particles: &[Particle]) -> Diagnostics {
+    measure_with(particles, Kernel::default(), &Potentials::NONE)
+}
+
+// the potential energy softened by `kernel`, in the `external` potentials as well
+pub fn measure_with(particles: &[Particle], kernel: Kernel, external: &Potentials) -> Diagnostics {
+    let massive = Partition::new(particles).massive;
+    let sums = massive
+        .par_iter()
+        .map(|&i| contribution(particles, &massive, kernel, external, i))
+        .reduce(
+            || [0.0; SUMS],
+            |mut a, b| {
+                for k in 0..SUMS {
+                    a[k] += b[k];
+                }
+                a
+            },
+        );
+    Diagnostics::from_sums(&sums)
+}
+
+// the particles grouped by their galaxy and species, in that order
+pub fn groups(particles: &[Particle]) -> Vec<Group> {
+    #[derive(Default)]
+    struct Sums {
+        count: usize,
+        mass: f64,
+        // of position and velocity
+        weighted: [f64; 6],
+        plain: [f64; 6],
+    }
+    let mut sums: BTreeMap<(u32, Species), Sums> = BTreeMap::new();
+    for p in particles.iter().filter(|p| p.is_active()) {
+        let sums = sums.entry((p.galaxy(), p.species())).or_default();
+        sums.count += 1;
+        sums.mass += p.mass;
+        for (k, x) in p.pos.iter().chain(&p.vel).enumerate() {
+            sums.weighted[k] += *x as f64 * p.mass;
+            sums.plain[k] += *x as f64;
+        }
+    }
+    sums.into_iter()
+        .map(|((galaxy, species), sums)| {
+            let mean = |k: usize| {
+                if sums.mass > 0.0 {
+                    sums.weighted[k] / sums.mass
+                } else {
+                    sums.plain[k] / sums.count as f64
+                }
+            };
+            Group {
+                galaxy,
+                species,
+                count: sums.count,
+                mass: sums.mass,
+                center: [0, 1, 2].map(mean),
+                velocity: [3, 4, 5].map(mean),
+            }
+        })
+        .collect()
+}
+
+// mass, kinetic energy, half the potential energy with every other massive particle,
+// momentum, angular momentum, mass weighted position and external potential energy
+// of particle i
+fn contribution(
+    particles: &[Particle],
+    massive: &[usize],
+    kernel: Kernel,
+    external: &Potentials,
+    i: usize,
+) -> [f64; SUMS] {
+    let p = &particles[i];
+    let pos = Vector3::from(p.pos).map(|x| x as f64);
+    let vel = Vector3::from(p.vel).map(|x| x as f64);
+    let mut potential = 0.0;
+    for &j in massive {
+        if j == i {
+            continue;
+        }
+        let other = &particles[j];
+        let r = (Vector3::from(other.pos) - Vector3::from(p.pos))
+            .map(|x| x as f64)
+            .magnitude();
+        let calibrate = p.calibrate.max(other.calibrate);
+        potential -= 0.5 * G * p.mass * other.mass * kernel.potential(r, calibrate);
+    }
+    let momentum = vel * p.mass;
+    let angular = pos.cross(momentum);
+    [
+        p.mass,
+        0.5 * p.mass * vel.magnitude2(),
+        potential,
+        momentum.x,
+        momentum.y,
+        momentum.z,
+        angular.x,
+        angular.y,
+        angular.z,
+        pos.x * p.mass,
+        pos.y * p.mass,
+        pos.z * p.mass,
+        p.mass * external.potential(pos),
+    ]
+}
+
+impl Monitor {
+    // None unless the scenario asks for diagnostics, every frame's substeps by default
+    pub fn for_scenario(scenario: &Scenario) -> Result<Option<Self>, FileError> {
+        match (scenario.diagnostics_every, &scenario.diagnostics_csv) {
+            (None, None) => Ok(None),
+            (every, csv) => Self::new(every.unwrap_or(scenario.substeps), csv.clone()).map(Some),
+        }
+    }
+
+    pub fn new(every: u32, csv: Option<PathBuf>) -> Result<Self, FileError> {
+        let csv = match csv {
+            Some(path) => {
+                let file = File::create(&path).map_err(|e| FileError::Io(path.clone(), e))?;
+                let mut file = BufWriter::new(file);
+                writeln!(
+                    file,
+                    "step,time,mass,kinetic,potential,external,total,virial_ratio,\
+                     px,py,pz,lx,ly,lz,com_x,com_y,com_z"
+                )
+                .map_err(|e| FileError::Io(path.clone(), e))?;
+                Some((path, file))
+            }
+            None => None,
+        };
+        Ok(Self {
+            every: every.max(1) as u64,
+            next: 0,
+            csv,
+        })
+    }
+
+    pub fn due(&self, substeps: u64) -> bool {
+        substeps >= self.next
+    }
+
+    // substeps until the next record is due, at least one
+    pub fn remaining(&self, substeps: u64) -> u32 {
+        self.next.saturating_sub(substeps).clamp(1, u32::MAX as u64) as u32
+    }
+
+    pub fn record(&mut self, substeps: u64, time: f64, d: &Diagnostics) -> Result<(), FileError> {
+        self.next = (substeps / self.every + 1) * self.every;
+        println!("step {:>8}  t {:.4e} s  {}", substeps, time, d);
+        if let Some((path, file)) = &mut self.csv {
+            let write = |file: &mut BufWriter<File>| -> io::Result<()> {
+                let e = &d.energy;
+                write!(
+                    file,
+                    "{},{:e},{:e},{:e},{:e},{:e},{:e},{:e}",
+                    substeps,
+                    time,
+                    d.mass,
+                    e.kinetic,
+                    e.potential,
+                    e.external,
+                    e.total(),
+                    d.virial_ratio()
+                )?;
+                for v in [d.momentum, d.angular_momentum, d.center_of_mass] {
+                    write!(file, ",{:e},{:e},{:e}", v[0], v[1], v[2])?;
+                }
+                writeln!(file)?;
+                // keep the file readable while the run is still going
+                file.flush()
+            };
+            write(file).map_err(|e| FileError::Io(path.clone(), e))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let magnitude = |v: [f64; 3]| Vector3::from(v).magnitude();
+        write!(
+            f,
+            "E {:.6e} J (K {:.4e}, W {:.4e}",
+            self.energy.total(),
+            self.energy.kinetic,
+            self.energy.potential
+        )?;
+        if self.energy.external != 0.0 {
+            write!(f, ", external {:.4e}", self.energy.external)?;
+        }
+        write!(
+            f,
+            ")  2K/|W| {:.4}  |P| {:.4e}  |L| {:.4e}  com ({:.4e}, {:.4e}, {:.4e})",
+            self.virial_ratio(),
+            magnitude(self.momentum),
+            magnitude(self.angular_momentum),
+            self.center_of_mass[0],
+            self.center_of_mass[1],
+            self.center_of_mass[2]
+        )
+    }
+}
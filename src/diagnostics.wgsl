@@ -0,0 +1,264 @@
+// the per particle sums of `diagnostics::contribution`, added up per workgroup,
+// the partial sums of every workgroup are added on the cpu
+struct Particle {
+    pos : vec3<f32>,
+    _pad1 : f32,
+    vel : vec3<f32>,
+    _pad : f32,
+    mass : f64,
+    calibrate : f64,
+};
+
+struct Gpu_Info {
+    matrix : mat4x4<f32>,
+    particles : u32,
+    motion : f32,
+    massive : u32,
+    // the `softening::Kernel` code
+    softening : u32,
+};
+
+struct Data {
+    data : array<Particle>,
+};
+
+struct Massive {
+    indices : array<u32>,
+};
+
+struct Partials {
+    sums : array<f64>,
+};
+
+// `external::GpuPotential`, kind 0 is an unused slot
+struct Potential {
+    kind : u32,
+    center : vec4<f64>,
+    params : vec4<f64>,
+    tensor : array<vec4<f64>, 3>,
+};
+
+struct External {
+    data : array<Potential>,
+};
+
+@group(0) @binding(0) var<uniform> gpu_info : Gpu_Info;
+@group(0) @binding(1) var<storage, read> particles : Data;
+@group(0) @binding(2) var<storage, read> massive : Massive;
+@group(0) @binding(3) var<storage, read_write> partials : Partials;
+@group(0) @binding(4) var<storage, read> potentials : External;
+
+const SUMS : u32 = 13u;
+
+var<workgroup> sums : array<f64, 832>;
+
+fn length2(v : vec3<f64>) -> f64 {
+    return v.x * v.x + v.y * v.y + v.z * v.z;
+}
+
+// the builtin atan only exists for 32-bit floats, x >= 0
+fn atan64(x : f64) -> f64 {
+    // atan(x) = π/2 - atan(1/x), then three halvings of the angle
+    // atan(x) = 2 atan(x / (1 + √(1 + x²))) leave |x| < 0.1
+    var y : f64 = x;
+    var offset : f64 = f64(0.0);
+    var sign : f64 = f64(1.0);
+    if (y > f64(1.0)) {
+        y = f64(1.0) / y;
+        // π/2 as the sum of two f32 literals, there are no f64 literals
+        offset = f64(1.5707963705062866) + f64(-4.3711388286737929e-8);
+        sign = f64(-1.0);
+    }
+    for (var k : u32 = 0u; k < 3u; k = k + 1u) {
+        y = y / (f64(1.0) + sqrt(f64(1.0) + y * y));
+    }
+    let y2 : f64 = y * y;
+    var term : f64 = y;
+    var series : f64 = f64(0.0);
+    for (var n : u32 = 0u; n < 8u; n = n + 1u) {
+        series = series + term / f64(2u * n + 1u);
+        term = -term * y2;
+    }
+    return offset + sign * f64(8.0) * series;
+}
+
+// y³/3 + y⁵/5 + ..., atanh(y) - y to full precision for |y| <= 0.2
+fn atanh_tail(y : f64) -> f64 {
+    let y2 : f64 = y * y;
+    var term : f64 = y * y2;
+    var series : f64 = f64(0.0);
+    for (var n : u32 = 1u; n < 13u; n = n + 1u) {
+        series = series + term / f64(2u * n + 1u);
+        term = term * y2;
+    }
+    return series;
+}
+
+// the builtin log only exists for 32-bit floats, ln(1 + x) = 2 atanh(x / (2 + x))
+// for x >= 0, taking square roots of 1 + x until the series converges quickly
+fn log1p64(x : f64) -> f64 {
+    var w : f64 = f64(1.0) + x;
+    var y : f64 = x / (f64(2.0) + x);
+    var scale : f64 = f64(2.0);
+    for (var k : u32 = 0u; k < 64u && w > f64(1.5); k = k + 1u) {
+        w = sqrt(w);
+        y = (w - f64(1.0)) / (w + f64(1.0));
+        scale = scale * f64(2.0);
+    }
+    return scale * (y + atanh_tail(y));
+}
+
+fn log64(x : f64) -> f64 {
+    if (x < f64(1.0)) {
+        return -log1p64(f64(1.0) / x - f64(1.0));
+    }
+    return log1p64(x - f64(1.0));
+}
+
+// `softening::Kernel::potential` for the kernel of `gpu_info.softening`, 1 / r far away
+fn softened_inverse(r : f64, calibrate : f64) -> f64 {
+    let one : f64 = f64(1.0);
+    switch gpu_info.softening {
+        // the potential of a force falling off as 1 / (r² + calibrate) instead of 1 / r²
+        case 0u: {
+            if (calibrate > f64(0.0)) {
+                let s : f64 = sqrt(calibrate);
+                return atan64(s / r) / s;
+            }
+        }
+        // plummer
+        case 1u: {
+            return one / sqrt(r * r + calibrate);
+        }
+        // the cubic spline
+        case 2u: {
+            let h : f64 = f64(14.0) / f64(5.0) * sqrt(calibrate);
+            if (r < h) {
+                let u : f64 = r / h;
+                if (u < f64(0.5)) {
+                    return -(f64(-14.0) / f64(5.0) + u * u * (f64(16.0) / f64(3.0) + u * u *
+                    (f64(32.0) / f64(5.0) * u - f64(48.0) / f64(5.0)))) / h;
+                }
+                return -(f64(-16.0) / f64(5.0) + one / (f64(15.0) * u) + u * u * (f64(32.0) /
+                f64(3.0) + u * (f64(-16.0) + u * (f64(48.0) / f64(5.0) - f64(32.0) / f64(15.0) *
+                u)))) / h;
+            }
+        }
+        // wendland C2
+        case 3u: {
+            let h : f64 = f64(3.0) * sqrt(calibrate);
+            if (r < h) {
+                let u : f64 = r / h;
+                let u2 : f64 = u * u;
+                return (f64(3.0) + u2 * (f64(-7.0) + u2 * (f64(21.0) + u * (f64(-28.0) + u *
+                (f64(15.0) - f64(3.0) * u))))) / h;
+            }
+        }
+        default: {}
+    }
+    return one / r;
+}
+
+// `external::Potentials::potential` per unit mass, G is part of the parameters
+fn external_potential(pos : vec3<f64>) -> f64 {
+    var potential : f64 = f64(0.0);
+    for (var k : u32 = 0u; k < arrayLength(&potentials.data); k = k + 1u) {
+        let p : Potential = potentials.data[k];
+        let d : vec3<f64> = pos - p.center.xyz;
+        switch p.kind {
+            // point mass, G m and ε²
+            case 1u: {
+                potential = potential - p.params.x / sqrt(length2(d) + p.params.y);
+            }
+            // miyamoto-nagai, G m, a and b
+            case 2u: {
+                let az : f64 = p.params.y + sqrt(d.z * d.z + p.params.z * p.params.z);
+                potential = potential - p.params.x / sqrt(d.x * d.x + d.y * d.y + az * az);
+            }
+            // logarithmic, v², core² and 1 / q²
+            case 3u: {
+                let s : f64 = p.params.y + d.x * d.x + d.y * d.y + d.z * d.z * p.params.z;
+                potential = potential + f64(0.5) * p.params.x * log64(s);
+            }
+            // nfw, G m and the scale radius
+            case 4u: {
+                let r : f64 = sqrt(length2(d));
+                if (r > f64(0.0)) {
+                    potential = potential - p.params.x * log1p64(r / p.params.y) / r;
+                } else {
+                    potential = potential - p.params.x / p.params.y;
+                }
+            }
+            // tidal, the rows of the tensor
+            case 5u: {
+                let pull : vec3<f64> = vec3<f64>(dot(p.tensor[0].xyz, d), dot(p.tensor[1].xyz, d),
+                dot(p.tensor[2].xyz, d));
+                potential = potential - f64(0.5) * dot(d, pull);
+            }
+            default: {}
+        }
+    }
+    return potential;
+}
+
+@compute
+@workgroup_size(64)
+fn main(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let i : u32 = global_invocation_id.x;
+    let G : f64 = f64(6.67408e-11);
+    for (var k : u32 = 0u; k < SUMS; k = k + 1u) {
+        sums[local * SUMS + k] = f64(0.0);
+    }
+
+    if (i < gpu_info.particles && particles.data[i].mass > f64(0.0)) {
+        let p : Particle = particles.data[i];
+        let pos : vec3<f64> = vec3<f64>(p.pos);
+        let vel : vec3<f64> = vec3<f64>(p.vel);
+        var potential : f64 = f64(0.0);
+        for (var m : u32 = 0u; m < gpu_info.massive; m = m + 1u) {
+            let j : u32 = massive.indices[m];
+            if (j == i) {
+                continue;
+            }
+            let r : f64 = sqrt(length2(vec3<f64>(particles.data[j].pos - p.pos)));
+            potential = potential - f64(0.5) * G * p.mass * particles.data[j].mass *
+            softened_inverse(r, max(p.calibrate, particles.data[j].calibrate));
+        }
+        let momentum : vec3<f64> = vel * p.mass;
+        let angular : vec3<f64> = cross(pos, momentum);
+        let base : u32 = local * SUMS;
+        sums[base] = p.mass;
+        sums[base + 1u] = f64(0.5) * p.mass * length2(vel);
+        sums[base + 2u] = potential;
+        sums[base + 3u] = momentum.x;
+        sums[base + 4u] = momentum.y;
+        sums[base + 5u] = momentum.z;
+        sums[base + 6u] = angular.x;
+        sums[base + 7u] = angular.y;
+        sums[base + 8u] = angular.z;
+        sums[base + 9u] = pos.x * p.mass;
+        sums[base + 10u] = pos.y * p.mass;
+        sums[base + 11u] = pos.z * p.mass;
+        sums[base + 12u] = p.mass * external_potential(pos);
+    }
+    workgroupBarrier();
+
+    for (var stride : u32 = 32u; stride > 0u; stride = stride / 2u) {
+        if (local < stride) {
+            for (var k : u32 = 0u; k < SUMS; k = k + 1u) {
+                sums[local * SUMS + k] = sums[local * SUMS + k] + sums[(local + stride) * SUMS + k];
+            }
+        }
+        workgroupBarrier();
+    }
+
+    if (local == 0u) {
+        for (var k : u32 = 0u; k < SUMS; k = k + 1u) {
+            partials.sums[workgroup_id.x * SUMS + k] = sums[k];
+        }
+    }
+}
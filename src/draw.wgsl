@@ -1,8 +1,9 @@
 struct Particle {
     pos : vec3<f32>,
-    _pad1 : f32,
+    // species in the low byte, galaxy index above
+    tag : u32,
     vel : vec3<f32>,
-    _pad : f32,
+    id : u32,
     mass : f64,
     calibrate : f64,
 };
@@ -11,9 +12,23 @@ struct Gpu_Info {
     matrix : mat4x4<f32>,
     particles : u32,
     motion : f32,
-    _pad : vec2<f32>,
+    massive : u32,
+    _pad : f32,
 };
 
+const HALO : u32 = 1u;
+const BLACK_HOLE : u32 = 5u;
+
+// one color per galaxy, repeating after six
+const PALETTE = array<vec3<f32>, 6>(
+    vec3<f32>(0.722, 0.22, 0.231),
+    vec3<f32>(0.345, 0.522, 0.635),
+    vec3<f32>(0.486, 0.639, 0.345),
+    vec3<f32>(0.859, 0.631, 0.282),
+    vec3<f32>(0.549, 0.420, 0.678),
+    vec3<f32>(0.780, 0.780, 0.780),
+);
+
 struct DataCurrent {
     data : array<Particle>,
 };
@@ -42,16 +57,20 @@ fn vs_main(
     }
 
     let glpos : vec4<f32> = gpu_info.matrix * vec4<f32>(dataCurrent.data[i].pos, 1.0);
-    pointSize = clamp(30.0 * 1E11 / glpos.z, 1.0, 20.0);
+    let species : u32 = dataCurrent.data[i].tag & 0xFFu;
+    let galaxy : u32 = dataCurrent.data[i].tag >> 8u;
+    if (species == HALO) {
+        pointSize = 1.0;
+    } else {
+        pointSize = clamp(30.0 * 1E11 / glpos.z, 1.0, 20.0);
+    }
 
-    if (dataCurrent.data[i].mass > f64(1E33)) {
+    if (species == BLACK_HOLE) {
         fragColor = vec3<f32>(0.0, 0.0, 0.0);
+    } else if (species == HALO) {
+        fragColor = PALETTE[galaxy % 6u] * 0.35;
     } else {
-        if (i < i32(gpu_info.particles) / 2 + 1) {
-            fragColor = vec3<f32>(0.722, 0.22, 0.231);
-        } else {
-            fragColor = vec3<f32>(0.345, 0.522, 0.635);
-        }
+        fragColor = PALETTE[galaxy % 6u];
     }
 
     return glpos;
@@ -0,0 +1,310 @@
+use {
+    crate::{scenario::FileError, Particle},
+    std::{
+        fs::File,
+        io::{self, BufWriter, Write},
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+// formats for external tools, particle snapshots read back by the simulator stay .ron / .json
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    // id, pos, vel, mass, species and galaxy per row, for pandas and spreadsheets
+    Csv,
+    // legacy binary vtk polydata
+    Vtk,
+    // xml vtk unstructured grid with the arrays appended as raw binary
+    Vtu,
+    // binary little endian ply
+    Ply,
+}
+
+// the u32 attributes of every particle after its mass, the species is the code of
+// a `Species`
+type Tag = fn(&Particle) -> u32;
+const TAGS: [(&str, Tag); 3] = [
+    ("species", |p| p.species() as u32),
+    ("galaxy", Particle::galaxy),
+    ("id", Particle::id),
+];
+
+// a .pvd collection of the files of a run, which paraview loads as one animation
+pub struct Series {
+    path: PathBuf,
+    entries: Vec<(f64, String)>,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+
+    // whether a .pvd collection can refer to files of this format
+    pub fn has_series(self) -> bool {
+        matches!(self, Format::Vtk | Format::Vtu)
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "vtk" => Ok(Format::Vtk),
+            "vtu" => Ok(Format::Vtu),
+            "ply" => Ok(Format::Ply),
+            _ => Err(format!(
+                "unknown export format `{}`, expected one of csv, vtk, vtu, ply",
+                s
+            )),
+        }
+    }
+}
+
+// writes the particles in the format given by the extension of `path`
+pub fn write(path: &Path, particles: &[Particle]) -> Result<(), FileError> {
+    let format = Format::from_path(path).ok_or_else(|| FileError::UnknownFormat(path.into()))?;
+    let write = |file: &mut BufWriter<File>| match format {
+        Format::Csv => csv(file, particles),
+        Format::Vtk => vtk(file, particles),
+        Format::Vtu => vtu(file, particles),
+        Format::Ply => ply(file, particles),
+    };
+    File::create(path)
+        .and_then(|file| {
+            let mut file = BufWriter::new(file);
+            write(&mut file)?;
+            file.flush()
+        })
+        .map_err(|e| FileError::Io(path.into(), e))
+}
+
+// inactive particles have a negative mass
+fn csv(file: &mut impl Write, particles: &[Particle]) -> io::Result<()> {
+    writeln!(file, "id,x,y,z,vx,vy,vz,mass,species,galaxy")?;
+    for p in particles {
+        writeln!(
+            file,
+            "{},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{},{}",
+            p.id(),
+            p.pos[0],
+            p.pos[1],
+            p.pos[2],
+            p.vel[0],
+            p.vel[1],
+            p.vel[2],
+            p.mass,
+            p.species() as u32,
+            p.galaxy()
+        )?;
+    }
+    Ok(())
+}
+
+// legacy binary files are big endian
+fn vtk(file: &mut impl Write, particles: &[Particle]) -> io::Result<()> {
+    let n = particles.len();
+    write!(
+        file,
+        "# vtk DataFile Version 3.0\nnbodysim snapshot\nBINARY\nDATASET POLYDATA\n"
+    )?;
+    writeln!(file, "POINTS {} float", n)?;
+    for p in particles {
+        for x in p.pos {
+            file.write_all(&x.to_be_bytes())?;
+        }
+    }
+    // one vertex cell per point, without cells paraview draws nothing
+    write!(file, "\nVERTICES {} {}\n", n, 2 * n)?;
+    for id in 0..n as i32 {
+        file.write_all(&1i32.to_be_bytes())?;
+        file.write_all(&id.to_be_bytes())?;
+    }
+    write!(file, "\nPOINT_DATA {}\nVECTORS velocity float\n", n)?;
+    for p in particles {
+        for x in p.vel {
+            file.write_all(&x.to_be_bytes())?;
+        }
+    }
+    write!(file, "\nSCALARS mass double 1\nLOOKUP_TABLE default\n")?;
+    for p in particles {
+        file.write_all(&p.mass.to_be_bytes())?;
+    }
+    for (name, tag) in TAGS {
+        write!(
+            file,
+            "\nSCALARS {} unsigned_int 1\nLOOKUP_TABLE default\n",
+            name
+        )?;
+        for p in particles {
+            file.write_all(&tag(p).to_be_bytes())?;
+        }
+    }
+    writeln!(file)
+}
+
+// every array is a block of the appended data, a u64 byte count followed by the
+// little endian values, the offsets count from the `_` marker
+fn vtu(file: &mut impl Write, particles: &[Particle]) -> io::Result<()> {
+    let n = particles.len() as u64;
+    let arrays: [(&str, &str, u64, u64); 9] = [
+        ("Points", "Float32", 3, 4),
+        ("velocity", "Float32", 3, 4),
+        ("mass", "Float64", 1, 8),
+        ("species", "UInt32", 1, 4),
+        ("galaxy", "UInt32", 1, 4),
+        ("id", "UInt32", 1, 4),
+        ("connectivity", "Int64", 1, 8),
+        ("offsets", "Int64", 1, 8),
+        ("types", "UInt8", 1, 1),
+    ];
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    for &(_, _, components, size) in &arrays {
+        offsets.push(offset);
+        offset += 8 + n * components * size;
+    }
+    let array = |k: usize| {
+        let (name, kind, components, _) = arrays[k];
+        format!(
+            "<DataArray type=\"{}\" Name=\"{}\" NumberOfComponents=\"{}\" \
+             format=\"appended\" offset=\"{}\"/>",
+            kind, name, components, offsets[k]
+        )
+    };
+    writeln!(file, "<?xml version=\"1.0\"?>")?;
+    writeln!(
+        file,
+        "<VTKFile type=\"UnstructuredGrid\" version=\"1.0\" \
+         byte_order=\"LittleEndian\" header_type=\"UInt64\">"
+    )?;
+    writeln!(file, "  <UnstructuredGrid>")?;
+    writeln!(
+        file,
+        "    <Piece NumberOfPoints=\"{}\" NumberOfCells=\"{}\">",
+        n, n
+    )?;
+    writeln!(
+        file,
+        "      <Points>\n        {}\n      </Points>",
+        array(0)
+    )?;
+    writeln!(
+        file,
+        "      <PointData Vectors=\"velocity\" Scalars=\"mass\">"
+    )?;
+    for k in 1..6 {
+        writeln!(file, "        {}", array(k))?;
+    }
+    writeln!(file, "      </PointData>\n      <Cells>")?;
+    for k in 6..9 {
+        writeln!(file, "        {}", array(k))?;
+    }
+    writeln!(file, "      </Cells>\n    </Piece>\n  </UnstructuredGrid>")?;
+    write!(file, "  <AppendedData encoding=\"raw\">\n   _")?;
+
+    let header = |file: &mut dyn Write, components: u64, size: u64| {
+        file.write_all(&(n * components * size).to_le_bytes())
+    };
+    header(file, 3, 4)?;
+    for p in particles {
+        for x in p.pos {
+            file.write_all(&x.to_le_bytes())?;
+        }
+    }
+    header(file, 3, 4)?;
+    for p in particles {
+        for x in p.vel {
+            file.write_all(&x.to_le_bytes())?;
+        }
+    }
+    header(file, 1, 8)?;
+    for p in particles {
+        file.write_all(&p.mass.to_le_bytes())?;
+    }
+    for (_, tag) in TAGS {
+        header(file, 1, 4)?;
+        for p in particles {
+            file.write_all(&tag(p).to_le_bytes())?;
+        }
+    }
+    // the connectivity is 0..n, the offsets 1..=n
+    for first in [0u64, 1] {
+        header(file, 1, 8)?;
+        for i in first..first + n {
+            file.write_all(&i.to_le_bytes())?;
+        }
+    }
+    // VTK_VERTEX
+    header(file, 1, 1)?;
+    for _ in 0..n {
+        file.write_all(&[1u8])?;
+    }
+    writeln!(file, "\n  </AppendedData>\n</VTKFile>")
+}
+
+fn ply(file: &mut impl Write, particles: &[Particle]) -> io::Result<()> {
+    write!(
+        file,
+        "ply\nformat binary_little_endian 1.0\ncomment nbodysim snapshot\n\
+         element vertex {}\n\
+         property float x\nproperty float y\nproperty float z\n\
+         property float vx\nproperty float vy\nproperty float vz\n\
+         property double mass\nproperty uint species\nproperty uint galaxy\n\
+         property uint id\nend_header\n",
+        particles.len()
+    )?;
+    for p in particles {
+        for x in p.pos.iter().chain(&p.vel) {
+            file.write_all(&x.to_le_bytes())?;
+        }
+        file.write_all(&p.mass.to_le_bytes())?;
+        for (_, tag) in TAGS {
+            file.write_all(&tag(p).to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+impl Series {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            entries: Vec::new(),
+        }
+    }
+
+    // adds a file at simulated `time` and rewrites the collection, so it also loads
+    // while the run is still going, `file` is relative to the collection
+    pub fn push(&mut self, time: f64, file: &Path) -> Result<(), FileError> {
+        self.entries.push((time, file.display().to_string()));
+        let write = |file: &mut BufWriter<File>| -> io::Result<()> {
+            writeln!(file, "<?xml version=\"1.0\"?>")?;
+            writeln!(file, "<VTKFile type=\"Collection\" version=\"0.1\">")?;
+            writeln!(file, "  <Collection>")?;
+            for (time, name) in &self.entries {
+                writeln!(
+                    file,
+                    "    <DataSet timestep=\"{:e}\" part=\"0\" file=\"{}\"/>",
+                    time,
+                    escape(name)
+                )?;
+            }
+            writeln!(file, "  </Collection>\n</VTKFile>")?;
+            file.flush()
+        };
+        File::create(&self.path)
+            .and_then(|file| write(&mut BufWriter::new(file)))
+            .map_err(|e| FileError::Io(self.path.clone(), e))
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,246 @@
+use {
+    crate::cpu::G,
+    bytemuck::Zeroable,
+    cgmath::{prelude::*, Vector3},
+    serde::{Deserialize, Serialize},
+};
+
+// at most this many per scenario, so `Forces` stays Copy
+pub const MAX_POTENTIALS: usize = 8;
+
+// a fixed field the particles move in on top of their own gravity, for following
+// stars through a galaxy without simulating it. positions are in m
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Potential {
+    // -G m / √(r² + ε²)
+    PointMass {
+        pos: [f64; 3],
+        mass: f64,
+        #[serde(default)]
+        epsilon: f64,
+    },
+    // Miyamoto & Nagai's disk in the plane z = pos.z, -G m / √(R² + (a + √(z² + b²))²)
+    // with the scale length a and the scale height b
+    MiyamotoNagai {
+        pos: [f64; 3],
+        mass: f64,
+        a: f64,
+        b: f64,
+    },
+    // ½ v² ln(core² + R² + z² / q²), a flat rotation curve at `speed` in m/s outside
+    // the core, flattened along z by q
+    Logarithmic {
+        pos: [f64; 3],
+        speed: f64,
+        core: f64,
+        #[serde(default = "round")]
+        flattening: f64,
+    },
+    // -G m ln(1 + r / scale) / r, m is 4π ρ₀ scale³ and the mass within r is
+    // m (ln(1 + x) - x / (1 + x))
+    Nfw {
+        pos: [f64; 3],
+        mass: f64,
+        scale: f64,
+    },
+    // the tidal acceleration T (x - pos) of a distant host, T symmetric in 1/s²,
+    // -½ (x - pos)ᵀ T (x - pos)
+    Tidal {
+        pos: [f64; 3],
+        tensor: [[f64; 3]; 3],
+    },
+}
+
+// the potentials of a scenario
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Potentials([Option<Potential>; MAX_POTENTIALS]);
+
+// a `Potential` as compute.wgsl and diagnostics.wgsl see it, kind 0 is an unused slot
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct GpuPotential {
+    kind: u32,
+    _pad: [u32; 7],
+    center: [f64; 4],
+    params: [f64; 4],
+    tensor: [[f64; 4]; 3],
+}
+
+fn round() -> f64 {
+    1.0
+}
+
+impl Potential {
+    fn pos(&self) -> Vector3<f64> {
+        match *self {
+            Potential::PointMass { pos, .. }
+            | Potential::MiyamotoNagai { pos, .. }
+            | Potential::Logarithmic { pos, .. }
+            | Potential::Nfw { pos, .. }
+            | Potential::Tidal { pos, .. } => pos.into(),
+        }
+    }
+
+    pub fn acceleration(&self, pos: Vector3<f64>) -> Vector3<f64> {
+        let d = pos - self.pos();
+        match *self {
+            Potential::PointMass { mass, epsilon, .. } => {
+                let s = d.magnitude2() + epsilon * epsilon;
+                if s > 0.0 {
+                    -d * (G * mass / (s * s.sqrt()))
+                } else {
+                    Vector3::zero()
+                }
+            }
+            Potential::MiyamotoNagai { mass, a, b, .. } => {
+                let zb = (d.z * d.z + b * b).sqrt();
+                let s = d.x * d.x + d.y * d.y + (a + zb) * (a + zb);
+                let pull = G * mass / (s * s.sqrt());
+                let vertical = if zb > 0.0 { (a + zb) / zb } else { 1.0 };
+                -Vector3::new(d.x, d.y, d.z * vertical) * pull
+            }
+            Potential::Logarithmic {
+                speed,
+                core,
+                flattening,
+                ..
+            } => {
+                let q2 = flattening * flattening;
+                let s = core * core + d.x * d.x + d.y * d.y + d.z * d.z / q2;
+                -Vector3::new(d.x, d.y, d.z / q2) * (speed * speed / s)
+            }
+            Potential::Nfw { mass, scale, .. } => {
+                let r = d.magnitude();
+                if r > 0.0 {
+                    let x = r / scale;
+                    -d * (G * mass * (libm::log1p(x) - x / (1.0 + x)) / (r * r * r))
+                } else {
+                    Vector3::zero()
+                }
+            }
+            Potential::Tidal { tensor, .. } => {
+                let row = |k: usize| Vector3::from(tensor[k]).dot(d);
+                Vector3::new(row(0), row(1), row(2))
+            }
+        }
+    }
+
+    // per unit mass
+    pub fn potential(&self, pos: Vector3<f64>) -> f64 {
+        let d = pos - self.pos();
+        match *self {
+            Potential::PointMass { mass, epsilon, .. } => {
+                -G * mass / (d.magnitude2() + epsilon * epsilon).sqrt()
+            }
+            Potential::MiyamotoNagai { mass, a, b, .. } => {
+                let zb = (d.z * d.z + b * b).sqrt();
+                -G * mass / (d.x * d.x + d.y * d.y + (a + zb) * (a + zb)).sqrt()
+            }
+            Potential::Logarithmic {
+                speed,
+                core,
+                flattening,
+                ..
+            } => {
+                let s = core * core + d.x * d.x + d.y * d.y + d.z * d.z / (flattening * flattening);
+                0.5 * speed * speed * s.ln()
+            }
+            Potential::Nfw { mass, scale, .. } => {
+                let r = d.magnitude();
+                if r > 0.0 {
+                    -G * mass * libm::log1p(r / scale) / r
+                } else {
+                    -G * mass / scale
+                }
+            }
+            Potential::Tidal { tensor, .. } => {
+                let row = |k: usize| Vector3::from(tensor[k]).dot(d);
+                -0.5 * d.dot(Vector3::new(row(0), row(1), row(2)))
+            }
+        }
+    }
+
+    fn to_gpu(self) -> GpuPotential {
+        let mut gpu = GpuPotential::zeroed();
+        let pos = self.pos();
+        gpu.center = [pos.x, pos.y, pos.z, 0.0];
+        // G is folded into the parameters
+        let (kind, params) = match self {
+            Potential::PointMass { mass, epsilon, .. } => (1, [G * mass, epsilon * epsilon, 0.0]),
+            Potential::MiyamotoNagai { mass, a, b, .. } => (2, [G * mass, a, b]),
+            Potential::Logarithmic {
+                speed,
+                core,
+                flattening,
+                ..
+            } => (
+                3,
+                [speed * speed, core * core, 1.0 / (flattening * flattening)],
+            ),
+            Potential::Nfw { mass, scale, .. } => (4, [G * mass, scale, 0.0]),
+            Potential::Tidal { tensor, .. } => {
+                for (row, from) in gpu.tensor.iter_mut().zip(tensor) {
+                    row[..3].copy_from_slice(&from);
+                }
+                (5, [0.0; 3])
+            }
+        };
+        gpu.kind = kind;
+        gpu.params[..3].copy_from_slice(&params);
+        gpu
+    }
+}
+
+impl Potentials {
+    pub const NONE: Self = Self([None; MAX_POTENTIALS]);
+
+    // the first `MAX_POTENTIALS` of them, `Scenario::validate` rejects any more
+    pub fn new(potentials: &[Potential]) -> Self {
+        let mut list = [None; MAX_POTENTIALS];
+        for (slot, potential) in list.iter_mut().zip(potentials) {
+            *slot = Some(*potential);
+        }
+        Self(list)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Potential> {
+        self.0.iter().flatten()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0[0].is_none()
+    }
+
+    pub fn acceleration(&self, pos: Vector3<f64>) -> Vector3<f64> {
+        self.iter()
+            .fold(Vector3::zero(), |acc, p| acc + p.acceleration(pos))
+    }
+
+    // the time derivative of the acceleration along `vel`, by central differences
+    // over a millionth of the distance to each potential's center
+    pub fn jerk(&self, pos: Vector3<f64>, vel: Vector3<f64>) -> Vector3<f64> {
+        let speed = vel.magnitude();
+        if speed == 0.0 {
+            return Vector3::zero();
+        }
+        self.iter().fold(Vector3::zero(), |jerk, p| {
+            let h = 1e-6 * (pos - p.pos()).magnitude().max(1.0) / speed;
+            jerk + (p.acceleration(pos + vel * h) - p.acceleration(pos - vel * h)) / (2.0 * h)
+        })
+    }
+
+    // per unit mass
+    pub fn potential(&self, pos: Vector3<f64>) -> f64 {
+        self.iter().map(|p| p.potential(pos)).sum()
+    }
+
+    // the storage buffer of compute.wgsl and diagnostics.wgsl, one unused slot if
+    // there are none since bindings cannot be empty
+    pub fn to_gpu(&self) -> Vec<GpuPotential> {
+        let mut gpu: Vec<GpuPotential> = self.iter().map(|p| p.to_gpu()).collect();
+        if gpu.is_empty() {
+            gpu.push(GpuPotential::zeroed());
+        }
+        gpu
+    }
+}
@@ -0,0 +1,529 @@
+use {
+    crate::{assign_ids, scenario::FileError, Particle, Species},
+    serde::{Deserialize, Serialize},
+    std::{
+        fs,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+// a kiloparsec, 10^10 solar masses and a kilometre per second in si units,
+// the internal units gadget uses by default
+const KPC: f64 = 3.085678e19;
+const MASS_1E10_SUN: f64 = 1.989e40;
+const KM_PER_S: f64 = 1e3;
+
+// the header record is always this long, padded with zeros
+const HEADER_SIZE: usize = 256;
+// gadget particle types, gas, halo, disk, bulge, stars and boundary
+pub const TYPES: usize = 6;
+// a `Species` is written as the type of its code, only bodies would be gas, which
+// gadget treats as sph particles, so massive bodies are written as boundary and
+// massless ones as disk particles. gas is read back as bodies and boundary particles
+// as black holes
+const MASSLESS_TYPE: usize = 2;
+const MASSIVE_TYPE: usize = 5;
+
+// format 1 is a sequence of fortran records, format 2 puts a record with a
+// four character label and the size of the next block in front of each of them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    One,
+    Two,
+}
+
+// the si value of one internal unit of a snapshot
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Units {
+    pub length: f64,
+    pub mass: f64,
+    pub velocity: f64,
+    // lengths and masses are in units of 1/h, divided by the HubbleParam of the header
+    pub little_h: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Header {
+    // particles of each type in this file
+    pub npart: [u32; TYPES],
+    // the mass of every particle of a type, 0 if they are listed in the mass block
+    pub mass: [f64; TYPES],
+    // the time, or the scale factor in cosmological runs
+    pub time: f64,
+    pub redshift: f64,
+    pub flag_sfr: i32,
+    pub flag_feedback: i32,
+    // particles of each type over all files, the low 32 bits
+    pub npart_total: [u32; TYPES],
+    pub flag_cooling: i32,
+    pub num_files: i32,
+    pub box_size: f64,
+    pub omega0: f64,
+    pub omega_lambda: f64,
+    pub hubble_param: f64,
+    pub flag_stellarage: i32,
+    pub flag_metals: i32,
+    pub npart_total_high_word: [u32; TYPES],
+    pub flag_entropy_instead_u: i32,
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Self {
+            length: KPC,
+            mass: MASS_1E10_SUN,
+            velocity: KM_PER_S,
+            little_h: false,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" => Ok(Format::One),
+            "2" => Ok(Format::Two),
+            _ => Err(format!("unknown gadget format `{}`, expected 1 or 2", s)),
+        }
+    }
+}
+
+impl Units {
+    fn scale(&self, header: &Header) -> (f64, f64) {
+        let h = if self.little_h && header.hubble_param > 0.0 {
+            header.hubble_param
+        } else {
+            1.0
+        };
+        (self.length / h, self.mass / h)
+    }
+}
+
+// whether the file starts like a gadget snapshot of either format and byte order
+pub fn is_gadget(path: &Path) -> bool {
+    let mut first = [0; 4];
+    let read = fs::File::open(path).and_then(|mut file| {
+        use std::io::Read;
+        file.read_exact(&mut first)
+    });
+    read.is_ok()
+        && [u32::from_le_bytes(first), u32::from_be_bytes(first)]
+            .iter()
+            .any(|&marker| marker == HEADER_SIZE as u32 || marker == 8)
+}
+
+// reads every file of a snapshot, `snap.0` also reads `snap.1` and onwards, velocities
+// are taken as they are stored, gadget ids are kept as far as they fit 32 bits and the
+// particles stay in file order, which is grouped by type
+pub fn read(
+    path: &Path,
+    units: &Units,
+    calibrate: f64,
+) -> Result<(Header, Vec<Particle>), FileError> {
+    let (header, mut particles) = read_file(path, units, calibrate)?;
+    if header.num_files > 1 {
+        let name = path.to_string_lossy();
+        let base = name.strip_suffix(".0").ok_or_else(|| {
+            FileError::Invalid(
+                path.into(),
+                format!(
+                    "split over {} files, expected the name of the first one ending in .0",
+                    header.num_files
+                ),
+            )
+        })?;
+        for k in 1..header.num_files {
+            let (_, more) = read_file(&PathBuf::from(format!("{}.{}", base, k)), units, calibrate)?;
+            particles.extend(more);
+        }
+    }
+    assign_ids(&mut particles);
+    Ok((header, particles))
+}
+
+fn read_file(
+    path: &Path,
+    units: &Units,
+    calibrate: f64,
+) -> Result<(Header, Vec<Particle>), FileError> {
+    let bytes = fs::read(path).map_err(|e| FileError::Io(path.into(), e))?;
+    let invalid = |message: String| FileError::Invalid(path.into(), message);
+    let blocks =
+        Blocks::new(&bytes).ok_or_else(|| invalid("not a gadget-2 snapshot".to_string()))?;
+    let mut blocks =
+        blocks.map(|block| block.ok_or_else(|| invalid("truncated or corrupt record".to_string())));
+    let mut next = |label: &[u8; 4]| -> Result<Record, FileError> {
+        // format 2 files may hold blocks we do not need in between
+        loop {
+            let block = blocks.next().ok_or_else(|| {
+                invalid(format!(
+                    "no {} block",
+                    String::from_utf8_lossy(label).trim()
+                ))
+            })??;
+            if block.label.is_none_or(|l| &l == label) {
+                return Ok(block);
+            }
+        }
+    };
+
+    let head = next(b"HEAD")?;
+    if head.data.len() != HEADER_SIZE {
+        return Err(invalid(format!(
+            "header of {} bytes, expected {}",
+            head.data.len(),
+            HEADER_SIZE
+        )));
+    }
+    let header = Header::parse(&mut Fields::new(head.data, head.big_endian));
+    let n: usize = header.npart.iter().map(|&n| n as usize).sum();
+    let (length, mass_unit) = units.scale(&header);
+
+    let floats = |record: &Record, per: usize, what: &str| -> Result<Vec<f64>, FileError> {
+        let mut fields = Fields::new(record.data, record.big_endian);
+        if record.data.len() == n * per * 4 {
+            Ok((0..n * per).map(|_| fields.f32() as f64).collect())
+        } else if record.data.len() == n * per * 8 {
+            Ok((0..n * per).map(|_| fields.f64()).collect())
+        } else {
+            Err(invalid(format!(
+                "{} block of {} bytes for {} particles",
+                what,
+                record.data.len(),
+                n
+            )))
+        }
+    };
+    let pos = floats(&next(b"POS ")?, 3, "position")?;
+    let vel = floats(&next(b"VEL ")?, 3, "velocity")?;
+    let ids = next(b"ID  ")?;
+    let mut fields = Fields::new(ids.data, ids.big_endian);
+    let ids: Vec<u32> = if ids.data.len() == n * 4 {
+        (0..n).map(|_| fields.u32()).collect()
+    } else if ids.data.len() == n * 8 {
+        // the upper words are only set for more than 4 billion particles
+        (0..n).map(|_| fields.u64() as u32).collect()
+    } else {
+        return Err(invalid(format!(
+            "id block of {} bytes for {} particles",
+            ids.data.len(),
+            n
+        )));
+    };
+    // only types without a mass in the header are listed in the mass block
+    let listed: usize = (0..TYPES)
+        .filter(|&t| header.mass[t] == 0.0)
+        .map(|t| header.npart[t] as usize)
+        .sum();
+    let listed = if listed > 0 {
+        let record = next(b"MASS")?;
+        let mut fields = Fields::new(record.data, record.big_endian);
+        if record.data.len() == listed * 4 {
+            (0..listed).map(|_| fields.f32() as f64).collect()
+        } else if record.data.len() == listed * 8 {
+            (0..listed).map(|_| fields.f64()).collect()
+        } else {
+            return Err(invalid(format!(
+                "mass block of {} bytes for {} particles",
+                record.data.len(),
+                listed
+            )));
+        }
+    } else {
+        Vec::new()
+    };
+
+    let mut particles = Vec::with_capacity(n);
+    let mut listed = listed.into_iter();
+    let mut i = 0;
+    for t in 0..TYPES {
+        for _ in 0..header.npart[t] {
+            let mass = if header.mass[t] == 0.0 {
+                listed.next().unwrap_or(0.0)
+            } else {
+                header.mass[t]
+            };
+            let vector = |v: &[f64], scale: f64| [0, 1, 2].map(|k| (v[3 * i + k] * scale) as f32);
+            particles.push(
+                Particle::new(
+                    vector(&pos, length),
+                    vector(&vel, units.velocity),
+                    mass * mass_unit,
+                    calibrate,
+                )
+                .with_species(Species::from_code(t as u32))
+                .with_id(ids[i]),
+            );
+            i += 1;
+        }
+    }
+    Ok((header, particles))
+}
+
+// writes a single little endian file with the masses in the mass block, inactive
+// particles are left out, `time` in seconds is stored in internal units. bodies have
+// no type of their own, massive ones are read back as black holes and massless ones
+// as disk particles
+pub fn write(
+    path: &Path,
+    particles: &[Particle],
+    format: Format,
+    units: &Units,
+    time: f64,
+) -> Result<(), FileError> {
+    let kind = |p: &Particle| match p.species() {
+        Species::Body if p.mass > 0.0 => MASSIVE_TYPE,
+        Species::Body => MASSLESS_TYPE,
+        species => species as usize,
+    };
+    // the file lists the particles grouped by type
+    let ordered: Vec<&Particle> = (0..TYPES)
+        .flat_map(|t| {
+            particles
+                .iter()
+                .filter(move |p| p.is_active() && kind(p) == t)
+        })
+        .collect();
+    let mut header = Header {
+        time: time * units.velocity / units.length,
+        num_files: 1,
+        hubble_param: 1.0,
+        ..Header::default()
+    };
+    for p in &ordered {
+        header.npart[kind(p)] += 1;
+    }
+    header.npart_total = header.npart;
+
+    let mut bytes = Vec::new();
+    let mut block = |label: &[u8; 4], data: Vec<u8>| {
+        if format == Format::Two {
+            bytes.extend_from_slice(&8u32.to_le_bytes());
+            bytes.extend_from_slice(label);
+            bytes.extend_from_slice(&(data.len() as u32 + 8).to_le_bytes());
+            bytes.extend_from_slice(&8u32.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&data);
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    };
+    block(b"HEAD", header.to_bytes());
+    let floats = |values: &mut dyn Iterator<Item = f64>| -> Vec<u8> {
+        values.flat_map(|x| (x as f32).to_le_bytes()).collect()
+    };
+    block(
+        b"POS ",
+        floats(
+            &mut ordered
+                .iter()
+                .flat_map(|p| p.pos.map(|x| x as f64 / units.length)),
+        ),
+    );
+    block(
+        b"VEL ",
+        floats(
+            &mut ordered
+                .iter()
+                .flat_map(|p| p.vel.map(|x| x as f64 / units.velocity)),
+        ),
+    );
+    block(
+        b"ID  ",
+        ordered.iter().flat_map(|p| p.id.to_le_bytes()).collect(),
+    );
+    block(
+        b"MASS",
+        floats(&mut ordered.iter().map(|p| p.mass / units.mass)),
+    );
+    fs::write(path, bytes).map_err(|e| FileError::Io(path.into(), e))
+}
+
+impl Header {
+    fn parse(fields: &mut Fields) -> Self {
+        Header {
+            npart: [(); TYPES].map(|_| fields.u32()),
+            mass: [(); TYPES].map(|_| fields.f64()),
+            time: fields.f64(),
+            redshift: fields.f64(),
+            flag_sfr: fields.u32() as i32,
+            flag_feedback: fields.u32() as i32,
+            npart_total: [(); TYPES].map(|_| fields.u32()),
+            flag_cooling: fields.u32() as i32,
+            num_files: fields.u32() as i32,
+            box_size: fields.f64(),
+            omega0: fields.f64(),
+            omega_lambda: fields.f64(),
+            hubble_param: fields.f64(),
+            flag_stellarage: fields.u32() as i32,
+            flag_metals: fields.u32() as i32,
+            npart_total_high_word: [(); TYPES].map(|_| fields.u32()),
+            flag_entropy_instead_u: fields.u32() as i32,
+        }
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        let u32s = |bytes: &mut Vec<u8>, values: &[u32]| {
+            values
+                .iter()
+                .for_each(|v| bytes.extend_from_slice(&v.to_le_bytes()))
+        };
+        u32s(&mut bytes, &self.npart);
+        self.mass
+            .iter()
+            .for_each(|m| bytes.extend_from_slice(&m.to_le_bytes()));
+        for x in [self.time, self.redshift] {
+            bytes.extend_from_slice(&x.to_le_bytes());
+        }
+        u32s(
+            &mut bytes,
+            &[self.flag_sfr as u32, self.flag_feedback as u32],
+        );
+        u32s(&mut bytes, &self.npart_total);
+        u32s(
+            &mut bytes,
+            &[self.flag_cooling as u32, self.num_files as u32],
+        );
+        for x in [
+            self.box_size,
+            self.omega0,
+            self.omega_lambda,
+            self.hubble_param,
+        ] {
+            bytes.extend_from_slice(&x.to_le_bytes());
+        }
+        u32s(
+            &mut bytes,
+            &[self.flag_stellarage as u32, self.flag_metals as u32],
+        );
+        u32s(&mut bytes, &self.npart_total_high_word);
+        u32s(&mut bytes, &[self.flag_entropy_instead_u as u32]);
+        bytes.resize(HEADER_SIZE, 0);
+        bytes
+    }
+}
+
+struct Record<'a> {
+    label: Option<[u8; 4]>,
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+// the records of a file, None for a record whose markers do not match
+struct Blocks<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    format: Format,
+    big_endian: bool,
+}
+
+impl<'a> Blocks<'a> {
+    fn new(bytes: &'a [u8]) -> Option<Self> {
+        let first: [u8; 4] = bytes.get(..4)?.try_into().ok()?;
+        let (marker, big_endian) = match u32::from_le_bytes(first) {
+            marker if marker as usize == HEADER_SIZE || marker == 8 => (marker, false),
+            _ => (u32::from_be_bytes(first), true),
+        };
+        let format = match marker as usize {
+            HEADER_SIZE => Format::One,
+            8 => Format::Two,
+            _ => return None,
+        };
+        Some(Self {
+            bytes,
+            offset: 0,
+            format,
+            big_endian,
+        })
+    }
+
+    fn record(&mut self) -> Option<&'a [u8]> {
+        let u32_at = |offset: usize| -> Option<usize> {
+            let bytes: [u8; 4] = self.bytes.get(offset..offset + 4)?.try_into().ok()?;
+            Some(if self.big_endian {
+                u32::from_be_bytes(bytes)
+            } else {
+                u32::from_le_bytes(bytes)
+            } as usize)
+        };
+        let size = u32_at(self.offset)?;
+        let start = self.offset + 4;
+        let end = start.checked_add(size)?;
+        if u32_at(end)? != size {
+            return None;
+        }
+        self.offset = end + 4;
+        self.bytes.get(start..end)
+    }
+}
+
+impl<'a> Iterator for Blocks<'a> {
+    type Item = Option<Record<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.bytes.len() {
+            return None;
+        }
+        let label = match self.format {
+            Format::One => None,
+            Format::Two => match self.record() {
+                Some(label) if label.len() == 8 => Some([label[0], label[1], label[2], label[3]]),
+                _ => return Some(None),
+            },
+        };
+        Some(self.record().map(|data| Record {
+            label,
+            data,
+            big_endian: self.big_endian,
+        }))
+    }
+}
+
+// reads the fields of a record in order, past its end as zeros
+struct Fields<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    big_endian: bool,
+}
+
+impl<'a> Fields<'a> {
+    fn new(bytes: &'a [u8], big_endian: bool) -> Self {
+        Self {
+            bytes,
+            offset: 0,
+            big_endian,
+        }
+    }
+
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let mut bytes = [0; N];
+        if let Some(slice) = self.bytes.get(self.offset..self.offset + N) {
+            bytes.copy_from_slice(slice);
+        }
+        self.offset += N;
+        if self.big_endian {
+            bytes.reverse();
+        }
+        bytes
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take())
+    }
+
+    fn f32(&mut self) -> f32 {
+        f32::from_le_bytes(self.take())
+    }
+
+    fn f64(&mut self) -> f64 {
+        f64::from_le_bytes(self.take())
+    }
+}
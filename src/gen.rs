@@ -1,84 +1,191 @@
 use {
-    crate::Particle,
+    crate::{
+        softening::{Kernel, Softening},
+        Particle, Species,
+    },
     cgmath::{
         prelude::*,
         {Point3, Vector3},
     },
-    rand::{thread_rng, Rng},
+    rand::{Rng, SeedableRng},
+    rand_chacha::ChaCha8Rng,
     std::f32::consts::PI,
 };
 
+mod disk;
+mod halo;
+mod sphere;
+pub use {
+    disk::{Bulge, Disk},
+    halo::{Halo, HaloProfile},
+    sphere::Model,
+};
+
+// the generator behind every seed, chacha8 gives the same stream on every platform
+// and, unlike StdRng, in every release of rand
+pub type SeededRng = ChaCha8Rng;
+
+// the same value `create` uses
+const G: f64 = 6.67408e-11;
+// points of the tables the speeds are drawn from
+const SPEEDS: usize = 256;
+
+pub fn seeded(seed: u64) -> SeededRng {
+    SeededRng::seed_from_u64(seed)
+}
+
+// the central mass the stars of a generated galaxy circle around, for spheres
+// their total mass
+#[derive(Clone, Copy, Debug)]
+pub struct Center {
+    pub pos: Point3<f32>,
+    pub vel: Vector3<f32>,
+    pub mass: f64,
+    // of the center and the stars, the orbital speeds are softened the same way
+    pub softening: Softening,
+}
+
+impl Center {
+    // the speed of a circular orbit at `radius` for a star of `species`, plus the pull
+    // of the halo mass within it
+    fn circular_speed(&self, species: Species, radius: f32, halo: Option<&halo::Model>) -> f32 {
+        let r = radius as f64;
+        let halo = halo.map_or(0.0, |halo| G * halo.enclosed(r) / r);
+        let calibrate = self.softening.pair(Species::BlackHole, species);
+        let pull = match self.softening.kernel {
+            // squared in f32 as it always was, the galaxies of old seeds depend on it
+            Kernel::Calibrate => G * self.mass * r / ((radius * radius) as f64 + calibrate),
+            kernel => G * self.mass * r * r * kernel.force(r, calibrate),
+        };
+        (pull + halo).sqrt() as f32
+    }
+
+    fn calibrate(&self, species: Species) -> f64 {
+        self.softening.calibrate(species)
+    }
+
+    // a massless star at `offset` from the center moving at `vel` relative to it
+    fn star(&self, species: Species, offset: Vector3<f32>, vel: Vector3<f32>) -> Particle {
+        Particle::new(
+            (self.pos + offset).into(),
+            (self.vel + vel).into(),
+            0.0,
+            self.calibrate(species),
+        )
+        .with_species(species)
+    }
+}
+
 pub fn create(
-    angle: f32,
-    normal: Vector3<f32>,
     particles: &mut Vec<Particle>,
-    calibrate: f64,
-    center_pos: Point3<f32>,
-    center_vel: Vector3<f32>,
-    center_mass: f64,
+    center: &Center,
+    normal: Vector3<f32>,
+    angle: f32,
     radius: f32,
 ) {
     // normalize(cross(N, T')), T' is arbitrary vector
     let tangent: Vector3<f32> = normal.cross(Vector3::new(normal.z, normal.y, normal.x));
     // cross(N, T) for movement
-    let orthogonal: Vector3<f32> = tangent * angle.sin() + normal.cross(tangent) * angle.cos();
+    // libm instead of the platform's sin and cos, which may round differently
+    let orthogonal: Vector3<f32> =
+        tangent * libm::sinf(angle) + normal.cross(tangent) * libm::cosf(angle);
     let movement: Vector3<f32> = orthogonal.cross(normal).normalize();
     // is radius really necessary?
     // pos = center + offset
-    let pos: Point3<f32> = center_pos + orthogonal * radius;
-    let gravity: f64 = 6.67408e-11;
-    // gravitational acceleration formula
-    // see if you can get rid of the calibrate value and if it still works well
-    let speed: f32 = (gravity * center_mass * radius as f64
-        / ((radius * radius) as f64 + calibrate))
-        .sqrt() as f32;
+    // gravitational acceleration formula, softened like the forces
+    let speed: f32 = center.circular_speed(Species::Disk, radius, None);
     // V' = V+g, g = gravitational acceleration * vector of movement
-    let vel = center_vel + movement * speed;
-    particles.push(Particle::new(pos.into(), vel.into(), 0.0, calibrate));
+    particles.push(center.star(Species::Disk, orthogonal * radius, movement * speed));
 }
 
 pub fn formation(
+    rng: &mut impl Rng,
     particles: &mut Vec<Particle>,
     amount: u32,
-    calibrate: f64,
-    center_pos: Point3<f32>,
-    center_vel: Vector3<f32>,
-    center_mass: f64,
+    center: &Center,
     normal: Vector3<f32>,
 ) {
     for _ in 0..amount / 5 {
-        let radius = 5e9 + thread_rng().gen_range(0.0..1e11);
-        let angle = thread_rng().gen::<f32>() * 2.0 * PI;
-        create(
-            angle,
-            normal.normalize(),
-            particles,
-            calibrate,
-            center_pos,
-            center_vel,
-            center_mass,
-            radius,
-        );
+        let radius = 5e9 + rng.gen_range(0.0..1e11);
+        let angle = rng.gen::<f32>() * 2.0 * PI;
+        create(particles, center, normal.normalize(), angle, radius);
     }
 
     // based on number of stars in the arms vs center of Milky Way (80%)
-    for _ in 0..(amount * (4 / 5)) {
+    for _ in 0..amount - amount / 5 {
         let arms = 4;
-        let radius = 5e9 + thread_rng().gen_range(0.0..1e11);
+        let radius = 5e9 + rng.gen_range(0.0..1e11);
         // θ = (2π / n) + (2π / n_arm) * (arm_number - 1) + f(r)
         // f(r) is a function that includes variation in the number
-        let arm: f32 = thread_rng().gen_range(0..(arms)) as f32;
-        let angle = (arm as f32 / (arms as f32) * 2.0 * PI) - (radius * 1e-11)
-            + thread_rng().gen_range(0.0..0.15);
-        create(
-            angle,
-            normal.normalize(),
-            particles,
-            calibrate,
-            center_pos,
-            center_vel,
-            center_mass,
-            radius,
-        );
+        let arm: f32 = rng.gen_range(0..(arms)) as f32;
+        let angle = (arm / (arms as f32) * 2.0 * PI) - (radius * 1e-11) + rng.gen_range(0.0..0.15);
+        create(particles, center, normal.normalize(), angle, radius);
+    }
+}
+
+// two unit vectors spanning the plane orthogonal to `normal`
+fn plane(normal: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let normal = normal.normalize();
+    let other = if normal.x.abs() < 0.9 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    let u = normal.cross(other).normalize();
+    (u, normal.cross(u))
+}
+
+// the lengths the rejection samplers scale by and the masses the models are in units
+// of, at or below 0 a sampler never accepts anything and a model divides by 0
+fn positive<T: Into<f64> + std::fmt::Display + Copy>(name: &str, value: T) -> Result<(), String> {
+    if value.into() > 0.0 {
+        Ok(())
+    } else {
+        Err(format!("{} must be positive, not {}", name, value))
+    }
+}
+
+// in (0, 1], so its logarithm is finite
+fn uniform(rng: &mut impl Rng) -> f32 {
+    1.0 - rng.gen::<f32>()
+}
+
+// box-muller, with libm so a seed gives the same value everywhere
+fn gaussian(rng: &mut impl Rng) -> f32 {
+    let r = (-2.0 * libm::logf(uniform(rng))).sqrt();
+    r * libm::cosf(rng.gen::<f32>() * 2.0 * PI)
+}
+
+// an isotropic gaussian velocity with standard deviation `sigma` per axis
+fn scatter(rng: &mut impl Rng, sigma: f32) -> Vector3<f32> {
+    if sigma <= 0.0 {
+        return Vector3::zero();
+    }
+    Vector3::new(gaussian(rng), gaussian(rng), gaussian(rng)) * sigma
+}
+
+fn sphere(rng: &mut impl Rng) -> Vector3<f32> {
+    let z = rng.gen_range(-1.0f32..1.0);
+    let angle = rng.gen::<f32>() * 2.0 * PI;
+    let r = (1.0 - z * z).sqrt();
+    Vector3::new(r * libm::cosf(angle), r * libm::sinf(angle), z)
+}
+
+// a speed in [0, max] drawn by inverting the tabulated cumulative `density`
+fn speed(rng: &mut impl Rng, max: f64, density: impl Fn(f64) -> f64) -> f64 {
+    let h = max / SPEEDS as f64;
+    let mut cumulative = [0.0; SPEEDS + 1];
+    let mut previous = density(0.0);
+    for i in 1..=SPEEDS {
+        let next = density(i as f64 * h).max(0.0);
+        cumulative[i] = cumulative[i - 1] + 0.5 * (previous + next) * h;
+        previous = next;
     }
+    let target = rng.gen::<f64>() * cumulative[SPEEDS];
+    let i = cumulative
+        .partition_point(|&c| c <= target)
+        .clamp(1, SPEEDS);
+    let (a, b) = (cumulative[i - 1], cumulative[i]);
+    let t = if b > a { (target - a) / (b - a) } else { 0.0 };
+    (i as f64 - 1.0 + t) * h
 }
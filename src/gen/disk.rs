@@ -0,0 +1,198 @@
+use {
+    super::{gaussian, halo, plane, positive, scatter, sphere, uniform, Center, Halo},
+    crate::{Particle, Species},
+    cgmath::{prelude::*, Vector3},
+    rand::Rng,
+    serde::{Deserialize, Serialize},
+    std::f32::consts::PI,
+};
+
+// an exponential disk, surface density ∝ exp(-r / scale_length), with part of its
+// stars along logarithmic spiral arms and part in a central bulge
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Disk {
+    // the radius where the surface density fell to 1/e
+    pub scale_length: f32,
+    // no stars beyond this many scale lengths
+    pub cutoff: f32,
+    // of the sech² vertical profile, 0 for a flat disk
+    pub scale_height: f32,
+    pub arms: u32,
+    // the angle between an arm and a circle in degrees, smaller winds tighter
+    pub pitch: f32,
+    // the share of the disk stars in the arms, the rest is spread around evenly
+    pub arm_fraction: f32,
+    // the standard deviation of the angle of arm stars around their arm in radians
+    pub arm_width: f32,
+    // the share of all stars in the bulge, the rest is the disk
+    pub bulge_fraction: f32,
+    pub bulge: Bulge,
+    // random velocity of the disk stars relative to their circular speed
+    pub dispersion: f32,
+    // a live halo around the same center, the stars circle in its mass too
+    pub halo: Option<Halo>,
+}
+
+// a hernquist sphere, density ∝ 1 / (r (r + radius)³), of stars on randomly tilted
+// circular orbits
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bulge {
+    // the scale radius, half the stars are within 2.414 of it
+    pub radius: f32,
+    // no stars beyond this many radii
+    pub cutoff: f32,
+    // random velocity relative to the circular speed
+    pub dispersion: f32,
+}
+
+impl Default for Disk {
+    fn default() -> Self {
+        Self {
+            scale_length: 2e10,
+            cutoff: 5.0,
+            scale_height: 0.0,
+            arms: 2,
+            pitch: 15.0,
+            arm_fraction: 0.5,
+            arm_width: 0.2,
+            bulge_fraction: 0.2,
+            bulge: Bulge::default(),
+            dispersion: 0.05,
+            halo: None,
+        }
+    }
+}
+
+impl Default for Bulge {
+    fn default() -> Self {
+        Self {
+            radius: 5e9,
+            cutoff: 10.0,
+            dispersion: 0.2,
+        }
+    }
+}
+
+impl Disk {
+    pub fn validate(&self) -> Result<(), String> {
+        positive("the scale length of a disk", self.scale_length)?;
+        positive("the cutoff of a disk", self.cutoff)?;
+        if let Some(halo) = &self.halo {
+            halo.validate()?;
+        }
+        self.bulge.validate()
+    }
+
+    // `amount` massless stars around `center` in the plane orthogonal to `normal`
+    pub fn generate(
+        &self,
+        rng: &mut impl Rng,
+        particles: &mut Vec<Particle>,
+        amount: u32,
+        center: &Center,
+        normal: Vector3<f32>,
+    ) {
+        let bulge = share(amount, self.bulge_fraction);
+        let arms = if self.arms > 0 {
+            share(amount - bulge, self.arm_fraction)
+        } else {
+            0
+        };
+        let halo = self.halo.map(|halo| halo.generate(rng, particles, center));
+        let halo = halo.as_ref();
+        self.bulge.stars(rng, particles, bulge, center, halo);
+
+        let (u, v) = plane(normal);
+        let normal = normal.normalize();
+        // a logarithmic spiral r = scale_length * exp(θ tan(pitch)) solved for θ
+        let winding = 1.0 / libm::tanf(self.pitch.clamp(1.0, 89.0) * PI / 180.0);
+        for i in 0..amount - bulge {
+            let radius = self.radius(rng);
+            let angle = if i < arms {
+                let arm = rng.gen_range(0..self.arms) as f32;
+                arm / self.arms as f32 * 2.0 * PI
+                    + libm::logf(radius / self.scale_length) * winding
+                    + gaussian(rng) * self.arm_width
+            } else {
+                rng.gen::<f32>() * 2.0 * PI
+            };
+            let height = if self.scale_height > 0.0 {
+                self.scale_height * libm::atanhf(rng.gen_range(-0.999..0.999))
+            } else {
+                0.0
+            };
+            let (cos, sin) = (libm::cosf(angle), libm::sinf(angle));
+            let radial = u * cos + v * sin;
+            // normal x radial, counterclockwise seen from the normal
+            let tangential = v * cos - u * sin;
+            let speed = center.circular_speed(Species::Disk, radius, halo);
+            let vel = tangential * speed + scatter(rng, self.dispersion * speed);
+            particles.push(center.star(Species::Disk, radial * radius + normal * height, vel));
+        }
+    }
+
+    // r e^(-r/h) is a gamma distribution, the sum of two exponential ones
+    fn radius(&self, rng: &mut impl Rng) -> f32 {
+        loop {
+            let r = -self.scale_length * libm::logf(uniform(rng) * uniform(rng));
+            if r <= self.cutoff * self.scale_length {
+                return r;
+            }
+        }
+    }
+}
+
+impl Bulge {
+    pub fn validate(&self) -> Result<(), String> {
+        positive("the radius of a bulge", self.radius)?;
+        positive("the cutoff of a bulge", self.cutoff)
+    }
+
+    pub fn generate(
+        &self,
+        rng: &mut impl Rng,
+        particles: &mut Vec<Particle>,
+        amount: u32,
+        center: &Center,
+    ) {
+        self.stars(rng, particles, amount, center, None)
+    }
+
+    fn stars(
+        &self,
+        rng: &mut impl Rng,
+        particles: &mut Vec<Particle>,
+        amount: u32,
+        center: &Center,
+        halo: Option<&halo::Model>,
+    ) {
+        for _ in 0..amount {
+            let radius = self.radius(rng);
+            let direction = sphere(rng);
+            // a random orbit through the position
+            let (u, v) = plane(direction);
+            let angle = rng.gen::<f32>() * 2.0 * PI;
+            let tangential = u * libm::cosf(angle) + v * libm::sinf(angle);
+            let speed = center.circular_speed(Species::Bulge, radius, halo);
+            let vel = tangential * speed + scatter(rng, self.dispersion * speed);
+            particles.push(center.star(Species::Bulge, direction * radius, vel));
+        }
+    }
+
+    // the inverse of the enclosed mass, M(r) / M = r² / (r + a)²
+    fn radius(&self, rng: &mut impl Rng) -> f32 {
+        loop {
+            let s = rng.gen::<f32>().sqrt();
+            let r = self.radius * s / (1.0 - s);
+            if r <= self.cutoff * self.radius {
+                return r;
+            }
+        }
+    }
+}
+
+fn share(amount: u32, fraction: f32) -> u32 {
+    ((amount as f32 * fraction.clamp(0.0, 1.0)).round() as u32).min(amount)
+}
@@ -0,0 +1,356 @@
+use {
+    super::{positive, speed, sphere, Center, G},
+    crate::{softening::Kernel, Particle, Species},
+    cgmath::{prelude::*, Vector3},
+    rand::Rng,
+    serde::{Deserialize, Serialize},
+    std::f64::consts::PI,
+};
+
+// of the universe for H0 = 70 km/s/Mpc, in kg/m³
+const CRITICAL_DENSITY: f64 = 9.204e-27;
+// beyond the virial radius the density falls off exponentially over this share
+// of it, the profiles alone would hold an infinite mass
+const DECAY: f64 = 0.1;
+// the tables reach from this share of the scale radius to this many decay lengths
+// beyond the virial radius
+const INNER: f64 = 1e-4;
+const DECAY_LENGTHS: f64 = 40.0;
+const POINTS: usize = 800;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum HaloProfile {
+    // ρ ∝ 1 / (x (1 + x)²), x in scale radii
+    #[default]
+    Nfw,
+    // ρ ∝ 1 / ((1 + x) (1 + x²)), with a core
+    Burkert,
+}
+
+// a live dark matter halo, in equilibrium with itself and the central mass
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Halo {
+    #[serde(default)]
+    pub profile: HaloProfile,
+    // the mass within the virial radius, the smooth cutoff beyond it adds up to a
+    // quarter of that
+    pub mass: f64,
+    // the virial radius over the scale radius
+    pub concentration: f32,
+    // the virial radius, by default where the mean density is 200 times the
+    // critical one
+    #[serde(default)]
+    pub radius: Option<f32>,
+    pub amount: u32,
+}
+
+// the halo tabulated at log-spaced radii in units with G = 1, a scale radius of
+// 1 and a virial mass of 1
+pub struct Model {
+    scale: f64,
+    mass: f64,
+    radii: Vec<f64>,
+    enclosed: Vec<f64>,
+    // the relative potential of the halo and the central mass
+    potential: Vec<f64>,
+    // ln f(E) at each potential
+    distribution: Vec<f64>,
+}
+
+impl Halo {
+    pub fn validate(&self) -> Result<(), String> {
+        positive("the mass of a halo", self.mass)
+    }
+
+    pub fn virial_radius(&self) -> f64 {
+        match self.radius {
+            Some(radius) => radius as f64,
+            None => libm::cbrt(3.0 * self.mass / (4.0 * PI * 200.0 * CRITICAL_DENSITY)),
+        }
+    }
+
+    // draws the particles around `center` and returns the model the stars of a
+    // disk in the same halo circle in
+    pub fn generate(
+        &self,
+        rng: &mut impl Rng,
+        particles: &mut Vec<Particle>,
+        center: &Center,
+    ) -> Model {
+        let model = Model::new(self, center);
+        model.sample(rng, particles, self.amount, center);
+        model
+    }
+}
+
+impl Model {
+    fn new(halo: &Halo, center: &Center) -> Self {
+        let c = (halo.concentration as f64).max(0.1);
+        let scale = halo.virial_radius() / c;
+        let density = Density::new(halo.profile, c);
+
+        let outer = c + DECAY_LENGTHS * DECAY * c;
+        let radii: Vec<f64> = (0..POINTS)
+            .map(|i| INNER * libm::pow(outer / INNER, i as f64 / (POINTS - 1) as f64))
+            .collect();
+        let rows: Vec<[f64; 3]> = radii.iter().map(|&x| density.at(x)).collect();
+        let step = libm::log(outer / INNER) / (POINTS - 1) as f64;
+
+        // the inner power law ρ ∝ x^s holds 4π x³ ρ / (3 + s)
+        let (x0, [rho0, slope0, _]) = (radii[0], rows[0]);
+        let mut enclosed = vec![4.0 * PI * x0.powi(3) * rho0 / (3.0 + x0 * slope0 / rho0)];
+        for i in 1..POINTS {
+            let shell = |k: usize| 4.0 * PI * radii[k].powi(3) * rows[k][0];
+            enclosed.push(enclosed[i - 1] + 0.5 * (shell(i - 1) + shell(i)) * step);
+        }
+        // the potential of the shells outside each radius
+        let mut outside = vec![0.0; POINTS];
+        for i in (0..POINTS - 1).rev() {
+            let shell = |k: usize| 4.0 * PI * radii[k].powi(2) * rows[k][0];
+            outside[i] = outside[i + 1] + 0.5 * (shell(i) + shell(i + 1)) * step;
+        }
+
+        // the central mass pulls with the kernel of the simulation
+        let mu = center.mass / halo.mass;
+        let kernel = center.softening.kernel;
+        let calibrate = center.softening.pair(Species::BlackHole, Species::Halo);
+        let s = calibrate.sqrt() / scale;
+        let potential: Vec<f64> = (0..POINTS)
+            .map(|i| {
+                let x = radii[i];
+                let central = match kernel {
+                    Kernel::Calibrate if s > 0.0 => mu * libm::atan(s / x) / s,
+                    Kernel::Calibrate => mu / x,
+                    kernel => mu * scale * kernel.potential(x * scale, calibrate),
+                };
+                enclosed[i] / x + outside[i] + central
+            })
+            .collect();
+        // d²ρ/dψ² from the derivatives in radius
+        let curvature: Vec<f64> = (0..POINTS)
+            .map(|i| {
+                let x = radii[i];
+                let [_, d1, d2] = rows[i];
+                // the central pull and its derivative
+                let (pull, slope) = match kernel {
+                    Kernel::Calibrate => {
+                        let pull = mu / (x * x + s * s);
+                        (pull, -2.0 * x * pull / (x * x + s * s))
+                    }
+                    kernel => {
+                        let r = x * scale;
+                        let g = kernel.force(r, calibrate) * scale.powi(3);
+                        let dg = kernel.force_derivative(r, calibrate) * scale.powi(4);
+                        (mu * x * g, mu * (g + x * dg))
+                    }
+                };
+                let psi1 = -enclosed[i] / (x * x) - pull;
+                let psi2 = 2.0 * enclosed[i] / x.powi(3) - 4.0 * PI * rows[i][0] - slope;
+                (d2 * psi1 - d1 * psi2) / psi1.powi(3)
+            })
+            .collect();
+        let distribution = (0..POINTS)
+            .map(|k| {
+                let f = eddington(&potential, &curvature, k) / (8f64.sqrt() * PI * PI);
+                libm::log(f.max(f64::MIN_POSITIVE))
+            })
+            .collect();
+
+        Self {
+            scale,
+            mass: halo.mass,
+            radii,
+            enclosed,
+            potential,
+            distribution,
+        }
+    }
+
+    // the mass of the halo within `r` metres
+    pub fn enclosed(&self, r: f64) -> f64 {
+        let x = r / self.scale;
+        let i = self.radii.partition_point(|&radius| radius < x);
+        let mass = if i == 0 {
+            self.enclosed[0] * (x / self.radii[0]).powi(2)
+        } else if i == POINTS {
+            self.enclosed[POINTS - 1]
+        } else {
+            let t = (x - self.radii[i - 1]) / (self.radii[i] - self.radii[i - 1]);
+            self.enclosed[i - 1] + (self.enclosed[i] - self.enclosed[i - 1]) * t
+        };
+        mass * self.mass
+    }
+
+    fn sample(
+        &self,
+        rng: &mut impl Rng,
+        particles: &mut Vec<Particle>,
+        amount: u32,
+        center: &Center,
+    ) {
+        if amount == 0 {
+            return;
+        }
+        let total = self.enclosed[POINTS - 1];
+        let stars: Vec<[Vector3<f64>; 2]> = (0..amount)
+            .map(|_| {
+                let (x, psi) = self.radius(rng.gen::<f64>() * total);
+                let v = speed(rng, (2.0 * psi).sqrt(), |v| {
+                    v * v * self.distribution(psi - 0.5 * v * v)
+                });
+                [
+                    sphere(rng).map(|c| c as f64) * x,
+                    sphere(rng).map(|c| c as f64) * v,
+                ]
+            })
+            .collect();
+
+        let n = amount as f64;
+        let mean = |k: usize| {
+            stars
+                .iter()
+                .fold(Vector3::zero(), |sum, star| sum + star[k])
+                / n
+        };
+        // the sample's own drift, so the halo stays on the central mass
+        let (pos, vel) = (mean(0), mean(1));
+        let speed = (G * self.mass / self.scale).sqrt();
+        for [offset, motion] in stars {
+            let offset = (offset - pos) * self.scale;
+            let motion = (motion - vel) * speed;
+            particles.push(
+                Particle::new(
+                    (center.pos + offset.map(|x| x as f32)).into(),
+                    (center.vel + motion.map(|x| x as f32)).into(),
+                    self.mass * total / n,
+                    center.softening.calibrate(Species::Halo),
+                )
+                .with_species(Species::Halo),
+            );
+        }
+    }
+
+    // the radius enclosing `mass` and the potential there
+    fn radius(&self, mass: f64) -> (f64, f64) {
+        let i = self.enclosed.partition_point(|&m| m < mass);
+        if i == 0 {
+            return (self.radii[0] * mass / self.enclosed[0], self.potential[0]);
+        }
+        let i = i.min(POINTS - 1);
+        let (a, b) = (self.enclosed[i - 1], self.enclosed[i]);
+        let t = if b > a { (mass - a) / (b - a) } else { 0.0 };
+        (
+            self.radii[i - 1] + (self.radii[i] - self.radii[i - 1]) * t,
+            self.potential[i - 1] + (self.potential[i] - self.potential[i - 1]) * t,
+        )
+    }
+
+    // f at relative energy `e`, interpolated in the potential
+    fn distribution(&self, e: f64) -> f64 {
+        if e <= 0.0 {
+            return 0.0;
+        }
+        // the potential falls outwards
+        let k = self.potential.partition_point(|&psi| psi > e);
+        let ln = if k == 0 {
+            self.distribution[0]
+        } else if k == POINTS {
+            self.distribution[POINTS - 1]
+        } else {
+            let (a, b) = (self.potential[k - 1], self.potential[k]);
+            let t = (a - e) / (a - b);
+            self.distribution[k - 1] + (self.distribution[k] - self.distribution[k - 1]) * t
+        };
+        libm::exp(ln)
+    }
+}
+
+// ∫₀ᴱ d²ρ/dψ² dψ / √(E - ψ) at E = ψ_k, exact for d²ρ/dψ² linear between the
+// tabulated potentials, with u = E - ψ each piece is ∫ (h₀ + κ (u - u₀)) / √u du
+fn eddington(potential: &[f64], curvature: &[f64], k: usize) -> f64 {
+    let e = potential[k];
+    let mut sum = 0.0;
+    for j in k..potential.len() - 1 {
+        let (u0, u1) = (e - potential[j], e - potential[j + 1]);
+        if u1 <= u0 {
+            continue;
+        }
+        let (h0, h1) = (curvature[j], curvature[j + 1]);
+        let kappa = (h1 - h0) / (u1 - u0);
+        sum += (h0 - kappa * u0) * 2.0 * (u1.sqrt() - u0.sqrt())
+            + kappa * 2.0 / 3.0 * (u1 * u1.sqrt() - u0 * u0.sqrt());
+    }
+    sum
+}
+
+// the density and its first two derivatives, normalized to a virial mass of 1
+struct Density {
+    profile: HaloProfile,
+    concentration: f64,
+    norm: f64,
+    // of the exponential cutoff ρ(c) (x / c)^ε exp(-(x - c) / decay)
+    epsilon: f64,
+    decay: f64,
+}
+
+impl Density {
+    fn new(profile: HaloProfile, c: f64) -> Self {
+        let mass = match profile {
+            HaloProfile::Nfw => 4.0 * PI * (libm::log1p(c) - c / (1.0 + c)),
+            HaloProfile::Burkert => {
+                PI * (libm::log1p(c * c) + 2.0 * libm::log1p(c) - 2.0 * libm::atan(c))
+            }
+        };
+        let decay = DECAY * c;
+        let mut density = Self {
+            profile,
+            concentration: c,
+            norm: 1.0 / mass,
+            epsilon: 0.0,
+            decay,
+        };
+        // continue the logarithmic slope of the profile at the virial radius
+        density.epsilon = c / decay + c * density.logarithmic(c)[0];
+        density
+    }
+
+    // d ln ρ / dx and its derivative within the virial radius
+    fn logarithmic(&self, x: f64) -> [f64; 2] {
+        match self.profile {
+            HaloProfile::Nfw => [
+                -1.0 / x - 2.0 / (1.0 + x),
+                1.0 / (x * x) + 2.0 / (1.0 + x).powi(2),
+            ],
+            HaloProfile::Burkert => {
+                let q = 1.0 + x * x;
+                [
+                    -1.0 / (1.0 + x) - 2.0 * x / q,
+                    1.0 / (1.0 + x).powi(2) - 2.0 * (1.0 - x * x) / (q * q),
+                ]
+            }
+        }
+    }
+
+    fn profile(&self, x: f64) -> f64 {
+        self.norm
+            * match self.profile {
+                HaloProfile::Nfw => 1.0 / (x * (1.0 + x).powi(2)),
+                HaloProfile::Burkert => 1.0 / ((1.0 + x) * (1.0 + x * x)),
+            }
+    }
+
+    // ρ, dρ/dx and d²ρ/dx²
+    fn at(&self, x: f64) -> [f64; 3] {
+        let c = self.concentration;
+        let (rho, [l, dl]) = if x <= c {
+            (self.profile(x), self.logarithmic(x))
+        } else {
+            (
+                self.profile(c) * libm::pow(x / c, self.epsilon) * libm::exp(-(x - c) / self.decay),
+                [self.epsilon / x - 1.0 / self.decay, -self.epsilon / (x * x)],
+            )
+        };
+        [rho, rho * l, rho * (l * l + dl)]
+    }
+}
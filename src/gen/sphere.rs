@@ -0,0 +1,335 @@
+use {
+    super::{plane, speed, sphere, Center},
+    crate::{cpu::G, Particle, Species},
+    cgmath::{prelude::*, Vector3},
+    rand::Rng,
+    std::f64::consts::PI,
+};
+
+// the models without an edge stop at this share of their mass, farther out
+// there would only be a few stars very far apart
+const MASS_FRACTION: f64 = 0.99;
+// the distribution functions of the eddington models are tabulated at
+// log-spaced radii in this range, in scale radii
+const RADII: (f64, f64, usize) = (1e-6, 1e6, 480);
+
+// spheres of equal mass stars in equilibrium, in units with G = M = 1 and a scale
+// radius of 1 until `Model::generate` scales them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Model {
+    // drawn the way of aarseth, hénon and wielen (1974)
+    Plummer,
+    Hernquist,
+    Jaffe,
+    // the lowered isothermal sphere of depth W0 = `depth`, with an `anisotropy`
+    // radius in king radii a michie model, radial orbits beyond it
+    King { depth: f64, anisotropy: Option<f64> },
+}
+
+impl Model {
+    // `amount` stars of `center.mass` in total with the center of mass at `center`,
+    // `radius` is the scale radius, for king models the king radius
+    pub fn generate(
+        self,
+        rng: &mut impl Rng,
+        particles: &mut Vec<Particle>,
+        amount: u32,
+        radius: f32,
+        center: &Center,
+    ) {
+        if amount == 0 {
+            return;
+        }
+        let sampler = Sampler::new(self);
+        let stars: Vec<_> = (0..amount).map(|_| sampler.star(rng)).collect();
+
+        let radius = radius as f64;
+        // the unit of velocity is sqrt(G M / r) with M the model's total mass
+        let speed = (G * center.mass / radius / sampler.mass).sqrt();
+        let n = amount as f64;
+        let mean = |k: usize| {
+            stars
+                .iter()
+                .fold(Vector3::zero(), |sum: Vector3<f64>, star| {
+                    sum + star[k].map(|x| x as f64)
+                })
+                / n
+        };
+        // the sample's own drift, so the center of mass sits still at `center`
+        let (pos, vel) = (mean(0), mean(1));
+        for star in stars {
+            let offset = (star[0].map(|x| x as f64) - pos) * radius;
+            let motion = (star[1].map(|x| x as f64) - vel) * speed;
+            particles.push(
+                Particle::new(
+                    (center.pos + offset.map(|x| x as f32)).into(),
+                    (center.vel + motion.map(|x| x as f32)).into(),
+                    center.mass / n,
+                    center.softening.calibrate(Species::Star),
+                )
+                .with_species(Species::Star),
+            );
+        }
+    }
+}
+
+struct Sampler {
+    model: Model,
+    // in the model's units, 1 for all but the king models
+    mass: f64,
+    eddington: Vec<f64>,
+    king: Vec<[f64; 3]>,
+}
+
+impl Sampler {
+    fn new(model: Model) -> Self {
+        let mut sampler = Self {
+            model,
+            mass: 1.0,
+            eddington: Vec::new(),
+            king: Vec::new(),
+        };
+        match model {
+            Model::Plummer => {}
+            Model::Hernquist | Model::Jaffe => sampler.eddington = eddington(model),
+            Model::King { depth, anisotropy } => {
+                sampler.king = king(depth, anisotropy);
+                sampler.mass = sampler.king.last().unwrap()[2];
+            }
+        }
+        sampler
+    }
+
+    // position and velocity in model units, velocities of king models in units
+    // of their central dispersion
+    fn star(&self, rng: &mut impl Rng) -> [Vector3<f32>; 2] {
+        let u = rng.gen::<f64>();
+        let (r, v, k) = match self.model {
+            Model::Plummer => {
+                let r = 1.0 / (libm::pow(u * MASS_FRACTION, -2.0 / 3.0) - 1.0).sqrt();
+                // q = v / v_escape is distributed as q² (1 - q²)^(7/2), below 0.1
+                let q = loop {
+                    let q = rng.gen::<f64>();
+                    if 0.1 * rng.gen::<f64>() < q * q * libm::pow(1.0 - q * q, 3.5) {
+                        break q;
+                    }
+                };
+                (r, q * 2f64.sqrt() * libm::pow(1.0 + r * r, -0.25), 0.0)
+            }
+            Model::Hernquist | Model::Jaffe => {
+                let u = u * MASS_FRACTION;
+                let r = match self.model {
+                    Model::Hernquist => u.sqrt() / (1.0 - u.sqrt()),
+                    _ => u / (1.0 - u),
+                }
+                .max(RADII.0);
+                let psi = potential(self.model, r);
+                let v = speed(rng, (2.0 * psi).sqrt(), |v| {
+                    v * v * self.distribution(psi - 0.5 * v * v)
+                });
+                (r, v, 0.0)
+            }
+            Model::King { anisotropy, .. } => {
+                let (r, w) = self.king_radius(u * self.mass);
+                let ra2 = anisotropy.map_or(f64::INFINITY, |ra| ra * ra);
+                let v = speed(rng, (2.0 * w).sqrt(), |v| {
+                    v * v * (libm::exp(w - 0.5 * v * v) - 1.0) * tilt(r * r * v * v / (2.0 * ra2))
+                });
+                (r, v, r * r * v * v / (2.0 * ra2))
+            }
+        };
+        // the cosine between velocity and radius, anisotropic models favour
+        // radial orbits by exp(-L² / (2 ra²)) = exp(-k sin²)
+        let cos = loop {
+            let cos = rng.gen_range(-1.0..1.0);
+            if k == 0.0 || rng.gen::<f64>() < libm::exp(-k * (1.0 - cos * cos)) {
+                break cos;
+            }
+        };
+        let direction = sphere(rng);
+        let (a, b) = plane(direction);
+        let angle = rng.gen::<f32>() * 2.0 * std::f32::consts::PI;
+        let tangential = a * libm::cosf(angle) + b * libm::sinf(angle);
+        let sin = (1.0 - cos * cos).sqrt();
+        [
+            direction * r as f32,
+            (direction * cos as f32 + tangential * sin as f32) * v as f32,
+        ]
+    }
+
+    // the tabulated distribution function at relative energy `e`, interpolated
+    // in the log of the radius where the potential is `e`
+    fn distribution(&self, e: f64) -> f64 {
+        if e <= 0.0 {
+            return 0.0;
+        }
+        let r = match self.model {
+            Model::Hernquist => 1.0 / e - 1.0,
+            _ => 1.0 / libm::expm1(e),
+        };
+        let (first, last, n) = RADII;
+        let x = (libm::log(r / first) / libm::log(last / first) * (n - 1) as f64)
+            .clamp(0.0, (n - 1) as f64);
+        let i = (x as usize).min(n - 2);
+        let t = x - i as f64;
+        libm::exp(self.eddington[i] * (1.0 - t) + self.eddington[i + 1] * t)
+    }
+
+    // the radius enclosing `mass` and the potential there, from the integrated table
+    fn king_radius(&self, mass: f64) -> (f64, f64) {
+        let i = self
+            .king
+            .partition_point(|row| row[2] < mass)
+            .clamp(1, self.king.len() - 1);
+        let (a, b) = (self.king[i - 1], self.king[i]);
+        let t = if b[2] > a[2] {
+            (mass - a[2]) / (b[2] - a[2])
+        } else {
+            0.0
+        };
+        (
+            a[0] + (b[0] - a[0]) * t,
+            (a[1] + (b[1] - a[1]) * t).max(0.0),
+        )
+    }
+}
+
+// the relative potential -Φ, positive and 0 at infinity
+fn potential(model: Model, r: f64) -> f64 {
+    match model {
+        Model::Plummer => 1.0 / (1.0 + r * r).sqrt(),
+        Model::Hernquist => 1.0 / (1.0 + r),
+        _ => libm::log1p(1.0 / r),
+    }
+}
+
+// d²ρ/dψ², both densities written as functions of the potential
+//   hernquist ρ = ψ⁴ / (2π (1 - ψ)), jaffe ρ = 4 sinh⁴(ψ/2) / π
+fn curvature(model: Model, psi: f64) -> f64 {
+    match model {
+        Model::Hernquist => {
+            let q = 1.0 - psi;
+            (12.0 * psi * psi / q + 8.0 * psi.powi(3) / (q * q) + 2.0 * psi.powi(4) / q.powi(3))
+                / (2.0 * PI)
+        }
+        _ => {
+            let (s, c) = (libm::sinh(0.5 * psi), libm::cosh(0.5 * psi));
+            4.0 / PI * (3.0 * s * s * c * c + s.powi(4))
+        }
+    }
+}
+
+// ln f(E) at the potential of each radius of RADII, by eddington's formula
+//   f(E) = 1 / (√8 π²) ∫₀ᴱ d²ρ/dψ² dψ / √(E - ψ)
+// with ψ = E - t² to remove the singularity, dρ/dψ vanishes at ψ = 0 for both
+fn eddington(model: Model) -> Vec<f64> {
+    let (first, last, n) = RADII;
+    let steps = 512;
+    (0..n)
+        .map(|i| {
+            let r = first * libm::pow(last / first, i as f64 / (n - 1) as f64);
+            let e = potential(model, r);
+            let h = e.sqrt() / steps as f64;
+            let integral: f64 = (0..steps)
+                .map(|k| {
+                    let t = (k as f64 + 0.5) * h;
+                    2.0 * curvature(model, e - t * t) * h
+                })
+                .sum();
+            libm::log((integral / (8f64.sqrt() * PI * PI)).max(f64::MIN_POSITIVE))
+        })
+        .collect()
+}
+
+// radius, potential W and enclosed mass from the center to the tidal radius of a
+// king or michie model, in king radii, units of the central dispersion and
+// with 4πGρ₀ = 9
+fn king(depth: f64, anisotropy: Option<f64>) -> Vec<[f64; 3]> {
+    let ra2 = anisotropy.map_or(f64::INFINITY, |ra| ra * ra);
+    let central = density(0.0, depth, ra2);
+    // W'' + 2 W' / r = -9 ρ / ρ₀ in x = ln r, with p = dW/dr
+    let derivative = |x: f64, [w, p]: [f64; 2]| {
+        let r = libm::exp(x);
+        [r * p, -9.0 * r * density(r, w, ra2) / central - 2.0 * p]
+    };
+    let (mut x, h) = (libm::log(1e-4), 0.005);
+    let r = libm::exp(x);
+    // near the center W = W0 - 3/2 r²
+    let mut y = [depth - 1.5 * r * r, -3.0 * r];
+    let mut table = vec![[0.0, depth, 0.0], [r, y[0], 3.0 * r.powi(3)]];
+    while y[0] > 0.0 && x < libm::log(1e6) {
+        let k1 = derivative(x, y);
+        let k2 = derivative(x + 0.5 * h, add(y, k1, 0.5 * h));
+        let k3 = derivative(x + 0.5 * h, add(y, k2, 0.5 * h));
+        let k4 = derivative(x + h, add(y, k3, h));
+        let next = [
+            y[0] + h / 6.0 * (k1[0] + 2.0 * k2[0] + 2.0 * k3[0] + k4[0]),
+            y[1] + h / 6.0 * (k1[1] + 2.0 * k2[1] + 2.0 * k3[1] + k4[1]),
+        ];
+        if next[0] <= 0.0 {
+            // the tidal radius, where W falls to 0
+            let t = y[0] / (y[0] - next[0]);
+            let r = libm::exp(x + h * t);
+            let p = y[1] + (next[1] - y[1]) * t;
+            table.push([r, 0.0, -r * r * p]);
+            break;
+        }
+        x += h;
+        y = next;
+        let r = libm::exp(x);
+        table.push([r, y[0], -r * r * y[1]]);
+    }
+    table
+}
+
+fn add(y: [f64; 2], k: [f64; 2], h: f64) -> [f64; 2] {
+    [y[0] + k[0] * h, y[1] + k[1] * h]
+}
+
+// ∫ (e^(W - v²/2) - 1) exp(-r² v_t² / (2 ra²)) d³v, up to a constant factor
+fn density(r: f64, w: f64, ra2: f64) -> f64 {
+    if w <= 0.0 {
+        return 0.0;
+    }
+    let escape = (2.0 * w).sqrt();
+    let steps = 64;
+    let h = escape / steps as f64;
+    (0..steps)
+        .map(|i| {
+            let v = (i as f64 + 0.5) * h;
+            v * v * (libm::exp(w - 0.5 * v * v) - 1.0) * tilt(r * r * v * v / (2.0 * ra2)) * h
+        })
+        .sum()
+}
+
+// the mean of exp(-k sin²) over directions, e^-k ∫₀¹ e^(kμ²) dμ = D(√k) / √k
+fn tilt(k: f64) -> f64 {
+    if k < 1e-8 {
+        1.0
+    } else {
+        dawson(k.sqrt()) / k.sqrt()
+    }
+}
+
+// dawson's integral e^(-x²) ∫₀ˣ e^(t²) dt for x >= 0 by rybicki's method
+fn dawson(x: f64) -> f64 {
+    const H: f64 = 0.4;
+    if x < 0.2 {
+        let x2 = x * x;
+        return x * (1.0 - 2.0 / 3.0 * x2 * (1.0 - 0.4 * x2 * (1.0 - 2.0 / 7.0 * x2)));
+    }
+    let n0 = 2.0 * (0.5 * x / H).round();
+    let xp = x - n0 * H;
+    let mut e1 = libm::exp(2.0 * xp * H);
+    let e2 = e1 * e1;
+    let (mut d1, mut d2) = (n0 + 1.0, n0 - 1.0);
+    let mut sum = 0.0;
+    for i in 0..6 {
+        let c = libm::exp(-((2 * i + 1) as f64 * H).powi(2));
+        sum += c * (e1 / d1 + 1.0 / (d2 * e1));
+        d1 += 2.0;
+        d2 -= 2.0;
+        e1 *= e2;
+    }
+    sum * libm::exp(-xp * xp) / PI.sqrt()
+}
@@ -0,0 +1,43 @@
+use {
+    serde::{Deserialize, Serialize},
+    std::str::FromStr,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Integrator {
+    // semi-implicit euler, the original update in compute.wgsl
+    Euler,
+    // kick-drift-kick, symplectic so the energy error stays bounded
+    Leapfrog,
+    // the following run on the cpu only, in f64 within each batch of substeps
+    // classic 4th order runge-kutta, 4 force evaluations per step
+    Rk4,
+    // Yoshida's 4th order symplectic triple jump, 3 force evaluations per step
+    Yoshida,
+    // 4th order hermite predictor-corrector, needs the jerk along with the force
+    Hermite,
+}
+
+impl Integrator {
+    pub fn runs_on_gpu(self) -> bool {
+        matches!(self, Integrator::Euler | Integrator::Leapfrog)
+    }
+}
+
+impl FromStr for Integrator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "euler" => Ok(Integrator::Euler),
+            "leapfrog" => Ok(Integrator::Leapfrog),
+            "rk4" => Ok(Integrator::Rk4),
+            "yoshida" => Ok(Integrator::Yoshida),
+            "hermite" => Ok(Integrator::Hermite),
+            _ => Err(format!(
+                "unknown integrator `{}`, expected one of euler, leapfrog, rk4, yoshida, hermite",
+                s
+            )),
+        }
+    }
+}
@@ -0,0 +1,456 @@
+#![deny(nonstandard_style, unused)]
+
+pub mod accretion;
+pub mod collision;
+pub mod cpu;
+pub mod diagnostics;
+pub mod export;
+pub mod external;
+pub mod gadget;
+pub mod gen;
+pub mod integrator;
+pub mod scenario;
+pub mod sim;
+pub mod snapshot;
+pub mod softening;
+pub mod solver;
+pub mod timestep;
+
+pub use {scenario::Scenario, sim::Simulation};
+
+use {
+    crate::softening::{Kernel, Softening},
+    rand::Rng,
+    serde::{Deserialize, Serialize},
+};
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Particle {
+    pos: [f32; 3],
+    // the `Species` in the low byte and the galaxy index above, in what the
+    // compute shaders see as padding
+    #[serde(default, alias = "species")]
+    tag: u32,
+    vel: [f32; 3],
+    // stable across steps, reordering and files, 0 until assigned
+    #[serde(default)]
+    id: u32,
+    mass: f64,
+    calibrate: f64,
+}
+
+/// What a particle stands for, the simulation treats all of them alike. The codes
+/// are the particle types of Gadget snapshots.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+)]
+pub enum Species {
+    /// A particle given on its own, or read without a species.
+    #[default]
+    Body = 0,
+    /// A particle of a live dark matter halo.
+    Halo = 1,
+    Disk = 2,
+    Bulge = 3,
+    /// A star of a spherical cluster or galaxy.
+    Star = 4,
+    /// The central mass of a galaxy.
+    BlackHole = 5,
+}
+
+impl Species {
+    pub const ALL: [Species; 6] = [
+        Species::Body,
+        Species::Halo,
+        Species::Disk,
+        Species::Bulge,
+        Species::Star,
+        Species::BlackHole,
+    ];
+
+    /// The species of a code, unknown codes are bodies.
+    pub fn from_code(code: u32) -> Self {
+        Self::ALL
+            .get(code as usize)
+            .copied()
+            .unwrap_or(Species::Body)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Copy)]
+pub enum Galaxy {
+    Particle {
+        pos: [f32; 3],
+        vel: [f32; 3],
+        mass: f64,
+    },
+    Init {
+        center_pos: [f32; 3],
+        center_vel: [f32; 3],
+        center_mass: f64,
+        amount: u32,
+        normal: [f32; 3],
+    },
+    /// An exponential disk with spiral arms and a bulge around a central mass, shaped
+    /// by `gen::Disk`.
+    Disk {
+        center_pos: [f32; 3],
+        center_vel: [f32; 3],
+        center_mass: f64,
+        amount: u32,
+        normal: [f32; 3],
+        #[serde(default)]
+        profile: gen::Disk,
+    },
+    /// A spherical cloud of stars around a central mass, shaped by `gen::Bulge`.
+    Bulge {
+        center_pos: [f32; 3],
+        center_vel: [f32; 3],
+        center_mass: f64,
+        amount: u32,
+        #[serde(default)]
+        profile: gen::Bulge,
+    },
+    /// A live dark matter halo, around a central mass unless `center_mass` is 0.
+    Halo {
+        center_pos: [f32; 3],
+        center_vel: [f32; 3],
+        #[serde(default)]
+        center_mass: f64,
+        halo: gen::Halo,
+    },
+    /// A self-gravitating Plummer sphere of `amount` stars weighing `mass` in total,
+    /// `radius` is its scale radius.
+    Plummer {
+        center_pos: [f32; 3],
+        center_vel: [f32; 3],
+        mass: f64,
+        amount: u32,
+        radius: f32,
+    },
+    /// A Hernquist sphere, like `Plummer` but with a 1/r density cusp.
+    Hernquist {
+        center_pos: [f32; 3],
+        center_vel: [f32; 3],
+        mass: f64,
+        amount: u32,
+        radius: f32,
+    },
+    /// A Jaffe sphere, like `Plummer` but with a 1/r² density cusp.
+    Jaffe {
+        center_pos: [f32; 3],
+        center_vel: [f32; 3],
+        mass: f64,
+        amount: u32,
+        radius: f32,
+    },
+    /// A King model with the central potential `depth` (W0, usually 1 to 12) and
+    /// the king radius `radius`. With an `anisotropy` radius it is a Michie model,
+    /// whose orbits turn radial beyond that radius.
+    King {
+        center_pos: [f32; 3],
+        center_vel: [f32; 3],
+        mass: f64,
+        amount: u32,
+        radius: f32,
+        depth: f32,
+        #[serde(default)]
+        anisotropy: Option<f32>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct GpuInfo {
+    pub matrix: [[f32; 4]; 4],
+    pub particles: u32,
+    pub motion: f32,
+    // the length of the massive index buffer, see `Partition`
+    pub massive: u32,
+    // the `softening::Kernel` code
+    pub softening: u32,
+}
+
+impl Particle {
+    /// A negative mass marks the particle as inactive, a zero mass as a massless star.
+    pub fn new(pos: [f32; 3], vel: [f32; 3], mass: f64, calibrate: f64) -> Self {
+        Self {
+            pos,
+            vel,
+            mass,
+            calibrate,
+            tag: Species::Body as u32,
+            id: 0,
+        }
+    }
+
+    pub fn with_species(mut self, species: Species) -> Self {
+        self.tag = self.tag & !0xff | species as u32;
+        self
+    }
+
+    /// The index of the galaxy in the scenario the particle was generated for.
+    pub fn with_galaxy(mut self, galaxy: u32) -> Self {
+        self.tag = self.tag & 0xff | galaxy << 8;
+        self
+    }
+
+    pub fn with_id(mut self, id: u32) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn pos(&self) -> [f32; 3] {
+        self.pos
+    }
+
+    pub fn vel(&self) -> [f32; 3] {
+        self.vel
+    }
+
+    pub fn mass(&self) -> f64 {
+        self.mass
+    }
+
+    pub fn calibrate(&self) -> f64 {
+        self.calibrate
+    }
+
+    pub fn species(&self) -> Species {
+        Species::from_code(self.tag & 0xff)
+    }
+
+    pub fn galaxy(&self) -> u32 {
+        self.tag >> 8
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.mass >= 0.0
+    }
+
+    pub fn set_pos(&mut self, pos: [f32; 3]) {
+        self.pos = pos;
+    }
+
+    pub fn set_vel(&mut self, vel: [f32; 3]) {
+        self.vel = vel;
+    }
+
+    pub fn set_mass(&mut self, mass: f64) {
+        self.mass = mass;
+    }
+}
+
+/// The particles that are advanced, those with a mass of zero or more, and the
+/// ones among them that attract the others, those with a positive mass.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Partition {
+    pub active: Vec<usize>,
+    pub massive: Vec<usize>,
+}
+
+impl Partition {
+    pub fn new(particles: &[Particle]) -> Self {
+        let mut partition = Self::default();
+        for (i, p) in particles.iter().enumerate() {
+            if p.mass >= 0.0 {
+                partition.active.push(i);
+            }
+            if p.mass > 0.0 {
+                partition.massive.push(i);
+            }
+        }
+        partition
+    }
+}
+
+/// Gives the particles without an id, those with 0, the ids following the largest one.
+pub fn assign_ids(particles: &mut [Particle]) {
+    let mut next = particles.iter().map(|p| p.id).max().unwrap_or(0);
+    for p in particles.iter_mut().filter(|p| p.id == 0) {
+        next += 1;
+        p.id = next;
+    }
+}
+
+/// Central masses first, then the stars of every generated galaxy, though nothing
+/// relies on that order any more. Every particle is tagged with the index of its
+/// galaxy and its species, and numbered from 1.
+pub fn init_galaxy(calibrate: f64, galaxies: &[Galaxy], rng: &mut impl Rng) -> Vec<Particle> {
+    init_galaxy_with(Softening::new(Kernel::Calibrate, calibrate), galaxies, rng)
+}
+
+/// Like `init_galaxy`, with the softening of every species and the orbital speeds
+/// computed with the kernel the simulation is going to use.
+pub fn init_galaxy_with(
+    softening: Softening,
+    galaxies: &[Galaxy],
+    rng: &mut impl Rng,
+) -> Vec<Particle> {
+    let mut particles = Vec::new();
+    for (i, c) in galaxies.iter().enumerate() {
+        let species = match c {
+            Galaxy::Particle { .. } => Species::Body,
+            _ => Species::BlackHole,
+        };
+        let calibrate = softening.calibrate(species);
+        let particle = match c {
+            Galaxy::Particle { pos, vel, mass } => {
+                Particle::new((*pos).into(), (*vel).into(), *mass, calibrate)
+            }
+            Galaxy::Init {
+                center_pos,
+                center_vel,
+                center_mass,
+                ..
+            }
+            | Galaxy::Disk {
+                center_pos,
+                center_vel,
+                center_mass,
+                ..
+            }
+            | Galaxy::Bulge {
+                center_pos,
+                center_vel,
+                center_mass,
+                ..
+            } => Particle::new(
+                (*center_pos).into(),
+                (*center_vel).into(),
+                *center_mass,
+                calibrate,
+            ),
+            Galaxy::Halo {
+                center_pos,
+                center_vel,
+                center_mass,
+                ..
+            } if *center_mass > 0.0 => {
+                Particle::new(*center_pos, *center_vel, *center_mass, calibrate)
+            }
+            // spheres and bare halos have no central mass
+            _ => continue,
+        };
+        particles.push(particle.with_species(species).with_galaxy(i as u32));
+    }
+
+    let center = |pos: [f32; 3], vel: [f32; 3], mass| gen::Center {
+        pos: pos.into(),
+        vel: vel.into(),
+        mass,
+        softening,
+    };
+    for (index, i) in galaxies.iter().enumerate() {
+        let start = particles.len();
+        match *i {
+            Galaxy::Particle { .. } => {}
+            Galaxy::Init {
+                center_pos,
+                center_vel,
+                center_mass,
+                amount,
+                normal,
+            } => gen::formation(
+                rng,
+                &mut particles,
+                amount,
+                &center(center_pos, center_vel, center_mass),
+                normal.into(),
+            ),
+            Galaxy::Disk {
+                center_pos,
+                center_vel,
+                center_mass,
+                amount,
+                normal,
+                profile,
+            } => profile.generate(
+                rng,
+                &mut particles,
+                amount,
+                &center(center_pos, center_vel, center_mass),
+                normal.into(),
+            ),
+            Galaxy::Bulge {
+                center_pos,
+                center_vel,
+                center_mass,
+                amount,
+                profile,
+            } => profile.generate(
+                rng,
+                &mut particles,
+                amount,
+                &center(center_pos, center_vel, center_mass),
+            ),
+            Galaxy::Halo {
+                center_pos,
+                center_vel,
+                center_mass,
+                halo,
+            } => {
+                halo.generate(
+                    rng,
+                    &mut particles,
+                    &center(center_pos, center_vel, center_mass),
+                );
+            }
+            Galaxy::Plummer {
+                center_pos,
+                center_vel,
+                mass,
+                amount,
+                radius,
+            }
+            | Galaxy::Hernquist {
+                center_pos,
+                center_vel,
+                mass,
+                amount,
+                radius,
+            }
+            | Galaxy::Jaffe {
+                center_pos,
+                center_vel,
+                mass,
+                amount,
+                radius,
+            } => {
+                let model = match i {
+                    Galaxy::Plummer { .. } => gen::Model::Plummer,
+                    Galaxy::Hernquist { .. } => gen::Model::Hernquist,
+                    _ => gen::Model::Jaffe,
+                };
+                let center = center(center_pos, center_vel, mass);
+                model.generate(rng, &mut particles, amount, radius, &center)
+            }
+            Galaxy::King {
+                center_pos,
+                center_vel,
+                mass,
+                amount,
+                radius,
+                depth,
+                anisotropy,
+            } => {
+                let model = gen::Model::King {
+                    depth: depth as f64,
+                    anisotropy: anisotropy.map(|ra| (ra / radius) as f64),
+                };
+                let center = center(center_pos, center_vel, mass);
+                model.generate(rng, &mut particles, amount, radius, &center)
+            }
+        }
+        for p in &mut particles[start..] {
+            *p = p.with_galaxy(index as u32);
+        }
+    }
+    assign_ids(&mut particles);
+    particles
+}
@@ -1,9 +1,18 @@
 use wgpu::{util::DeviceExt, SurfaceTexture};
 use {
-    crate::{GpuInfo, Particle},
     cgmath::{prelude::*, Matrix4, PerspectiveFov, Point3, Quaternion, Rad, Vector3},
-    std::{collections::HashSet, f32::consts::PI, time::Instant},
-    winit::{event, event_loop::ControlFlow},
+    nbodysim::{
+        collision,
+        diagnostics::Monitor,
+        snapshot::{self, Checkpoint},
+        GpuInfo, Particle, Scenario, Simulation,
+    },
+    std::{collections::HashSet, f32::consts::PI, path::Path, time::Instant},
+    winit::{
+        event,
+        event_loop::{ControlFlow, EventLoop},
+        platform::run_return::EventLoopExtRunReturn,
+    },
 };
 pub mod state;
 use state::State;
@@ -17,11 +26,31 @@ fn build_matrix(pos: Point3<f32>, dir: Vector3<f32>, aspect: f32) -> Matrix4<f32
     }) * Matrix4::look_to_rh(pos, dir, Vector3::new(0.0, 1.0, 0.0))
 }
 
-pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
-    let mut state: State = State::new(gpu_info, particles).await;
-    let n = state.particles.len();
-    let p_size = (n * std::mem::size_of::<Particle>()) as u64;
-    let workgroups = (n / 256) as u32;
+// returns the simulation once the window is closed
+pub async fn run(
+    particles: Vec<Particle>,
+    scenario: &Scenario,
+    mut monitor: Option<Monitor>,
+    mut log: Option<collision::Log>,
+    resume: Option<&Checkpoint>,
+) -> Simulation {
+    let mut event_loop = EventLoop::new();
+    let mut state: State = State::new(&event_loop, particles, scenario).await;
+    if let Some(checkpoint) = resume {
+        state.sim.resume(checkpoint);
+    }
+    let substeps = scenario.substeps;
+    // checkpoints go to the working directory, periodically and on F5, from snapshots
+    // read back without stalling the frames
+    state.sim.set_snapshot_every(scenario.checkpoint_every);
+    let write_checkpoint = |sim: &Simulation, snapshot| {
+        let checkpoint = sim.checkpoint_from(snapshot);
+        let path = snapshot::checkpoint_path(Path::new("."), checkpoint.substeps);
+        match checkpoint.write(&path) {
+            Ok(()) => println!("wrote {}", path.display()),
+            Err(e) => eprintln!("error: {}", e),
+        }
+    };
 
     let mut cam: Vector3<f32> = Vector3::new(
         -state.display.camera_pos[0],
@@ -29,7 +58,7 @@ pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
         -state.display.camera_pos[2],
     );
     cam = cam.normalize();
-    gpu_info.matrix = build_matrix(
+    state.sim.gpu_info.matrix = build_matrix(
         state.display.camera_pos.into(),
         cam,
         state.display.size.width as f32 / state.display.size.height as f32,
@@ -39,21 +68,8 @@ pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
     let mut keys = HashSet::new();
     let mut right = cam.cross(Vector3::new(0.0, 1.0, 0.0)).normalize();
     let mut update = Instant::now();
-    {
-        let mut encoder =
-            state
-                .display
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("Command Encoder"),
-                });
-
-        encoder.copy_buffer_to_buffer(&state.cur_init, 0, &state.cur, 0, p_size);
-
-        state.display.queue.submit([encoder.finish()]);
-    }
 
-    state.event_loop.run(move |event, _, control_flow| {
+    event_loop.run_return(|event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
         match event {
             event::Event::DeviceEvent {
@@ -83,6 +99,9 @@ pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
                         event::VirtualKeyCode::Escape => {
                             *control_flow = ControlFlow::Exit;
                         }
+                        event::VirtualKeyCode::F5 => {
+                            state.sim.request_snapshot();
+                        }
                         _ => {}
                     }
                     keys.insert(key);
@@ -192,7 +211,7 @@ pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
                         _ => {}
                     }
                 }
-                gpu_info.matrix = build_matrix(
+                state.sim.gpu_info.matrix = build_matrix(
                     tmp.into(),
                     cam,
                     state.display.config.width as f32 / state.display.config.height as f32,
@@ -206,27 +225,20 @@ pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
                         .device
                         .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                             label: Some("GpuInfo Buffer"),
-                            contents: bytemuck::cast_slice(&[gpu_info]),
+                            contents: bytemuck::cast_slice(&[state.sim.gpu_info]),
                             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_SRC,
                         });
 
+                state.sim.encode(&mut encoder, substeps);
+                let gpu = state.sim.gpu().expect("the viewer shares its device");
                 encoder.copy_buffer_to_buffer(
                     &new_gpu_info,
                     0,
-                    &state.gpu_buffer,
+                    &gpu.gpu_buffer,
                     0,
                     std::mem::size_of::<GpuInfo>() as u64,
                 );
 
-                for _ in 0..3 {
-                    encoder.copy_buffer_to_buffer(&state.cur, 0, &state.prev, 0, p_size);
-                    let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                        label: Some("Compute Pass"),
-                    });
-                    cpass.set_pipeline(&state.comp_pipeline);
-                    cpass.set_bind_group(0, &state.bind_group, &[]);
-                    cpass.dispatch_workgroups(workgroups, 1, 1);
-                }
                 {
                     let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                         label: Some("Render Pass"),
@@ -257,11 +269,36 @@ pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
                     });
 
                     rpass.set_pipeline(&state.render_pipeline);
-                    rpass.set_bind_group(0, &state.bind_group, &[]);
-                    rpass.draw(0..n as u32, 0..1);
+                    rpass.set_bind_group(0, &gpu.bind_group, &[]);
+                    rpass.draw(0..state.sim.gpu_info.particles, 0..1);
                 }
                 drop(view);
                 state.display.queue.submit([encoder.finish()]);
+                state.sim.submitted();
+                if let Some(monitor) = &mut monitor {
+                    if monitor.due(state.sim.substeps()) {
+                        let diagnostics = state.sim.diagnostics();
+                        if let Err(e) =
+                            monitor.record(state.sim.substeps(), state.sim.time(), &diagnostics)
+                        {
+                            eprintln!("error: {}", e);
+                            *control_flow = ControlFlow::Exit;
+                        }
+                    }
+                }
+                if let Some(log) = &mut log {
+                    let events = state.sim.collisions();
+                    for event in &events {
+                        println!("{}", event);
+                    }
+                    if let Err(e) = log.record(&events) {
+                        eprintln!("error: {}", e);
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
+                for snapshot in state.sim.snapshots() {
+                    write_checkpoint(&state.sim, snapshot);
+                }
                 surface_texture.present();
                 state
                     .display
@@ -274,4 +311,8 @@ pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
             _ => {}
         }
     });
+    for snapshot in state.sim.wait_snapshots() {
+        write_checkpoint(&state.sim, snapshot);
+    }
+    state.sim
 }
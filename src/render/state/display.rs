@@ -1,3 +1,4 @@
+use std::sync::Arc;
 use wgpu::Error;
 use winit::window::Window;
 
@@ -5,15 +6,15 @@ pub struct Display {
     pub surface: wgpu::Surface,
     pub window: Window,
     pub config: wgpu::SurfaceConfiguration,
-    pub device: wgpu::Device,
-    pub queue: wgpu::Queue,
+    pub device: Arc<wgpu::Device>,
+    pub queue: Arc<wgpu::Queue>,
     pub adapter: wgpu::Adapter,
     pub size: winit::dpi::PhysicalSize<u32>,
     pub camera_pos: [f32; 3],
 }
 
 impl Display {
-    pub async fn new(window: Window) -> Result<Self, Error> {
+    pub async fn new(window: Window, camera_pos: [f32; 3]) -> Result<Self, Error> {
         let size = window.inner_size();
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::all(),
@@ -33,8 +34,7 @@ impl Display {
                 &wgpu::DeviceDescriptor {
                     label: None,
                     features: wgpu::Features::SPIRV_SHADER_PASSTHROUGH | wgpu::Features::SHADER_F64
-                        | wgpu::Features::VERTEX_WRITABLE_STORAGE
-                        | wgpu::Features::MAPPABLE_PRIMARY_BUFFERS,
+                        | wgpu::Features::VERTEX_WRITABLE_STORAGE,
                     limits: if cfg!(target_arch = "wasm32") {
                         wgpu::Limits::downlevel_webgl2_defaults()
                     } else {
@@ -63,13 +63,12 @@ impl Display {
         };
         surface.configure(&device, &config);
 
-        let camera_pos = [0.0, 0.0, 1e10];
         Ok(Self {
             surface,
             window,
             config,
-            device,
-            queue,
+            device: Arc::new(device),
+            queue: Arc::new(queue),
             adapter,
             size,
             camera_pos,
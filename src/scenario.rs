@@ -0,0 +1,272 @@
+use {
+    crate::{
+        collision::Collision,
+        external::{Potential, MAX_POTENTIALS},
+        gadget::Units,
+        integrator::Integrator,
+        softening::Kernel,
+        solver::Solver,
+        timestep::Timestep,
+        Galaxy, Species,
+    },
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
+    std::{
+        collections::BTreeMap,
+        fmt, fs,
+        io::{self, Write},
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+// the scenario used when no file is given on the command line
+const DEFAULT_SCENARIO: &str = include_str!("../scenarios/collision.ron");
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    // the softening ε² in m², of the particles generated or read without one
+    pub calibrate: f64,
+    pub kernel: Kernel,
+    // the softening length ε in m, replaces calibrate with its square
+    pub epsilon: Option<f64>,
+    // ε in m of the particles of these species instead
+    pub species_epsilon: BTreeMap<Species, f64>,
+    pub motion: f32,
+    pub substeps: u32,
+    pub camera_pos: [f32; 3],
+    pub window_size: Option<[u32; 2]>,
+    pub seed: Option<u64>,
+    pub backend: Backend,
+    pub integrator: Integrator,
+    pub timestep: Timestep,
+    // accuracy of the adaptive and block timesteps, smaller is more accurate
+    pub eta: f64,
+    // the finest block level, with steps of motion / 2^max_level
+    pub max_level: u8,
+    pub solver: Solver,
+    // opening angle of the barnes-hut tree, smaller is more accurate
+    pub theta: f64,
+    pub quadrupole: bool,
+    // fixed fields the particles move in as well, at most `external::MAX_POTENTIALS`
+    pub external: Vec<Potential>,
+    pub collision: Collision,
+    // the radius massive particles touch at in m, the softening length of their species
+    // if omitted
+    pub collision_radius: Option<f64>,
+    // heavier particles are at least a sphere of this density in kg/m³
+    pub collision_density: Option<f64>,
+    // append every collision to this csv file
+    pub collision_csv: Option<PathBuf>,
+    // particles closer than this to a central mass in m are swallowed by it, cpu only
+    pub accretion_radius: Option<f64>,
+    // the central masses are the particles at least this heavy in kg and swallow the
+    // lighter ones, without it the black holes swallow only massless particles
+    pub accretion_mass: Option<f64>,
+    // print diagnostics every this many substeps
+    pub diagnostics_every: Option<u32>,
+    // and append them to this csv file as well
+    pub diagnostics_csv: Option<PathBuf>,
+    // write a .nbody checkpoint every this many substeps
+    pub checkpoint_every: Option<u32>,
+    // start from the particles of a snapshot, checkpoint or gadget-2 file instead of the galaxies
+    pub initial: Option<PathBuf>,
+    // the internal units of a gadget-2 initial file
+    pub units: Units,
+    pub galaxies: Vec<Galaxy>,
+}
+
+// where the physics runs, the viewer renders on the GPU either way
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Gpu,
+    Cpu,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Ron,
+    Json,
+}
+
+#[derive(Debug)]
+pub enum FileError {
+    Io(PathBuf, io::Error),
+    UnknownFormat(PathBuf),
+    // a binary file that is truncated, from another program or a newer version, or a
+    // scenario with settings no run can start from
+    Invalid(PathBuf, String),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            calibrate: 1E20,
+            kernel: Kernel::Calibrate,
+            epsilon: None,
+            species_epsilon: BTreeMap::new(),
+            motion: 6.0,
+            substeps: 3,
+            camera_pos: [0.0, 0.0, 1e10],
+            window_size: None,
+            seed: None,
+            backend: Backend::Gpu,
+            integrator: Integrator::Euler,
+            timestep: Timestep::Fixed,
+            eta: 0.025,
+            max_level: 8,
+            solver: Solver::Direct,
+            theta: 0.5,
+            quadrupole: false,
+            external: Vec::new(),
+            collision: Collision::None,
+            collision_radius: None,
+            collision_density: None,
+            collision_csv: None,
+            accretion_radius: None,
+            accretion_mass: None,
+            diagnostics_every: None,
+            diagnostics_csv: None,
+            checkpoint_every: None,
+            initial: None,
+            units: Units::default(),
+            galaxies: Vec::new(),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gpu" => Ok(Backend::Gpu),
+            "cpu" => Ok(Backend::Cpu),
+            _ => Err(format!("unknown backend `{}`, expected `gpu` or `cpu`", s)),
+        }
+    }
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "ron" => Some(Format::Ron),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+impl Scenario {
+    pub fn builtin() -> Self {
+        parse(DEFAULT_SCENARIO, Format::Ron, Path::new("<builtin>"))
+            .expect("builtin scenario is valid")
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FileError> {
+        let path = path.as_ref();
+        let scenario: Self = read(path)?;
+        scenario
+            .validate()
+            .map_err(|message| FileError::Invalid(path.into(), message))?;
+        Ok(scenario)
+    }
+
+    // the settings that parse but would hang the generators or not fit on the device
+    pub fn validate(&self) -> Result<(), String> {
+        if self.external.len() > MAX_POTENTIALS {
+            return Err(format!(
+                "{} external potentials, at most {} are supported",
+                self.external.len(),
+                MAX_POTENTIALS
+            ));
+        }
+        for (i, galaxy) in self.galaxies.iter().enumerate() {
+            match galaxy {
+                Galaxy::Disk { profile, .. } => profile.validate(),
+                Galaxy::Bulge { profile, .. } => profile.validate(),
+                Galaxy::Halo { halo, .. } => halo.validate(),
+                _ => Ok(()),
+            }
+            .map_err(|message| format!("galaxy {}: {}", i + 1, message))?;
+        }
+        Ok(())
+    }
+}
+
+// reads any deserializable value from a .ron or .json file
+pub fn read<T: DeserializeOwned>(path: &Path) -> Result<T, FileError> {
+    let format = Format::from_path(path).ok_or_else(|| FileError::UnknownFormat(path.into()))?;
+    let text = fs::read_to_string(path).map_err(|e| FileError::Io(path.into(), e))?;
+    parse(&text, format, path)
+}
+
+pub fn parse<T: DeserializeOwned>(text: &str, format: Format, path: &Path) -> Result<T, FileError> {
+    match format {
+        Format::Ron => ron::from_str(text).map_err(|e| FileError::Parse {
+            path: path.into(),
+            line: e.position.line,
+            column: e.position.col,
+            message: e.code.to_string(),
+        }),
+        Format::Json => serde_json::from_str(text).map_err(|e| {
+            // serde_json appends the position to its message, we print it ourselves
+            let message = e.to_string();
+            let message = match message.rsplit_once(" at line ") {
+                Some((message, _)) => message.to_string(),
+                None => message,
+            };
+            FileError::Parse {
+                path: path.into(),
+                line: e.line(),
+                column: e.column(),
+                message,
+            }
+        }),
+    }
+}
+
+pub fn write<T: Serialize>(path: &Path, value: &T) -> Result<(), FileError> {
+    let format = Format::from_path(path).ok_or_else(|| FileError::UnknownFormat(path.into()))?;
+    let text = match format {
+        Format::Ron => ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Format::Json => {
+            serde_json::to_string(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+    };
+    text.and_then(|text| {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        file.write_all(text.as_bytes())?;
+        file.flush()
+    })
+    .map_err(|e| FileError::Io(path.into(), e))
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            FileError::UnknownFormat(path) => write!(
+                f,
+                "{}: unknown file format, expected a .ron, .json or .nbody file",
+                path.display()
+            ),
+            FileError::Invalid(path, message) => write!(f, "{}: {}", path.display(), message),
+            FileError::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+        }
+    }
+}
+
+impl std::error::Error for FileError {}
@@ -9,13 +9,27 @@ layout(location = 0) out vec3 fragColor;
 
 struct Particle {
     vec3 pos;
-    float _p1;
+    // species in the low byte, galaxy index above
+    uint tag;
     vec3 vel;
-    float _pad;
+    uint id;
     double mass;
     double calibrate;
 };
 
+const uint HALO = 1;
+const uint BLACK_HOLE = 5;
+
+// one color per galaxy, repeating after six
+const vec3 PALETTE[6] = vec3[6](
+    vec3(0.722, 0.22, 0.231),
+    vec3(0.345, 0.522, 0.635),
+    vec3(0.486, 0.639, 0.345),
+    vec3(0.859, 0.631, 0.282),
+    vec3(0.549, 0.420, 0.678),
+    vec3(0.780, 0.780, 0.780)
+);
+
 layout(set = 0, binding = 0) uniform GlobalsBuffer {
     mat4 matrix;
     uint particles;
@@ -35,19 +49,21 @@ void main() {
     }
     gl_Position = matrix * vec4(data[i].pos, 1.0);
 
-    if (data[i].mass > 0) {
+    uint species = data[i].tag & 0xFF;
+    uint galaxy = data[i].tag >> 8;
+    if (species == HALO) {
+        // the halo is a faint haze around the stars
+        gl_PointSize = 1;
+    } else if (data[i].mass > 0) {
         gl_PointSize = clamp(30 * 1E11 / gl_Position.z, 1, 20);
     } else {
         gl_PointSize = clamp(1 * 1E11 / gl_Position.z, 1, 5);
     }
-    if(data[i].mass > 1E33) {
+    if(species == BLACK_HOLE) {
         fragColor = vec3(0.0, 0.0, 0.0);
+    } else if(species == HALO) {
+        fragColor = PALETTE[galaxy % 6] * 0.35;
     } else {
-        if(i < particles/2+1) {
-            fragColor = vec3(0.722, 0.22, 0.231);
-        }
-        else {
-            fragColor = vec3(0.345, 0.522, 0.635);
-        }
+        fragColor = PALETTE[galaxy % 6];
     }
 }
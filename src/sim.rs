@@ -0,0 +1,571 @@
+use {
+    crate::{
+        accretion::{self, Accretor},
+        assign_ids,
+        collision::{self, Collision, Event, Radii},
+        cpu,
+        diagnostics::{self, Diagnostics},
+        external::{Potentials, MAX_POTENTIALS},
+        integrator::Integrator,
+        scenario::{Backend, Scenario},
+        snapshot::Checkpoint,
+        solver::{Forces, Solver},
+        timestep::{self, Timestep},
+        GpuInfo, Particle, Partition,
+    },
+    cgmath::{Matrix4, SquareMatrix},
+    std::sync::Arc,
+};
+
+pub mod gpu;
+use gpu::Gpu;
+
+/// The particles and whichever backend advances them, independent of any window.
+pub struct Simulation {
+    pub gpu_info: GpuInfo,
+    // stored in checkpoints so a restart runs with the same settings
+    scenario: Scenario,
+    backend: Backend,
+    integrator: Integrator,
+    forces: Forces,
+    timestep: Timestep,
+    eta: f64,
+    max_level: u8,
+    collision: Collision,
+    radii: Radii,
+    accretion_radius: Option<f64>,
+    accretion_mass: Option<f64>,
+    accretors: Vec<Accretor>,
+    // authoritative for the cpu backend, a readback cache for the gpu backend
+    particles: Vec<Particle>,
+    // the block timestep level of each particle, its step is motion / 2^level
+    levels: Vec<u8>,
+    time: f64,
+    substeps: u64,
+    snapshots: Snapshots,
+    // since they were last collected
+    collisions: Vec<Event>,
+    gpu: Option<Gpu>,
+    // the settings of the scenario that could not be honoured
+    warnings: Vec<String>,
+}
+
+/// The particles after `substeps` substeps, `time` seconds into the run.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub substeps: u64,
+    pub time: f64,
+    pub particles: Vec<Particle>,
+}
+
+// when to take snapshots, and those of the cpu backend which are taken right away
+#[derive(Default)]
+struct Snapshots {
+    every: Option<u64>,
+    next: u64,
+    requested: bool,
+    ready: Vec<Snapshot>,
+}
+
+impl Simulation {
+    /// Creates a headless simulation, falling back to the cpu backend when no
+    /// adapter supports 64-bit floats. Particles without an id are numbered.
+    pub async fn new(scenario: &Scenario, mut particles: Vec<Particle>) -> Self {
+        assign_ids(&mut particles);
+        let mut warnings = Vec::new();
+        let forces = forces(scenario, &mut warnings);
+        let mut backend = resolve_backend(scenario, &mut warnings);
+        let mut gpu = None;
+        if backend == Backend::Gpu {
+            match gpu::request_device().await {
+                Some((device, queue)) => {
+                    gpu = Some(Gpu::new(
+                        Arc::new(device),
+                        Arc::new(queue),
+                        gpu_info(scenario, &particles),
+                        &particles,
+                        wgpu::ShaderStages::COMPUTE,
+                        forces,
+                    ))
+                }
+                None => {
+                    warnings.push(
+                        "no adapter with 64-bit float support, using the cpu backend".to_string(),
+                    );
+                    backend = Backend::Cpu;
+                }
+            }
+        }
+        Self {
+            gpu_info: gpu_info(scenario, &particles),
+            scenario: scenario.clone(),
+            backend,
+            integrator: resolve_integrator(scenario, &mut warnings),
+            forces,
+            timestep: scenario.timestep,
+            eta: scenario.eta,
+            max_level: resolve_max_level(scenario, &mut warnings),
+            collision: scenario.collision,
+            radii: Radii::for_scenario(scenario),
+            accretion_radius: scenario.accretion_radius,
+            accretion_mass: scenario.accretion_mass,
+            accretors: accretion::accretors(&particles, scenario.accretion_mass),
+            levels: vec![0; particles.len()],
+            time: 0.0,
+            substeps: 0,
+            snapshots: Snapshots::default(),
+            collisions: Vec::new(),
+            particles,
+            gpu,
+            warnings,
+        }
+    }
+
+    /// Shares an existing device, the buffers are also visible to vertex shaders.
+    pub fn with_device(
+        scenario: &Scenario,
+        mut particles: Vec<Particle>,
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+    ) -> Self {
+        assign_ids(&mut particles);
+        let mut warnings = Vec::new();
+        let forces = forces(scenario, &mut warnings);
+        let gpu_info = gpu_info(scenario, &particles);
+        let gpu = Gpu::new(
+            device,
+            queue,
+            gpu_info,
+            &particles,
+            wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::VERTEX,
+            forces,
+        );
+        Self {
+            gpu_info,
+            scenario: scenario.clone(),
+            backend: resolve_backend(scenario, &mut warnings),
+            integrator: resolve_integrator(scenario, &mut warnings),
+            forces,
+            timestep: scenario.timestep,
+            eta: scenario.eta,
+            max_level: resolve_max_level(scenario, &mut warnings),
+            collision: scenario.collision,
+            radii: Radii::for_scenario(scenario),
+            accretion_radius: scenario.accretion_radius,
+            accretion_mass: scenario.accretion_mass,
+            accretors: accretion::accretors(&particles, scenario.accretion_mass),
+            levels: vec![0; particles.len()],
+            time: 0.0,
+            substeps: 0,
+            snapshots: Snapshots::default(),
+            collisions: Vec::new(),
+            particles,
+            gpu: Some(gpu),
+            warnings,
+        }
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    pub fn integrator(&self) -> Integrator {
+        self.integrator
+    }
+
+    pub fn forces(&self) -> Forces {
+        self.forces
+    }
+
+    pub fn timestep(&self) -> Timestep {
+        self.timestep
+    }
+
+    pub fn collision(&self) -> Collision {
+        self.collision
+    }
+
+    /// The settings of the scenario that could not be honoured and what runs instead,
+    /// for the caller to report.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// The simulated time in seconds since the start.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// The number of substeps since the start.
+    pub fn substeps(&self) -> u64 {
+        self.substeps
+    }
+
+    /// The block timestep level of every particle, all zero unless the timestep is `Block`.
+    pub fn levels(&self) -> &[u8] {
+        &self.levels
+    }
+
+    pub fn gpu(&self) -> Option<&Gpu> {
+        self.gpu.as_ref()
+    }
+
+    /// Advances `steps` substeps and waits for them to finish.
+    pub fn step(&mut self, steps: u32) {
+        match &self.gpu {
+            Some(gpu) if self.backend == Backend::Gpu => {
+                let (device, queue) = (gpu.device.clone(), gpu.queue.clone());
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Step Encoder"),
+                });
+                self.encode(&mut encoder, steps);
+                queue.submit([encoder.finish()]);
+                self.submitted();
+                device.poll(wgpu::Maintain::Wait);
+            }
+            _ => self.step_cpu(steps),
+        }
+    }
+
+    /// Records `steps` substeps into an encoder, the cpu backend runs them right away.
+    /// Call `submitted` once the encoder is submitted.
+    pub fn encode(&mut self, encoder: &mut wgpu::CommandEncoder, steps: u32) {
+        match &mut self.gpu {
+            Some(gpu) if self.backend == Backend::Gpu => {
+                gpu.encode_steps(encoder, steps, self.integrator);
+                self.time += self.gpu_info.motion.max(0.0) as f64 * steps as f64;
+                self.substeps += steps as u64;
+                if self.snapshots.due(self.substeps)
+                    && gpu.encode_readback(encoder, self.substeps, self.time)
+                {
+                    self.snapshots.taken(self.substeps);
+                }
+            }
+            _ => self.step_cpu(steps),
+        }
+    }
+
+    /// Starts reading back the snapshots recorded by `encode`, right after its encoder
+    /// was submitted.
+    pub fn submitted(&mut self) {
+        if let Some(gpu) = &mut self.gpu {
+            gpu.readback_submitted();
+        }
+    }
+
+    /// Takes a snapshot every `every` substeps since the start of the run, collected
+    /// with `snapshots`.
+    pub fn set_snapshot_every(&mut self, every: Option<u32>) {
+        self.snapshots.every = every.map(|every| every.max(1) as u64);
+        self.snapshots.taken(self.substeps);
+    }
+
+    /// Takes a snapshot once the next substeps are done.
+    pub fn request_snapshot(&mut self) {
+        self.snapshots.requested = true;
+    }
+
+    /// The snapshots read back so far, oldest first, without waiting for the device.
+    pub fn snapshots(&mut self) -> Vec<Snapshot> {
+        self.collect_snapshots(false)
+    }
+
+    /// Every requested snapshot, waiting for those still being read back.
+    pub fn wait_snapshots(&mut self) -> Vec<Snapshot> {
+        self.collect_snapshots(true)
+    }
+
+    fn collect_snapshots(&mut self, wait: bool) -> Vec<Snapshot> {
+        let mut snapshots = std::mem::take(&mut self.snapshots.ready);
+        if let Some(gpu) = &mut self.gpu {
+            snapshots.extend(gpu.collect_readbacks(wait));
+        }
+        snapshots
+    }
+
+    fn snapshot_cpu(&mut self) {
+        self.snapshots.ready.push(Snapshot {
+            substeps: self.substeps,
+            time: self.time,
+            particles: self.particles.clone(),
+        });
+        self.snapshots.taken(self.substeps);
+    }
+
+    /// What every central mass accreted since the start, or since the particles were
+    /// last replaced.
+    pub fn accretors(&self) -> &[Accretor] {
+        &self.accretors
+    }
+
+    /// The collisions since the last call, oldest first.
+    pub fn collisions(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.collisions)
+    }
+
+    /// Energy, momenta and center of mass, reduced on the device on the gpu backend.
+    pub fn diagnostics(&self) -> Diagnostics {
+        match &self.gpu {
+            Some(gpu) if self.backend == Backend::Gpu => gpu.diagnostics(),
+            _ => diagnostics::measure_with(
+                &self.particles,
+                self.forces.kernel,
+                &self.forces.external,
+            ),
+        }
+    }
+
+    /// The current particles, read back from the device on the gpu backend.
+    pub fn particles(&mut self) -> &[Particle] {
+        if let (Backend::Gpu, Some(gpu)) = (self.backend, &self.gpu) {
+            self.particles = gpu.download();
+        }
+        &self.particles
+    }
+
+    /// The particles, clock, block levels and accretors, everything `resume` needs to continue
+    /// with bit-identical results on the same backend.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        let snapshot = Snapshot {
+            substeps: self.substeps,
+            time: self.time,
+            particles: self.particles().to_vec(),
+        };
+        self.checkpoint_from(snapshot)
+    }
+
+    /// A checkpoint of a snapshot, with the current block levels and accretors. Only the cpu backend
+    /// has any and it takes its snapshots right away, so collect them before stepping on.
+    pub fn checkpoint_from(&self, snapshot: Snapshot) -> Checkpoint {
+        Checkpoint {
+            scenario: self.scenario.clone(),
+            time: snapshot.time,
+            substeps: snapshot.substeps,
+            levels: self.levels.clone(),
+            particles: snapshot.particles,
+            accretors: self.accretors.clone(),
+        }
+    }
+
+    /// Continues from a checkpoint, the settings stay those the simulation was created with.
+    pub fn resume(&mut self, checkpoint: &Checkpoint) {
+        self.set_particles(checkpoint.particles.clone());
+        if checkpoint.levels.len() == self.levels.len() {
+            self.levels = checkpoint.levels.clone();
+        }
+        // what the central masses swallowed before, which the particles no longer show
+        for accretor in &checkpoint.accretors {
+            match self.accretors.iter_mut().find(|a| a.id == accretor.id) {
+                Some(a) => *a = *accretor,
+                None => self.accretors.push(*accretor),
+            }
+        }
+        self.accretors.sort_by_key(|a| a.id);
+        self.time = checkpoint.time;
+        self.substeps = checkpoint.substeps;
+        self.snapshots.taken(self.substeps);
+    }
+
+    /// Replaces every particle, the count may change.
+    pub fn set_particles(&mut self, mut particles: Vec<Particle>) {
+        assign_ids(&mut particles);
+        self.gpu_info.particles = particles.len() as u32;
+        self.gpu_info.massive = Partition::new(&particles).massive.len() as u32;
+        if let Some(gpu) = &mut self.gpu {
+            if particles.len() == self.particles.len() {
+                gpu.upload(&particles);
+                gpu.upload_info(self.gpu_info);
+            } else {
+                *gpu = gpu.resized(self.gpu_info, &particles);
+            }
+        }
+        self.levels = vec![0; particles.len()];
+        self.accretors = accretion::accretors(&particles, self.accretion_mass);
+        self.particles = particles;
+    }
+
+    fn step_cpu(&mut self, steps: u32) {
+        if self.collision == Collision::None && self.accretion_radius.is_none() {
+            self.time += self.advance_cpu(steps);
+        } else {
+            // one substep at a time, so pairs are caught before they pass each other
+            let mut removed = false;
+            for step in 1..=steps as u64 {
+                self.time += self.advance_cpu(1);
+                let events = collision::collide(
+                    &mut self.particles,
+                    self.collision,
+                    self.radii,
+                    self.substeps + step,
+                    self.time,
+                );
+                removed |= self.collision == Collision::Merge && !events.is_empty();
+                self.collisions.extend(events);
+                if let Some(radius) = self.accretion_radius {
+                    removed |= accretion::accrete(
+                        &mut self.particles,
+                        radius,
+                        self.accretion_mass,
+                        &mut self.accretors,
+                        self.time,
+                    ) > 0;
+                }
+            }
+            if removed {
+                self.gpu_info.massive = Partition::new(&self.particles).massive.len() as u32;
+                if let Some(gpu) = &self.gpu {
+                    gpu.upload_info(self.gpu_info);
+                }
+            }
+        }
+        if let Some(gpu) = &self.gpu {
+            gpu.upload(&self.particles);
+        }
+        self.substeps += steps as u64;
+        if self.snapshots.due(self.substeps) {
+            self.snapshot_cpu();
+        }
+    }
+
+    // the simulated time `steps` substeps took
+    fn advance_cpu(&mut self, steps: u32) -> f64 {
+        let motion = self.gpu_info.motion;
+        match self.timestep {
+            Timestep::Fixed => {
+                cpu::advance(
+                    &mut self.particles,
+                    self.integrator,
+                    self.forces,
+                    motion,
+                    steps,
+                );
+                if motion > 0.0 {
+                    motion as f64 * steps as f64
+                } else {
+                    0.0
+                }
+            }
+            Timestep::Adaptive => cpu::advance_adaptive(
+                &mut self.particles,
+                self.integrator,
+                self.forces,
+                motion,
+                self.eta,
+                steps,
+            ),
+            Timestep::Block => cpu::advance_block(
+                &mut self.particles,
+                &mut self.levels,
+                self.forces,
+                motion,
+                self.eta,
+                self.max_level,
+                steps,
+            ),
+        }
+    }
+}
+
+impl Snapshots {
+    fn due(&self, substeps: u64) -> bool {
+        self.requested || self.every.is_some_and(|_| substeps >= self.next)
+    }
+
+    fn taken(&mut self, substeps: u64) {
+        self.requested = false;
+        if let Some(every) = self.every {
+            self.next = (substeps / every + 1) * every;
+        }
+    }
+}
+
+fn resolve_backend(scenario: &Scenario, warnings: &mut Vec<String>) -> Backend {
+    if scenario.backend == Backend::Gpu && !scenario.integrator.runs_on_gpu() {
+        warnings.push(format!(
+            "the {:?} integrator only runs on the cpu backend",
+            scenario.integrator
+        ));
+        return Backend::Cpu;
+    }
+    if scenario.backend == Backend::Gpu && !scenario.solver.runs_on_gpu() {
+        warnings.push(format!(
+            "the {:?} solver only runs on the cpu backend",
+            scenario.solver
+        ));
+        return Backend::Cpu;
+    }
+    if scenario.backend == Backend::Gpu && !scenario.timestep.runs_on_gpu() {
+        warnings.push(format!(
+            "the {:?} timestep only runs on the cpu backend",
+            scenario.timestep
+        ));
+        return Backend::Cpu;
+    }
+    if scenario.backend == Backend::Gpu && !scenario.collision.runs_on_gpu() {
+        warnings.push(format!(
+            "{:?} collisions only run on the cpu backend",
+            scenario.collision
+        ));
+        return Backend::Cpu;
+    }
+    if scenario.backend == Backend::Gpu && scenario.accretion_radius.is_some() {
+        warnings.push("accretion only runs on the cpu backend".to_string());
+        return Backend::Cpu;
+    }
+    scenario.backend
+}
+
+fn resolve_integrator(scenario: &Scenario, warnings: &mut Vec<String>) -> Integrator {
+    if scenario.timestep == Timestep::Block && scenario.integrator != Integrator::Leapfrog {
+        warnings.push(format!(
+            "block timesteps use the leapfrog integrator instead of {:?}",
+            scenario.integrator
+        ));
+        return Integrator::Leapfrog;
+    }
+    scenario.integrator
+}
+
+fn forces(scenario: &Scenario, warnings: &mut Vec<String>) -> Forces {
+    if scenario.solver == Solver::BarnesHut && scenario.integrator == Integrator::Hermite {
+        warnings
+            .push("the Hermite integrator sums its forces directly, ignoring the tree".to_string());
+    }
+    // scenarios built in code skip the checks of `Scenario::load`
+    if scenario.external.len() > MAX_POTENTIALS {
+        warnings.push(format!(
+            "only the first {} of the {} external potentials are used",
+            MAX_POTENTIALS,
+            scenario.external.len()
+        ));
+    }
+    Forces {
+        solver: scenario.solver,
+        theta: scenario.theta,
+        quadrupole: scenario.quadrupole,
+        kernel: scenario.kernel,
+        external: Potentials::new(&scenario.external),
+    }
+}
+
+fn resolve_max_level(scenario: &Scenario, warnings: &mut Vec<String>) -> u8 {
+    if scenario.max_level > timestep::MAX_LEVEL {
+        warnings.push(format!(
+            "max_level {} is too fine, using {}",
+            scenario.max_level,
+            timestep::MAX_LEVEL
+        ));
+        return timestep::MAX_LEVEL;
+    }
+    scenario.max_level
+}
+
+fn gpu_info(scenario: &Scenario, particles: &[Particle]) -> GpuInfo {
+    GpuInfo {
+        matrix: Matrix4::identity().into(),
+        particles: particles.len() as u32,
+        motion: scenario.motion,
+        massive: Partition::new(particles).massive.len() as u32,
+        softening: scenario.kernel.code(),
+    }
+}
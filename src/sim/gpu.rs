@@ -0,0 +1,421 @@
+use {
+    crate::{
+        diagnostics::Diagnostics,
+        external::GpuPotential,
+        integrator::Integrator,
+        sim::Snapshot,
+        solver::{Forces, Solver},
+        GpuInfo, Particle, Partition,
+    },
+    std::sync::{mpsc, Arc},
+    wgpu::util::DeviceExt,
+};
+
+mod readback;
+mod reduction;
+use {readback::Readback, reduction::Reduction};
+
+pub struct Gpu {
+    pub device: Arc<wgpu::Device>,
+    pub queue: Arc<wgpu::Queue>,
+    pub prev: wgpu::Buffer,
+    pub cur: wgpu::Buffer,
+    pub staging: wgpu::Buffer,
+    // indices of the massive particles, the first `GpuInfo::massive` are valid
+    pub massive: wgpu::Buffer,
+    // the external potentials, one unused slot if there are none
+    pub external: wgpu::Buffer,
+    pub gpu_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    pub pipeline_layout: wgpu::PipelineLayout,
+    pub comp_pipeline: wgpu::ComputePipeline,
+    pub kick_pipeline: wgpu::ComputePipeline,
+    pub kick_half_pipeline: wgpu::ComputePipeline,
+    pub drift_pipeline: wgpu::ComputePipeline,
+    reduction: Reduction,
+    readback: Readback,
+    visibility: wgpu::ShaderStages,
+    forces: Forces,
+    p_size: u64,
+    workgroups: u32,
+}
+
+// a device for compute only, None if no adapter supports 64-bit floats
+pub async fn request_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        dx12_shader_compiler: Default::default(),
+    });
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        })
+        .await?;
+    if !adapter.features().contains(wgpu::Features::SHADER_F64) {
+        return None;
+    }
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::SHADER_F64,
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .ok()
+}
+
+impl Gpu {
+    pub fn new(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        gpu_info: GpuInfo,
+        particles: &[Particle],
+        visibility: wgpu::ShaderStages,
+        forces: Forces,
+    ) -> Self {
+        let p_size = std::mem::size_of_val(particles) as u64;
+        // bindings cannot be empty, an empty scenario still gets room for one particle
+        let buffer_size = p_size.max(std::mem::size_of::<Particle>() as u64);
+        let cs_mod = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compute Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../compute.wgsl").into()),
+        });
+
+        let gpu_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("GpuInfo Buffer"),
+            contents: bytemuck::cast_slice(&[gpu_info]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let prev = device.create_buffer(&wgpu::BufferDescriptor {
+            size: buffer_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            label: Some("Old Buffer"),
+            mapped_at_creation: false,
+        });
+        let cur = device.create_buffer(&wgpu::BufferDescriptor {
+            size: buffer_size,
+            usage: wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::STORAGE,
+            label: Some("Current Buffer"),
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&cur, 0, bytemuck::cast_slice(particles));
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            size: buffer_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            label: Some("Staging Buffer"),
+            mapped_at_creation: false,
+        });
+
+        // room for every particle so the partition can change without new buffers
+        let massive = device.create_buffer(&wgpu::BufferDescriptor {
+            size: (particles.len().max(1) * std::mem::size_of::<u32>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            label: Some("Massive Index Buffer"),
+            mapped_at_creation: false,
+        });
+        let external = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("External Potential Buffer"),
+            contents: bytemuck::cast_slice(&forces.external.to_gpu()),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<GpuInfo>() as _
+                            ),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<Particle>() as _,
+                            ),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<Particle>() as _,
+                            ),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<u32>() as _
+                            ),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<GpuPotential>() as _,
+                            ),
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: gpu_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: prev.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: cur.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: massive.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: external.as_entire_binding(),
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label, entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                module: &cs_mod,
+                entry_point,
+                layout: Some(&pipeline_layout),
+            })
+        };
+        let (main, kick, kick_half) = match forces.solver {
+            Solver::Tiled => ("main_tiled", "kick_tiled", "kick_half_tiled"),
+            Solver::Direct | Solver::BarnesHut => ("main", "kick", "kick_half"),
+        };
+        let comp_pipeline = pipeline("Compute Pipeline", main);
+        let kick_pipeline = pipeline("Kick Pipeline", kick);
+        let kick_half_pipeline = pipeline("Half Kick Pipeline", kick_half);
+        let drift_pipeline = pipeline("Drift Pipeline", "drift");
+
+        let reduction = Reduction::new(
+            &device,
+            &gpu_buffer,
+            &cur,
+            &massive,
+            &external,
+            particles.len(),
+        );
+        let readback = Readback::new(&device, p_size);
+
+        let gpu = Self {
+            device,
+            queue,
+            prev,
+            cur,
+            staging,
+            massive,
+            external,
+            gpu_buffer,
+            bind_group,
+            pipeline_layout,
+            comp_pipeline,
+            kick_pipeline,
+            kick_half_pipeline,
+            drift_pipeline,
+            reduction,
+            readback,
+            visibility,
+            forces,
+            p_size,
+            workgroups: (particles.len() as u32).div_ceil(256),
+        };
+        gpu.upload_partition(particles);
+        gpu
+    }
+
+    // new buffers on the same device, for a different particle count
+    pub fn resized(&self, gpu_info: GpuInfo, particles: &[Particle]) -> Self {
+        Self::new(
+            self.device.clone(),
+            self.queue.clone(),
+            gpu_info,
+            particles,
+            self.visibility,
+            self.forces,
+        )
+    }
+
+    pub fn encode_steps(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        steps: u32,
+        integrator: Integrator,
+    ) {
+        match integrator {
+            Integrator::Euler => {
+                for _ in 0..steps {
+                    self.encode_copy_old(encoder);
+                    self.encode_pass(encoder, &self.comp_pipeline);
+                }
+            }
+            // the half kicks only happen at both ends of the batch, in between
+            // the two halves of neighbouring substeps merge into one full kick
+            Integrator::Leapfrog => {
+                if steps == 0 {
+                    return;
+                }
+                self.encode_copy_old(encoder);
+                self.encode_pass(encoder, &self.kick_half_pipeline);
+                for step in 0..steps {
+                    self.encode_pass(encoder, &self.drift_pipeline);
+                    self.encode_copy_old(encoder);
+                    if step + 1 == steps {
+                        self.encode_pass(encoder, &self.kick_half_pipeline);
+                    } else {
+                        self.encode_pass(encoder, &self.kick_pipeline);
+                    }
+                }
+            }
+            Integrator::Rk4 | Integrator::Yoshida | Integrator::Hermite => {
+                unreachable!("{:?} only runs on the cpu backend", integrator)
+            }
+        }
+    }
+
+    // the force passes read positions from `prev` while writing `cur`
+    fn encode_copy_old(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.copy_buffer_to_buffer(&self.cur, 0, &self.prev, 0, self.p_size);
+    }
+
+    fn encode_pass(&self, encoder: &mut wgpu::CommandEncoder, pipeline: &wgpu::ComputePipeline) {
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Compute Pass"),
+        });
+        cpass.set_pipeline(pipeline);
+        cpass.set_bind_group(0, &self.bind_group, &[]);
+        cpass.dispatch_workgroups(self.workgroups, 1, 1);
+    }
+
+    pub fn upload(&self, particles: &[Particle]) {
+        self.queue
+            .write_buffer(&self.cur, 0, bytemuck::cast_slice(particles));
+        self.upload_partition(particles);
+    }
+
+    // the count goes into `GpuInfo::massive`, which the caller uploads
+    fn upload_partition(&self, particles: &[Particle]) {
+        let massive: Vec<u32> = Partition::new(particles)
+            .massive
+            .into_iter()
+            .map(|i| i as u32)
+            .collect();
+        if !massive.is_empty() {
+            self.queue
+                .write_buffer(&self.massive, 0, bytemuck::cast_slice(&massive));
+        }
+    }
+
+    pub fn upload_info(&self, gpu_info: GpuInfo) {
+        self.queue
+            .write_buffer(&self.gpu_buffer, 0, bytemuck::cast_slice(&[gpu_info]));
+    }
+
+    // blocks until every submitted step is done and copies the particles back
+    pub fn download(&self) -> Vec<Particle> {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Readback Encoder"),
+            });
+        encoder.copy_buffer_to_buffer(&self.cur, 0, &self.staging, 0, self.p_size);
+        self.queue.submit([encoder.finish()]);
+        let mut particles: Vec<Particle> = read_mapped(&self.device, &self.staging);
+        particles.truncate(self.p_size as usize / std::mem::size_of::<Particle>());
+        particles
+    }
+
+    // queues a copy of the particles as they are after everything encoded so far,
+    // false if every readback buffer is still in use
+    pub fn encode_readback(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        substeps: u64,
+        time: f64,
+    ) -> bool {
+        self.readback.encode(encoder, &self.cur, substeps, time)
+    }
+
+    // must follow the submission of the encoder the readbacks were recorded into
+    pub fn readback_submitted(&mut self) {
+        self.readback.submitted();
+    }
+
+    pub fn collect_readbacks(&mut self, wait: bool) -> Vec<Snapshot> {
+        self.readback.collect(&self.device, wait)
+    }
+
+    // reduces the current particles on the device, only the totals are read back
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.reduction.measure(&self.device, &self.queue)
+    }
+}
+
+// maps a MAP_READ buffer once the queue is idle and copies it out
+fn read_mapped<T: bytemuck::Pod>(device: &wgpu::Device, buffer: &wgpu::Buffer) -> Vec<T> {
+    let slice = buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        sender.send(result).ok();
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .expect("map callback dropped")
+        .expect("failed to map the staging buffer");
+    let data = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+    buffer.unmap();
+    data
+}
@@ -0,0 +1,137 @@
+use {
+    crate::{sim::Snapshot, Particle},
+    std::sync::mpsc,
+};
+
+// copies in flight at once, a request while all of them are busy waits for the next one
+const SLOTS: usize = 3;
+
+// a ring of staging buffers the particles are copied into and mapped asynchronously,
+// so the host gets snapshots without waiting for the device
+pub struct Readback {
+    slots: Vec<Slot>,
+    // of the particles, the buffers hold at least one since slices cannot be empty
+    size: u64,
+}
+
+struct Slot {
+    buffer: wgpu::Buffer,
+    state: State,
+}
+
+enum State {
+    Free,
+    // the copy is recorded but its encoder may not be submitted yet
+    Copied {
+        substeps: u64,
+        time: f64,
+    },
+    Mapping {
+        substeps: u64,
+        time: f64,
+        done: mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    },
+}
+
+impl Readback {
+    pub fn new(device: &wgpu::Device, size: u64) -> Self {
+        let slots = (0..SLOTS)
+            .map(|_| Slot {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    size: size.max(std::mem::size_of::<Particle>() as u64),
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    label: Some("Readback Buffer"),
+                    mapped_at_creation: false,
+                }),
+                state: State::Free,
+            })
+            .collect();
+        Self { slots, size }
+    }
+
+    // records a copy of `cur` into a free slot, false if every slot is busy
+    pub fn encode(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        cur: &wgpu::Buffer,
+        substeps: u64,
+        time: f64,
+    ) -> bool {
+        match self
+            .slots
+            .iter_mut()
+            .find(|slot| matches!(slot.state, State::Free))
+        {
+            Some(slot) => {
+                encoder.copy_buffer_to_buffer(cur, 0, &slot.buffer, 0, self.size);
+                slot.state = State::Copied { substeps, time };
+                true
+            }
+            None => false,
+        }
+    }
+
+    // starts mapping the copied slots, only valid once their encoder is submitted
+    pub fn submitted(&mut self) {
+        for slot in &mut self.slots {
+            if let State::Copied { substeps, time } = slot.state {
+                let (sender, done) = mpsc::channel();
+                slot.buffer
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |result| {
+                        sender.send(result).ok();
+                    });
+                slot.state = State::Mapping {
+                    substeps,
+                    time,
+                    done,
+                };
+            }
+        }
+    }
+
+    // the snapshots whose mapping finished, oldest first, with `wait` every mapping
+    // still in flight is waited for
+    pub fn collect(&mut self, device: &wgpu::Device, wait: bool) -> Vec<Snapshot> {
+        device.poll(if wait {
+            wgpu::Maintain::Wait
+        } else {
+            wgpu::Maintain::Poll
+        });
+        let mut snapshots = Vec::new();
+        let size = self.size as usize;
+        for slot in &mut self.slots {
+            let State::Mapping {
+                substeps,
+                time,
+                done,
+            } = &slot.state
+            else {
+                continue;
+            };
+            let result = if wait {
+                done.recv().ok()
+            } else {
+                done.try_recv().ok()
+            };
+            match result {
+                Some(Ok(())) => {
+                    let range = slot.buffer.slice(..).get_mapped_range();
+                    let particles: Vec<Particle> = bytemuck::cast_slice(&range[..size]).to_vec();
+                    drop(range);
+                    slot.buffer.unmap();
+                    snapshots.push(Snapshot {
+                        substeps: *substeps,
+                        time: *time,
+                        particles,
+                    });
+                }
+                Some(Err(e)) => panic!("failed to map a readback buffer: {}", e),
+                None => continue,
+            }
+            slot.state = State::Free;
+        }
+        snapshots.sort_by_key(|snapshot| snapshot.substeps);
+        snapshots
+    }
+}
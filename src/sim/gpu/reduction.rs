@@ -0,0 +1,138 @@
+use {
+    super::read_mapped,
+    crate::diagnostics::{Diagnostics, SUMS},
+};
+
+// must match the workgroup size in diagnostics.wgsl
+const WORKGROUP_SIZE: u32 = 64;
+
+// diagnostics.wgsl, one row of partial sums per workgroup which the cpu adds up
+pub struct Reduction {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    partials: wgpu::Buffer,
+    staging: wgpu::Buffer,
+    workgroups: u32,
+}
+
+impl Reduction {
+    pub fn new(
+        device: &wgpu::Device,
+        gpu_buffer: &wgpu::Buffer,
+        cur: &wgpu::Buffer,
+        massive: &wgpu::Buffer,
+        external: &wgpu::Buffer,
+        particles: usize,
+    ) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Diagnostics Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../diagnostics.wgsl").into()),
+        });
+        let workgroups = (particles as u32).div_ceil(WORKGROUP_SIZE).max(1);
+        let size = (workgroups as usize * SUMS * std::mem::size_of::<f64>()) as u64;
+        let partials = device.create_buffer(&wgpu::BufferDescriptor {
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            label: Some("Partial Sums Buffer"),
+            mapped_at_creation: false,
+        });
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            label: Some("Partial Sums Staging Buffer"),
+            mapped_at_creation: false,
+        });
+
+        let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Diagnostics Bind Group Layout"),
+            entries: &[
+                entry(0, wgpu::BufferBindingType::Uniform),
+                entry(1, wgpu::BufferBindingType::Storage { read_only: true }),
+                entry(2, wgpu::BufferBindingType::Storage { read_only: true }),
+                entry(3, wgpu::BufferBindingType::Storage { read_only: false }),
+                entry(4, wgpu::BufferBindingType::Storage { read_only: true }),
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Diagnostics Bind Group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: gpu_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: cur.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: massive.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: partials.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: external.as_entire_binding(),
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Diagnostics Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Diagnostics Pipeline"),
+            module: &module,
+            entry_point: "main",
+            layout: Some(&pipeline_layout),
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            partials,
+            staging,
+            workgroups,
+        }
+    }
+
+    // runs after every step submitted so far and blocks for the result
+    pub fn measure(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Diagnostics {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Diagnostics Encoder"),
+        });
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Diagnostics Pass"),
+            });
+            cpass.set_pipeline(&self.pipeline);
+            cpass.set_bind_group(0, &self.bind_group, &[]);
+            cpass.dispatch_workgroups(self.workgroups, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.partials, 0, &self.staging, 0, self.partials.size());
+        queue.submit([encoder.finish()]);
+
+        let partials: Vec<f64> = read_mapped(device, &self.staging);
+        let mut sums = [0.0; SUMS];
+        for row in partials.chunks_exact(SUMS) {
+            for k in 0..SUMS {
+                sums[k] += row[k];
+            }
+        }
+        Diagnostics::from_sums(&sums)
+    }
+}
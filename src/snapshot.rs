@@ -0,0 +1,241 @@
+use {
+    crate::{
+        accretion::Accretor,
+        assign_ids,
+        gadget::{self, Units},
+        scenario::{self, FileError, Scenario},
+        Particle,
+    },
+    std::{
+        fs,
+        io::{self, Write},
+        path::{Path, PathBuf},
+    },
+};
+
+// the first bytes of every checkpoint file
+const MAGIC: &[u8; 8] = b"NBODYSIM";
+// bumped whenever the layout below changes, older versions are not read
+pub const VERSION: u32 = 4;
+// the extension of checkpoint files
+pub const EXTENSION: &str = "nbody";
+
+// everything needed to continue a run exactly where it stopped, stored little endian as
+//   magic, version u32, time f64, substeps u64,
+//   scenario length u64, scenario as ron text,
+//   particle count u64, block level u8 per particle,
+//   pos 3 x f32, vel 3 x f32, mass f64, calibrate f64, tag u32, id u32 per particle,
+//   accretor count u64,
+//   id u32, galaxy u32, particles u64, mass f64, first f64, last f64 per accretor,
+//   with NaN times before its first capture
+// the random generator is only drawn from while the galaxies are built, the seed in
+// the scenario is all of its state a restart needs
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    pub scenario: Scenario,
+    pub time: f64,
+    pub substeps: u64,
+    pub levels: Vec<u8>,
+    pub particles: Vec<Particle>,
+    pub accretors: Vec<Accretor>,
+}
+
+// particle snapshots are plain arrays in the same .ron / .json formats as scenarios,
+// or the particles of a .nbody checkpoint
+pub fn save(path: &Path, particles: &[Particle]) -> Result<(), FileError> {
+    scenario::write(path, &particles)
+}
+
+// also reads gadget-2 files, in gadget's default units and with the default softening
+pub fn load(path: &Path) -> Result<Vec<Particle>, FileError> {
+    load_with(path, &Units::default(), Scenario::default().calibrate)
+}
+
+// `units` and `calibrate` only apply to gadget-2 files, which carry neither, particles
+// of older snapshots without ids are numbered after the others
+pub fn load_with(path: &Path, units: &Units, calibrate: f64) -> Result<Vec<Particle>, FileError> {
+    if is_checkpoint(path) {
+        return Checkpoint::read(path).map(|checkpoint| checkpoint.particles);
+    }
+    if scenario::Format::from_path(path).is_none() && gadget::is_gadget(path) {
+        return gadget::read(path, units, calibrate).map(|(_, particles)| particles);
+    }
+    let mut particles: Vec<Particle> = scenario::read(path)?;
+    assign_ids(&mut particles);
+    Ok(particles)
+}
+
+// checkpoint_000120.nbody for the checkpoint after 120 substeps
+pub fn checkpoint_path(dir: &Path, substeps: u64) -> PathBuf {
+    dir.join(format!("checkpoint_{:06}.{}", substeps, EXTENSION))
+}
+
+pub fn is_checkpoint(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case(EXTENSION))
+}
+
+impl Checkpoint {
+    pub fn write(&self, path: &Path) -> Result<(), FileError> {
+        let scenario = ron::to_string(&self.scenario).map_err(|e| {
+            FileError::Io(path.into(), io::Error::new(io::ErrorKind::InvalidData, e))
+        })?;
+        let mut bytes = Vec::with_capacity(64 + scenario.len() + self.particles.len() * 49);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.time.to_le_bytes());
+        bytes.extend_from_slice(&self.substeps.to_le_bytes());
+        bytes.extend_from_slice(&(scenario.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(scenario.as_bytes());
+        bytes.extend_from_slice(&(self.particles.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.levels);
+        for p in &self.particles {
+            for x in p.pos.iter().chain(&p.vel) {
+                bytes.extend_from_slice(&x.to_le_bytes());
+            }
+            bytes.extend_from_slice(&p.mass.to_le_bytes());
+            bytes.extend_from_slice(&p.calibrate.to_le_bytes());
+            bytes.extend_from_slice(&p.tag.to_le_bytes());
+            bytes.extend_from_slice(&p.id.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.accretors.len() as u64).to_le_bytes());
+        for a in &self.accretors {
+            bytes.extend_from_slice(&a.id.to_le_bytes());
+            bytes.extend_from_slice(&a.galaxy.to_le_bytes());
+            bytes.extend_from_slice(&a.particles.to_le_bytes());
+            for x in [
+                a.mass,
+                a.first.unwrap_or(f64::NAN),
+                a.last.unwrap_or(f64::NAN),
+            ] {
+                bytes.extend_from_slice(&x.to_le_bytes());
+            }
+        }
+        // write to a temporary file first so an interrupted write keeps the last checkpoint
+        let temporary = path.with_extension("nbody.tmp");
+        fs::File::create(&temporary)
+            .and_then(|mut file| {
+                file.write_all(&bytes)?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(|e| FileError::Io(path.into(), e))
+    }
+
+    pub fn read(path: &Path) -> Result<Self, FileError> {
+        let bytes = fs::read(path).map_err(|e| FileError::Io(path.into(), e))?;
+        let invalid = |message: String| FileError::Invalid(path.into(), message);
+        let mut reader = Reader {
+            bytes: &bytes,
+            offset: 0,
+        };
+        if reader.take(MAGIC.len()) != Some(MAGIC.as_slice()) {
+            return Err(invalid("not an nbodysim checkpoint".to_string()));
+        }
+        let truncated = || invalid("truncated checkpoint".to_string());
+        let version = reader.u32().ok_or_else(truncated)?;
+        if version != VERSION {
+            return Err(invalid(format!(
+                "checkpoint version {} is not supported, expected {}",
+                version, VERSION
+            )));
+        }
+        let time = reader.f64().ok_or_else(truncated)?;
+        let substeps = reader.u64().ok_or_else(truncated)?;
+        let length = reader.u64().ok_or_else(truncated)? as usize;
+        let text = reader.take(length).ok_or_else(truncated)?;
+        let text = std::str::from_utf8(text)
+            .map_err(|_| invalid("the stored scenario is not utf-8".to_string()))?;
+        let scenario = scenario::parse(text, scenario::Format::Ron, path)?;
+        let count = reader.u64().ok_or_else(truncated)? as usize;
+        let levels = reader.take(count).ok_or_else(truncated)?.to_vec();
+        let mut particles = Vec::with_capacity(count.min(bytes.len() / 48));
+        for _ in 0..count {
+            let mut f32s = [0.0; 6];
+            for x in &mut f32s {
+                *x = reader.f32().ok_or_else(truncated)?;
+            }
+            let mass = reader.f64().ok_or_else(truncated)?;
+            let calibrate = reader.f64().ok_or_else(truncated)?;
+            let mut particle = Particle::new(
+                [f32s[0], f32s[1], f32s[2]],
+                [f32s[3], f32s[4], f32s[5]],
+                mass,
+                calibrate,
+            );
+            particle.tag = reader.u32().ok_or_else(truncated)?;
+            particle.id = reader.u32().ok_or_else(truncated)?;
+            particles.push(particle);
+        }
+        let count = reader.u64().ok_or_else(truncated)? as usize;
+        let mut accretors = Vec::with_capacity(count.min(bytes.len() / 40));
+        for _ in 0..count {
+            let id = reader.u32().ok_or_else(truncated)?;
+            let galaxy = reader.u32().ok_or_else(truncated)?;
+            let captured = reader.u64().ok_or_else(truncated)?;
+            let mass = reader.f64().ok_or_else(truncated)?;
+            let mut time = || -> Result<Option<f64>, FileError> {
+                let time = reader.f64().ok_or_else(truncated)?;
+                Ok(Some(time).filter(|time| !time.is_nan()))
+            };
+            let (first, last) = (time()?, time()?);
+            accretors.push(Accretor {
+                id,
+                galaxy,
+                particles: captured,
+                mass,
+                first,
+                last,
+            });
+        }
+        if reader.offset != bytes.len() {
+            return Err(invalid(format!(
+                "{} unexpected bytes after the accretors",
+                bytes.len() - reader.offset
+            )));
+        }
+        Ok(Self {
+            scenario,
+            time,
+            substeps,
+            levels,
+            particles,
+            accretors,
+        })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let end = self.offset.checked_add(n)?;
+        let slice = self.bytes.get(self.offset..end)?;
+        self.offset = end;
+        Some(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        self.array().map(f32::from_le_bytes)
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        self.array().map(f64::from_le_bytes)
+    }
+}
@@ -0,0 +1,228 @@
+use {
+    crate::{scenario::Scenario, Particle, Species},
+    cgmath::{prelude::*, Vector3},
+    serde::{Deserialize, Serialize},
+    std::str::FromStr,
+};
+
+// how the pull of a particle is softened at short range, the same kernel is used by
+// the forces, the potential energy and the velocities of generated galaxies. every
+// kernel is given ε² as `calibrate`, a pair uses the larger ε of the two
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Kernel {
+    // m / (r² + ε²) along the direction, what the simulation always used
+    #[default]
+    Calibrate,
+    // m r / (r² + ε²)^(3/2), the potential of a plummer sphere of scale radius ε
+    Plummer,
+    // the cubic spline of Monaghan & Lattanzio as in Gadget, newtonian beyond 2.8 ε
+    Spline,
+    // a Wendland C2 density, newtonian beyond 3 ε
+    Wendland,
+    // plain newtonian gravity, ε only matters to the timesteps and collisions
+    None,
+}
+
+// the kernel and ε² of every species, the particles carry their ε² as `calibrate`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Softening {
+    pub kernel: Kernel,
+    // indexed by the species code
+    calibrate: [f64; 6],
+}
+
+impl Kernel {
+    // the value of `GpuInfo::softening` the shaders switch on
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    // g(r) such that the acceleration towards a mass m at offset d is G m g(r) d
+    pub fn force(self, r: f64, calibrate: f64) -> f64 {
+        match self {
+            Kernel::Calibrate => 1.0 / (r * (r * r + calibrate)),
+            Kernel::Plummer => {
+                let s = r * r + calibrate;
+                if s > 0.0 {
+                    1.0 / (s * s.sqrt())
+                } else {
+                    0.0
+                }
+            }
+            Kernel::Spline => {
+                let h = 14.0 / 5.0 * calibrate.sqrt();
+                if r >= h {
+                    return newtonian(r);
+                }
+                let u = r / h;
+                let h3 = h * h * h;
+                if u < 0.5 {
+                    (32.0 / 3.0 + u * u * (32.0 * u - 192.0 / 5.0)) / h3
+                } else {
+                    (64.0 / 3.0 - 48.0 * u + 192.0 / 5.0 * u * u
+                        - 32.0 / 3.0 * u * u * u
+                        - 1.0 / (15.0 * u * u * u))
+                        / h3
+                }
+            }
+            Kernel::Wendland => {
+                let h = 3.0 * calibrate.sqrt();
+                if r >= h {
+                    return newtonian(r);
+                }
+                let u = r / h;
+                (14.0 + u * u * (-84.0 + u * (140.0 + u * (-90.0 + 21.0 * u)))) / (h * h * h)
+            }
+            Kernel::None => newtonian(r),
+        }
+    }
+
+    // dg/dr, for the jerk of the hermite integrator
+    pub fn force_derivative(self, r: f64, calibrate: f64) -> f64 {
+        let newtonian = |r: f64| if r > 0.0 { -3.0 / (r * r * r * r) } else { 0.0 };
+        match self {
+            Kernel::Calibrate => {
+                let s = r * r + calibrate;
+                -(3.0 * r * r + calibrate) / (r * r * s * s)
+            }
+            Kernel::Plummer => {
+                let s = r * r + calibrate;
+                if s > 0.0 {
+                    -3.0 * r / (s * s * s.sqrt())
+                } else {
+                    0.0
+                }
+            }
+            Kernel::Spline => {
+                let h = 14.0 / 5.0 * calibrate.sqrt();
+                if r >= h {
+                    return newtonian(r);
+                }
+                let u = r / h;
+                let h4 = h * h * h * h;
+                if u < 0.5 {
+                    u * (96.0 * u - 384.0 / 5.0) / h4
+                } else {
+                    (-48.0 + 384.0 / 5.0 * u - 32.0 * u * u + 1.0 / (5.0 * u * u * u * u)) / h4
+                }
+            }
+            Kernel::Wendland => {
+                let h = 3.0 * calibrate.sqrt();
+                if r >= h {
+                    return newtonian(r);
+                }
+                let u = r / h;
+                u * (-168.0 + u * (420.0 + u * (-360.0 + 105.0 * u))) / (h * h * h * h)
+            }
+            Kernel::None => newtonian(r),
+        }
+    }
+
+    // the potential of a unit mass without -G, 1 / r far away
+    pub fn potential(self, r: f64, calibrate: f64) -> f64 {
+        match self {
+            // (π/2 - atan(r / s)) / s written without the cancellation at large r
+            Kernel::Calibrate if calibrate > 0.0 => {
+                let s = calibrate.sqrt();
+                (s / r).atan() / s
+            }
+            Kernel::Plummer => 1.0 / (r * r + calibrate).sqrt(),
+            Kernel::Spline if r < 14.0 / 5.0 * calibrate.sqrt() => {
+                let h = 14.0 / 5.0 * calibrate.sqrt();
+                let u = r / h;
+                let w = if u < 0.5 {
+                    -14.0 / 5.0 + u * u * (16.0 / 3.0 + u * u * (32.0 / 5.0 * u - 48.0 / 5.0))
+                } else {
+                    -16.0 / 5.0
+                        + 1.0 / (15.0 * u)
+                        + u * u * (32.0 / 3.0 + u * (-16.0 + u * (48.0 / 5.0 - 32.0 / 15.0 * u)))
+                };
+                -w / h
+            }
+            Kernel::Wendland if r < 3.0 * calibrate.sqrt() => {
+                let h = 3.0 * calibrate.sqrt();
+                let u = r / h;
+                let u2 = u * u;
+                (3.0 + u2 * (-7.0 + u2 * (21.0 + u * (-28.0 + u * (15.0 - 3.0 * u))))) / h
+            }
+            _ => 1.0 / r,
+        }
+    }
+
+    // the acceleration towards `mass` at offset `diff` without G, the loop body of
+    // `gravity` in compute.wgsl
+    pub fn acceleration(self, diff: Vector3<f64>, mass: f64, calibrate: f64) -> Vector3<f64> {
+        match self {
+            Kernel::Calibrate => diff.normalize() * mass / (diff.magnitude2() + calibrate),
+            kernel => diff * (mass * kernel.force(diff.magnitude(), calibrate)),
+        }
+    }
+}
+
+// 1 / r³, nothing for a particle on top of another
+fn newtonian(r: f64) -> f64 {
+    if r > 0.0 {
+        1.0 / (r * r * r)
+    } else {
+        0.0
+    }
+}
+
+impl FromStr for Kernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "calibrate" => Ok(Kernel::Calibrate),
+            "plummer" => Ok(Kernel::Plummer),
+            "spline" => Ok(Kernel::Spline),
+            "wendland" => Ok(Kernel::Wendland),
+            "none" => Ok(Kernel::None),
+            _ => Err(format!(
+                "unknown kernel `{}`, expected one of calibrate, plummer, spline, wendland, none",
+                s
+            )),
+        }
+    }
+}
+
+impl Softening {
+    // `calibrate` as ε² of every species
+    pub fn new(kernel: Kernel, calibrate: f64) -> Self {
+        Self {
+            kernel,
+            calibrate: [calibrate; 6],
+        }
+    }
+
+    // the scenario's ε, or its calibrate, overridden per species
+    pub fn for_scenario(scenario: &Scenario) -> Self {
+        let calibrate = scenario.epsilon.map_or(scenario.calibrate, |e| e * e);
+        scenario.species_epsilon.iter().fold(
+            Self::new(scenario.kernel, calibrate),
+            |softening, (&species, &epsilon)| softening.with_epsilon(species, epsilon),
+        )
+    }
+
+    // ε in m for the particles of `species`
+    pub fn with_epsilon(mut self, species: Species, epsilon: f64) -> Self {
+        self.calibrate[species as usize] = epsilon * epsilon;
+        self
+    }
+
+    pub fn calibrate(&self, species: Species) -> f64 {
+        self.calibrate[species as usize]
+    }
+
+    // ε² between particles of the two species
+    pub fn pair(&self, a: Species, b: Species) -> f64 {
+        self.calibrate(a).max(self.calibrate(b))
+    }
+
+    // gives every particle the ε² of its species
+    pub fn apply(&self, particles: &mut [Particle]) {
+        for p in particles {
+            p.calibrate = self.calibrate(p.species());
+        }
+    }
+}
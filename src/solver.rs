@@ -0,0 +1,58 @@
+use {
+    crate::{external::Potentials, softening::Kernel},
+    serde::{Deserialize, Serialize},
+    std::str::FromStr,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Solver {
+    // every particle against every massive particle, the loop in compute.wgsl
+    Direct,
+    // the same sum with the massive particles staged through workgroup memory,
+    // only differs from `Direct` on the gpu
+    Tiled,
+    // cpu only, an octree over the massive particles rebuilt for every force evaluation
+    BarnesHut,
+}
+
+// how the forces are summed, shared by every force evaluation of a simulation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Forces {
+    pub solver: Solver,
+    // a node is used as a whole when its size / distance is below theta
+    pub theta: f64,
+    // add the quadrupole moment to the monopole of nodes used as a whole
+    pub quadrupole: bool,
+    pub kernel: Kernel,
+    // added to the pull of the particles
+    pub external: Potentials,
+}
+
+impl Solver {
+    pub fn runs_on_gpu(self) -> bool {
+        matches!(self, Solver::Direct | Solver::Tiled)
+    }
+}
+
+impl Forces {
+    // a tree opening every node at theta 0 is the direct sum in another order
+    pub fn uses_tree(&self) -> bool {
+        self.solver == Solver::BarnesHut && self.theta > 0.0
+    }
+}
+
+impl FromStr for Solver {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "direct" => Ok(Solver::Direct),
+            "tiled" => Ok(Solver::Tiled),
+            "barneshut" | "barnes-hut" => Ok(Solver::BarnesHut),
+            _ => Err(format!(
+                "unknown solver `{}`, expected one of direct, tiled, barnes-hut",
+                s
+            )),
+        }
+    }
+}
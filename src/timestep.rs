@@ -0,0 +1,42 @@
+use {
+    serde::{Deserialize, Serialize},
+    std::str::FromStr,
+};
+
+// the finest level, `cpu::advance_block` counts the time within a step in u32 ticks
+// of max_dt / 2^MAX_LEVEL, so `1u32 << level` has to stay below 32 bits. 20 already
+// splits a step a million times
+pub const MAX_LEVEL: u8 = 20;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timestep {
+    // every particle advances by `motion` each substep
+    Fixed,
+    // the following run on the cpu only and pick steps from η √(ε / |a|)
+    // one shared step per substep, the smallest any particle asks for, at most `motion`
+    Adaptive,
+    // every substep spans `motion`, split into power of two steps per particle
+    Block,
+}
+
+impl Timestep {
+    pub fn runs_on_gpu(self) -> bool {
+        self == Timestep::Fixed
+    }
+}
+
+impl FromStr for Timestep {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fixed" => Ok(Timestep::Fixed),
+            "adaptive" => Ok(Timestep::Adaptive),
+            "block" => Ok(Timestep::Block),
+            _ => Err(format!(
+                "unknown timestep `{}`, expected one of fixed, adaptive, block",
+                s
+            )),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use {
+    cgmath::prelude::*,
+    nbodysim::{
+        accretion, gen, init_galaxy, scenario::Backend, Galaxy, Particle, Scenario, Simulation,
+        Species,
+    },
+};
+
+#[test]
+fn captured_particles_give_their_mass_and_momentum() {
+    let hole = |id, pos| {
+        Particle::new(pos, [1e4, 0.0, 0.0], 1e35, 1e20)
+            .with_species(Species::BlackHole)
+            .with_galaxy(id - 1)
+            .with_id(id)
+    };
+    let star = |id, pos, mass| {
+        Particle::new(pos, [0.0, 3e5, 0.0], mass, 1e20)
+            .with_species(Species::Disk)
+            .with_id(id)
+    };
+    let mut particles = vec![
+        hole(1, [0.0; 3]),
+        // the other central mass is inside the radius but is left to the collisions
+        hole(2, [5e8, 0.0, 0.0]),
+        star(3, [-2e8, 0.0, 0.0], 1e33),
+        star(4, [0.0, 3e8, 0.0], 0.0),
+        // closer to the second one
+        star(5, [4e8, 0.0, 0.0], 1e33),
+        star(6, [5e9, 0.0, 0.0], 1e33),
+    ];
+    let before = common::momentum(&particles);
+    let mut accretors = accretion::accretors(&particles, Some(1e34));
+    assert_eq!(accretors.len(), 2);
+
+    let captured = accretion::accrete(&mut particles, 1e9, Some(1e34), &mut accretors, 60.0);
+    assert_eq!(captured, 3);
+    assert_eq!(particles[0].mass(), 1e35 + 1e33);
+    assert_eq!(particles[1].mass(), 1e35 + 1e33);
+    assert!(particles[2..5].iter().all(|p| !p.is_active()));
+    assert!(particles[5].is_active());
+    assert!((common::momentum(&particles) - before).magnitude() < 1e-6 * before.magnitude());
+
+    assert_eq!(
+        (accretors[0].id, accretors[0].particles, accretors[0].mass),
+        (1, 2, 1e33)
+    );
+    assert_eq!(accretors[0].last, Some(60.0));
+    assert_eq!(accretors[1].particles, 1);
+    assert_eq!(accretors[1].rate(60.0), 1e33 / 60.0);
+}
+
+#[test]
+fn black_holes_only_swallow_test_particles_by_default() {
+    let mut particles = vec![
+        Particle::new([0.0; 3], [0.0; 3], 1e35, 1e20)
+            .with_species(Species::BlackHole)
+            .with_id(1),
+        // a heavy star is no central mass without a threshold
+        Particle::new([-3e8, 0.0, 0.0], [0.0; 3], 1e36, 1e20)
+            .with_species(Species::Star)
+            .with_id(2),
+        Particle::new([2e8, 0.0, 0.0], [0.0; 3], 1e30, 1e20).with_id(3),
+        Particle::new([0.0, 2e8, 0.0], [0.0; 3], 0.0, 1e20).with_id(4),
+    ];
+    let mut accretors = accretion::accretors(&particles, None);
+    assert_eq!(accretors.len(), 1);
+    assert_eq!(
+        accretion::accrete(&mut particles, 1e9, None, &mut accretors, 1.0),
+        1
+    );
+    assert!(particles[..3].iter().all(Particle::is_active));
+    assert!(!particles[3].is_active());
+    assert_eq!(particles[0].mass(), 1e35);
+
+    // with one, everything at least that heavy swallows the lighter particles
+    let mut accretors = accretion::accretors(&particles, Some(1e34));
+    assert_eq!(accretors.iter().map(|a| a.id).collect::<Vec<_>>(), [1, 2]);
+    accretion::accrete(&mut particles, 1e9, Some(1e34), &mut accretors, 2.0);
+    assert!(!particles[2].is_active());
+    assert_eq!(particles[0].mass(), 1e35 + 1e30);
+    assert!(particles[1].is_active());
+}
+
+#[test]
+fn infalling_stars_are_swallowed() {
+    let scenario = Scenario {
+        backend: Backend::Gpu,
+        accretion_radius: Some(2e9),
+        accretion_mass: Some(1e34),
+        galaxies: vec![
+            Galaxy::Init {
+                center_pos: [0.0; 3],
+                center_vel: [0.0; 3],
+                center_mass: 1e35,
+                amount: 0,
+                normal: [0.0, 0.0, 1.0],
+            },
+            Galaxy::Particle {
+                pos: [3e10, 0.0, 0.0],
+                vel: [0.0; 3],
+                mass: 0.0,
+            },
+            Galaxy::Particle {
+                pos: [0.0, -3e10, 0.0],
+                vel: [0.0; 3],
+                mass: 1e32,
+            },
+        ],
+        ..Scenario::default()
+    };
+    let particles = init_galaxy(scenario.calibrate, &scenario.galaxies, &mut gen::seeded(1));
+    let mut sim = pollster::block_on(Simulation::new(&scenario, particles));
+    assert_eq!(sim.backend(), Backend::Cpu);
+    assert_eq!(sim.warnings(), ["accretion only runs on the cpu backend"]);
+    sim.step(1000);
+
+    let accretors = sim.accretors().to_vec();
+    assert_eq!(accretors.len(), 1);
+    assert_eq!(accretors[0].particles, 2, "{:?}", accretors);
+    assert_eq!(accretors[0].mass, 1e32);
+    let particles = sim.particles();
+    assert_eq!(particles[0].mass(), 1e35 + 1e32);
+    assert!(particles[1..].iter().all(|p| !p.is_active()));
+}
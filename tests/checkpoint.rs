@@ -0,0 +1,158 @@
+mod common;
+
+use {
+    nbodysim::{
+        gen, init_galaxy,
+        integrator::Integrator,
+        scenario::{Backend, FileError},
+        snapshot::Checkpoint,
+        timestep::Timestep,
+        Galaxy, Particle, Scenario, Simulation,
+    },
+    std::fs,
+};
+
+fn scenario(integrator: Integrator, timestep: Timestep) -> Scenario {
+    Scenario {
+        backend: Backend::Cpu,
+        integrator,
+        timestep,
+        seed: Some(3),
+        galaxies: vec![Galaxy::Init {
+            center_pos: [0.0, 0.0, 0.0],
+            center_vel: [0.0, 0.0, 0.0],
+            center_mass: 1e35,
+            amount: 100,
+            normal: [0.0, 0.0, 1.0],
+        }],
+        ..Scenario::default()
+    }
+}
+
+fn particles(scenario: &Scenario) -> Vec<Particle> {
+    init_galaxy(
+        scenario.calibrate,
+        &scenario.galaxies,
+        &mut gen::seeded(scenario.seed.unwrap()),
+    )
+}
+
+fn bits(particles: &[Particle]) -> Vec<[u64; 8]> {
+    particles
+        .iter()
+        .map(|p| {
+            let (pos, vel) = (p.pos(), p.vel());
+            [
+                pos[0].to_bits() as u64,
+                pos[1].to_bits() as u64,
+                pos[2].to_bits() as u64,
+                vel[0].to_bits() as u64,
+                vel[1].to_bits() as u64,
+                vel[2].to_bits() as u64,
+                p.mass().to_bits(),
+                p.calibrate().to_bits(),
+            ]
+        })
+        .collect()
+}
+
+#[test]
+fn restart_is_bit_identical() {
+    let settings = [
+        (Integrator::Leapfrog, Timestep::Fixed),
+        (Integrator::Hermite, Timestep::Fixed),
+        (Integrator::Yoshida, Timestep::Adaptive),
+        (Integrator::Leapfrog, Timestep::Block),
+    ];
+    for (integrator, timestep) in settings {
+        let what = format!("{:?} {:?}", integrator, timestep);
+        let scenario = scenario(integrator, timestep);
+        let mut straight = pollster::block_on(Simulation::new(&scenario, particles(&scenario)));
+        straight.step(15);
+        straight.step(15);
+
+        let mut first = pollster::block_on(Simulation::new(&scenario, particles(&scenario)));
+        first.step(15);
+        let path = common::temporary(&format!("{:?}_{:?}.nbody", integrator, timestep));
+        first.checkpoint().write(&path).unwrap();
+        let checkpoint = Checkpoint::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut restarted = pollster::block_on(Simulation::new(
+            &checkpoint.scenario,
+            checkpoint.particles.clone(),
+        ));
+        restarted.resume(&checkpoint);
+        assert_eq!(restarted.integrator(), integrator, "{}", what);
+        assert_eq!(restarted.timestep(), timestep, "{}", what);
+        restarted.step(15);
+
+        assert_eq!(restarted.substeps(), straight.substeps(), "{}", what);
+        assert_eq!(
+            restarted.time().to_bits(),
+            straight.time().to_bits(),
+            "{}",
+            what
+        );
+        assert_eq!(restarted.levels(), straight.levels(), "{}", what);
+        assert!(
+            bits(restarted.particles()) == bits(straight.particles()),
+            "{}: the restarted run diverged",
+            what
+        );
+    }
+}
+
+#[test]
+fn truncated_checkpoints_are_rejected() {
+    let scenario = scenario(Integrator::Leapfrog, Timestep::Fixed);
+    let mut sim = pollster::block_on(Simulation::new(&scenario, particles(&scenario)));
+    let path = common::temporary("truncated.nbody");
+    sim.checkpoint().write(&path).unwrap();
+    let bytes = fs::read(&path).unwrap();
+    fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+    let result = Checkpoint::read(&path);
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(FileError::Invalid(..))));
+}
+
+#[test]
+fn accretors_survive_a_restart() {
+    let scenario = Scenario {
+        backend: Backend::Cpu,
+        accretion_radius: Some(2e9),
+        galaxies: vec![
+            Galaxy::Init {
+                center_pos: [0.0; 3],
+                center_vel: [0.0; 3],
+                center_mass: 1e35,
+                amount: 0,
+                normal: [0.0, 0.0, 1.0],
+            },
+            Galaxy::Particle {
+                pos: [3e10, 0.0, 0.0],
+                vel: [0.0; 3],
+                mass: 0.0,
+            },
+        ],
+        ..Scenario::default()
+    };
+    let particles = init_galaxy(scenario.calibrate, &scenario.galaxies, &mut gen::seeded(1));
+    let mut sim = pollster::block_on(Simulation::new(&scenario, particles));
+    sim.step(1000);
+    let accretors = sim.accretors().to_vec();
+    assert_eq!(accretors[0].particles, 1, "{:?}", accretors);
+
+    let path = common::temporary("accretors.nbody");
+    sim.checkpoint().write(&path).unwrap();
+    let checkpoint = Checkpoint::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(checkpoint.accretors, accretors);
+
+    let mut restarted = pollster::block_on(Simulation::new(
+        &checkpoint.scenario,
+        checkpoint.particles.clone(),
+    ));
+    restarted.resume(&checkpoint);
+    assert_eq!(restarted.accretors(), accretors);
+}
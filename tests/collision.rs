@@ -0,0 +1,170 @@
+mod common;
+
+use {
+    cgmath::{prelude::*, Vector3},
+    nbodysim::{
+        collision::{self, Collision, Radii},
+        init_galaxy,
+        scenario::Backend,
+        softening::{Kernel, Softening},
+        Galaxy, Particle, Scenario, Simulation, Species,
+    },
+    rand::{rngs::StdRng, SeedableRng},
+};
+
+fn radii() -> Radii {
+    Radii {
+        radius: Some(1e9),
+        softening: Softening::new(Kernel::Calibrate, 0.0),
+        density: None,
+    }
+}
+
+fn body(id: u32, pos: [f32; 3], vel: [f32; 3], mass: f64) -> Particle {
+    Particle::new(pos, vel, mass, 1e14).with_id(id)
+}
+
+fn kinetic(particles: &[Particle]) -> f64 {
+    particles
+        .iter()
+        .filter(|p| p.mass() > 0.0)
+        .map(|p| 0.5 * p.mass() * Vector3::from(p.vel()).map(|x| x as f64).magnitude2())
+        .sum()
+}
+
+#[test]
+fn merges_conserve_mass_and_momentum() {
+    let mut particles = vec![
+        body(1, [0.0; 3], [1e5, 0.0, 0.0], 1e30),
+        body(2, [1.5e9, 0.0, 0.0], [-1e5, 2e4, 0.0], 3e30),
+        // a star and a distant body are left alone
+        body(3, [5e8, 0.0, 0.0], [0.0; 3], 0.0),
+        body(4, [1e12, 0.0, 0.0], [0.0; 3], 1e30),
+    ];
+    let before = common::momentum(&particles);
+    let events = collision::collide(&mut particles, Collision::Merge, radii(), 7, 42.0);
+
+    assert_eq!(events.len(), 1);
+    let event = events[0];
+    assert_eq!((event.substeps, event.time), (7, 42.0));
+    // the heavier one survives
+    assert_eq!(event.ids, [2, 1]);
+    assert_eq!(event.masses, [3e30, 1e30]);
+    assert!((event.pos[0] - 1.125e9).abs() < 1e3, "{:?}", event.pos);
+
+    assert!(!particles[0].is_active());
+    assert_eq!(particles[0].mass(), -1e30);
+    assert_eq!(particles[1].mass(), 4e30);
+    assert_eq!(particles[1].pos(), [1.125e9, 0.0, 0.0]);
+    assert!((common::momentum(&particles) - before).magnitude() < 1e-6 * before.magnitude());
+    assert_eq!(particles[2].mass(), 0.0);
+    assert_eq!(particles[3].mass(), 1e30);
+}
+
+#[test]
+fn bounces_conserve_momentum_and_energy() {
+    let mut particles = vec![
+        body(1, [0.0; 3], [1e5, 1e4, 0.0], 1e30),
+        body(2, [1e9, 1e9, 0.0], [-3e4, 0.0, 5e3], 2e30),
+    ];
+    let (p, k) = (common::momentum(&particles), kinetic(&particles));
+    let events = collision::collide(&mut particles, Collision::Bounce, radii(), 0, 0.0);
+    assert_eq!(events.len(), 1);
+    assert!(particles.iter().all(|p| p.mass() > 0.0));
+    assert!((common::momentum(&particles) - p).magnitude() < 1e-6 * p.magnitude());
+    assert!((kinetic(&particles) - k).abs() < 1e-6 * k);
+
+    // now parting, they do not bounce back while still touching
+    let after = particles.clone();
+    assert!(collision::collide(&mut particles, Collision::Bounce, radii(), 1, 0.0).is_empty());
+    assert_eq!(
+        particles.iter().map(Particle::vel).collect::<Vec<_>>(),
+        after.iter().map(Particle::vel).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn outcome_does_not_depend_on_the_order() {
+    // three bodies touching in a row, the pair with the smallest ids merges first
+    let particles = vec![
+        body(1, [0.0; 3], [0.0; 3], 1e30),
+        body(2, [1.5e9, 0.0, 0.0], [0.0; 3], 1e30),
+        body(3, [3e9, 0.0, 0.0], [0.0; 3], 1e30),
+    ];
+    let mut forward = particles.clone();
+    let mut backward: Vec<Particle> = particles.iter().rev().copied().collect();
+    let a = collision::collide(&mut forward, Collision::Merge, radii(), 0, 0.0);
+    let b = collision::collide(&mut backward, Collision::Merge, radii(), 0, 0.0);
+    assert_eq!(a, b);
+    assert_eq!(a[0].ids, [1, 2]);
+    backward.reverse();
+    for (a, b) in forward.iter().zip(&backward) {
+        assert_eq!((a.id(), a.mass(), a.pos()), (b.id(), b.mass(), b.pos()));
+    }
+}
+
+#[test]
+fn particles_touch_at_the_softening_length_of_their_species() {
+    let scenario = Scenario {
+        collision: Collision::Merge,
+        epsilon: Some(1e9),
+        species_epsilon: [(Species::BlackHole, 4e9)].into(),
+        ..Scenario::default()
+    };
+    let radii = Radii::for_scenario(&scenario);
+    let star = body(1, [0.0; 3], [0.0; 3], 1e30).with_species(Species::Star);
+    let hole = body(2, [0.0; 3], [0.0; 3], 1e35).with_species(Species::BlackHole);
+    assert_eq!((radii.of(&star), radii.of(&hole)), (1e9, 4e9));
+
+    // stars 3e9 m apart miss each other, a star as far from a black hole does not
+    let mut particles = vec![star, body(3, [3e9, 0.0, 0.0], [0.0; 3], 1e30)];
+    assert!(collision::collide(&mut particles, Collision::Merge, radii, 0, 0.0).is_empty());
+    let mut particles = vec![hole, body(3, [3e9, 0.0, 0.0], [0.0; 3], 1e30)];
+    assert_eq!(
+        collision::collide(&mut particles, Collision::Merge, radii, 0, 0.0).len(),
+        1
+    );
+}
+
+#[test]
+fn colliding_galaxies_merge_their_centers() {
+    let scenario = Scenario {
+        backend: Backend::Gpu,
+        collision: Collision::Merge,
+        galaxies: vec![
+            Galaxy::Init {
+                center_pos: [-2e10, 0.0, 0.0],
+                center_vel: [2e6, 0.0, 0.0],
+                center_mass: 1e35,
+                amount: 50,
+                normal: [0.0, 0.0, 1.0],
+            },
+            Galaxy::Init {
+                center_pos: [2e10, 0.0, 0.0],
+                center_vel: [-2e6, 0.0, 0.0],
+                center_mass: 2e35,
+                amount: 50,
+                normal: [0.0, 1.0, 0.0],
+            },
+        ],
+        ..Scenario::default()
+    };
+    let particles = init_galaxy(
+        scenario.calibrate,
+        &scenario.galaxies,
+        &mut StdRng::seed_from_u64(2),
+    );
+    let mut sim = pollster::block_on(Simulation::new(&scenario, particles));
+    // collisions fall back to the cpu backend
+    assert_eq!(sim.backend(), Backend::Cpu);
+    sim.step(600);
+
+    let events = sim.collisions();
+    assert_eq!(events.len(), 1, "{:?}", events);
+    assert_eq!(events[0].collision, Collision::Merge);
+    assert_eq!(events[0].ids, [2, 1]);
+    assert!(sim.collisions().is_empty());
+    let particles = sim.particles();
+    assert_eq!(particles[0].mass(), -1e35);
+    assert_eq!(particles[1].mass(), 3e35);
+}
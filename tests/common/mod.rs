@@ -0,0 +1,38 @@
+// helpers shared by the integration tests, each test crate uses only some of them
+#![allow(dead_code)]
+
+use {
+    cgmath::Vector3,
+    nbodysim::{Particle, Scenario},
+    std::{env, fs, path::PathBuf},
+};
+
+// a path in the temporary directory no other test process writes to
+pub fn temporary(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("nbodysim_{}_{}", std::process::id(), name))
+}
+
+// the momentum of the active massive particles
+pub fn momentum(particles: &[Particle]) -> Vector3<f64> {
+    particles
+        .iter()
+        .filter(|p| p.mass() > 0.0)
+        .map(|p| Vector3::from(p.vel()).map(|x| x as f64) * p.mass())
+        .sum()
+}
+
+// every scenario under scenarios/ by its file name, loaded and validated as the
+// command line does
+pub fn example_scenarios() -> Vec<(String, Scenario)> {
+    let mut scenarios: Vec<(String, Scenario)> = fs::read_dir("scenarios")
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let scenario = Scenario::load(&path).unwrap_or_else(|e| panic!("{}", e));
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, scenario)
+        })
+        .collect();
+    scenarios.sort_by(|a, b| a.0.cmp(&b.0));
+    scenarios
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use {
+    cgmath::{prelude::*, Vector3},
+    nbodysim::{gen, init_galaxy, scenario::FileError, Galaxy, Scenario},
+    std::fs,
+};
+
+const CALIBRATE: f64 = 1e20;
+
+#[test]
+fn disks_are_exponential() {
+    let scale_length = 1e10;
+    let galaxies = [Galaxy::Disk {
+        center_pos: [0.0, 0.0, 0.0],
+        center_vel: [0.0, 0.0, 0.0],
+        center_mass: 1e35,
+        amount: 20000,
+        normal: [0.0, 0.0, 1.0],
+        profile: gen::Disk {
+            scale_length,
+            cutoff: 50.0,
+            arms: 0,
+            bulge_fraction: 0.0,
+            dispersion: 0.0,
+            ..gen::Disk::default()
+        },
+    }];
+    let particles = init_galaxy(CALIBRATE, &galaxies, &mut gen::seeded(1));
+    assert_eq!(particles.len(), 20001);
+
+    let mut sum = 0.0;
+    for p in &particles[1..] {
+        let pos = Vector3::from(p.pos());
+        let vel = Vector3::from(p.vel());
+        assert_eq!(p.mass(), 0.0);
+        assert!(pos.z.abs() < 1.0, "a flat disk has no height");
+        let r = pos.magnitude() as f64;
+        let speed = (6.67408e-11 * 1e35 * r / (r * r + CALIBRATE)).sqrt();
+        assert!((vel.magnitude() as f64 - speed).abs() < 1e-4 * speed);
+        assert!(pos.dot(vel).abs() < 1e-3 * pos.magnitude() * vel.magnitude());
+        sum += r;
+    }
+    // the mean radius of r e^(-r/h) is 2h
+    let mean = sum / 20000.0 / scale_length as f64;
+    assert!(
+        (mean - 2.0).abs() < 0.05,
+        "mean radius {} scale lengths",
+        mean
+    );
+}
+
+#[test]
+fn stars_are_split_between_bulge_arms_and_disk() {
+    let profile = gen::Disk {
+        cutoff: 3.0,
+        bulge_fraction: 0.25,
+        bulge: gen::Bulge {
+            radius: 1e9,
+            cutoff: 2.0,
+            ..gen::Bulge::default()
+        },
+        ..gen::Disk::default()
+    };
+    let galaxies = [Galaxy::Disk {
+        center_pos: [1e11, 0.0, 0.0],
+        center_vel: [0.0, 0.0, 0.0],
+        center_mass: 1e35,
+        amount: 1000,
+        normal: [1.0, 1.0, 0.0],
+        profile,
+    }];
+    let particles = init_galaxy(CALIBRATE, &galaxies, &mut gen::seeded(2));
+    assert_eq!(particles.len(), 1001);
+    let distances: Vec<f32> = particles[1..]
+        .iter()
+        .map(|p| (Vector3::from(p.pos()) - Vector3::new(1e11, 0.0, 0.0)).magnitude())
+        .collect();
+    let bulge = distances.iter().filter(|&&r| r <= 2e9 * 1.0001).count();
+    // the disk may put a few stars that close as well
+    assert!((250..270).contains(&bulge), "{} bulge stars", bulge);
+    assert!(distances
+        .iter()
+        .all(|&r| r <= 3.0 * profile.scale_length * 1.0001));
+}
+
+#[test]
+fn profiles_that_place_no_star_are_rejected() {
+    let disk = |profile| Galaxy::Disk {
+        center_pos: [0.0; 3],
+        center_vel: [0.0; 3],
+        center_mass: 1e35,
+        amount: 10,
+        normal: [0.0, 0.0, 1.0],
+        profile,
+    };
+    let bulge = |profile| Galaxy::Bulge {
+        center_pos: [0.0; 3],
+        center_vel: [0.0; 3],
+        center_mass: 1e35,
+        amount: 10,
+        profile,
+    };
+    let invalid = [
+        disk(gen::Disk {
+            cutoff: 0.0,
+            ..gen::Disk::default()
+        }),
+        disk(gen::Disk {
+            scale_length: -1e10,
+            ..gen::Disk::default()
+        }),
+        disk(gen::Disk {
+            bulge: gen::Bulge {
+                radius: f32::NAN,
+                ..gen::Bulge::default()
+            },
+            ..gen::Disk::default()
+        }),
+        bulge(gen::Bulge {
+            cutoff: -1.0,
+            ..gen::Bulge::default()
+        }),
+    ];
+    for galaxy in invalid {
+        let scenario = Scenario {
+            galaxies: vec![disk(gen::Disk::default()), galaxy],
+            ..Scenario::default()
+        };
+        let message = scenario.validate().unwrap_err();
+        assert!(message.starts_with("galaxy 2: "), "{}", message);
+    }
+
+    let path = common::temporary("cutoff.ron");
+    fs::write(&path, "(galaxies: [Bulge(center_pos: (0, 0, 0), center_vel: (0, 0, 0), center_mass: 1e35, amount: 10, profile: (cutoff: 0.0))])").unwrap();
+    let loaded = Scenario::load(&path);
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(loaded, Err(FileError::Invalid(..))));
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use {
+    nbodysim::{
+        export::{self, Series},
+        Particle, Species,
+    },
+    std::fs,
+};
+
+fn particles() -> Vec<Particle> {
+    vec![
+        Particle::new([1.5e10, -2.0, 3.25e-3], [1e3, 2e3, -3e3], 2e30, 1e10)
+            .with_species(Species::BlackHole)
+            .with_galaxy(1)
+            .with_id(1),
+        Particle::new([-4e11, 5e11, 0.0], [0.0, -7.5e4, 1.0], 0.0, 1e10)
+            .with_species(Species::Disk)
+            .with_galaxy(1)
+            .with_id(2),
+        // inactive particles are written as they are
+        Particle::new([7.0, 8.0, 9.0], [0.0; 3], -1.0, 1e10).with_id(3),
+    ]
+}
+
+fn written(name: &str) -> Vec<u8> {
+    let path = common::temporary(name);
+    export::write(&path, &particles()).unwrap();
+    let bytes = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    bytes
+}
+
+// the offset of the first `needle` in `bytes` from `start`
+fn find(bytes: &[u8], start: usize, needle: &str) -> usize {
+    start
+        + bytes[start..]
+            .windows(needle.len())
+            .position(|window| window == needle.as_bytes())
+            .unwrap_or_else(|| panic!("no `{}`", needle))
+}
+
+fn be_f32s(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks(4)
+        .map(|b| f32::from_be_bytes(b.try_into().unwrap()))
+        .collect()
+}
+
+fn le<const N: usize>(bytes: &[u8], offset: usize) -> [u8; N] {
+    bytes[offset..offset + N].try_into().unwrap()
+}
+
+#[test]
+fn csv_has_a_row_per_particle() {
+    let text = String::from_utf8(written("export.csv")).unwrap();
+    let mut lines = text.lines();
+    assert_eq!(lines.next(), Some("id,x,y,z,vx,vy,vz,mass,species,galaxy"));
+    let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+    assert_eq!(rows.len(), particles().len());
+    for (row, p) in rows.iter().zip(particles()) {
+        assert_eq!(row.len(), 10);
+        assert_eq!(row[0].parse::<u32>().unwrap(), p.id());
+        let floats: Vec<f32> = row[1..7].iter().map(|x| x.parse().unwrap()).collect();
+        assert_eq!(floats, [p.pos(), p.vel()].concat());
+        assert_eq!(row[7].parse::<f64>().unwrap(), p.mass());
+        assert_eq!(row[8].parse::<u32>().unwrap(), p.species() as u32);
+        assert_eq!(row[9].parse::<u32>().unwrap(), p.galaxy());
+    }
+}
+
+#[test]
+fn vtk_has_a_vertex_per_point() {
+    let bytes = written("export.vtk");
+    let n = particles().len();
+    assert!(bytes.starts_with(b"# vtk DataFile Version 3.0\n"));
+
+    let points = find(&bytes, 0, "POINTS 3 float\n") + "POINTS 3 float\n".len();
+    let positions = be_f32s(&bytes[points..points + 12 * n]);
+    let expected: Vec<f32> = particles().iter().flat_map(|p| p.pos()).collect();
+    assert_eq!(positions, expected);
+
+    let vertices = points + 12 * n;
+    let header = "\nVERTICES 3 6\n";
+    assert_eq!(find(&bytes, vertices, header), vertices);
+    let cells: Vec<i32> = bytes[vertices + header.len()..][..8 * n]
+        .chunks(4)
+        .map(|b| i32::from_be_bytes(b.try_into().unwrap()))
+        .collect();
+    assert_eq!(cells, [1, 0, 1, 1, 1, 2]);
+    let data = vertices + header.len() + 8 * n;
+    assert_eq!(find(&bytes, data, "\nPOINT_DATA 3\n"), data);
+
+    // the ids are the last array
+    let scalars = "SCALARS id unsigned_int 1\nLOOKUP_TABLE default\n";
+    let last = find(&bytes, data, scalars) + scalars.len();
+    let ids: Vec<u32> = bytes[last..last + 4 * n]
+        .chunks(4)
+        .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+        .collect();
+    assert_eq!(ids, [1, 2, 3]);
+    assert_eq!(&bytes[last + 4 * n..], b"\n");
+}
+
+#[test]
+fn vtu_offsets_point_at_the_appended_arrays() {
+    let bytes = written("export.vtu");
+    let n = particles().len();
+    let marker = "<AppendedData encoding=\"raw\">\n   _";
+    let start = find(&bytes, 0, marker) + marker.len();
+    let xml = String::from_utf8_lossy(&bytes[..start]);
+    assert!(xml.contains("<Piece NumberOfPoints=\"3\" NumberOfCells=\"3\">"));
+
+    // the name, component count and offset of every array in the order of the blocks
+    let arrays: Vec<(String, usize, usize)> = xml
+        .split("<DataArray ")
+        .skip(1)
+        .map(|array| {
+            let attribute = |name: &str| {
+                let at = array.find(&format!("{}=\"", name)).unwrap() + name.len() + 2;
+                let value = &array[at..];
+                value[..value.find('"').unwrap()].to_string()
+            };
+            (
+                attribute("Name"),
+                attribute("NumberOfComponents").parse().unwrap(),
+                attribute("offset").parse().unwrap(),
+            )
+        })
+        .collect();
+    let sizes = [4, 4, 8, 4, 4, 4, 8, 8, 1];
+    assert_eq!(arrays.len(), sizes.len());
+
+    // every block is a byte count followed by that many bytes, right up to the next
+    let mut end = 0;
+    for ((name, components, offset), size) in arrays.iter().zip(sizes) {
+        assert_eq!(*offset, end, "{}", name);
+        let count = u64::from_le_bytes(le(&bytes, start + offset)) as usize;
+        assert_eq!(count, n * components * size, "{}", name);
+        end = offset + 8 + count;
+    }
+    assert_eq!(&bytes[start + end..], b"\n  </AppendedData>\n</VTKFile>\n");
+
+    let block = |k: usize| start + arrays[k].2 + 8;
+    let masses: Vec<f64> = (0..n)
+        .map(|i| f64::from_le_bytes(le(&bytes, block(2) + 8 * i)))
+        .collect();
+    assert_eq!(
+        masses,
+        particles().iter().map(Particle::mass).collect::<Vec<_>>()
+    );
+    let offsets: Vec<i64> = (0..n)
+        .map(|i| i64::from_le_bytes(le(&bytes, block(7) + 8 * i)))
+        .collect();
+    assert_eq!(offsets, [1, 2, 3]);
+}
+
+#[test]
+fn ply_has_a_record_per_vertex() {
+    let bytes = written("export.ply");
+    let n = particles().len();
+    let end = find(&bytes, 0, "end_header\n") + "end_header\n".len();
+    let header = String::from_utf8_lossy(&bytes[..end]);
+    assert!(header.starts_with("ply\nformat binary_little_endian 1.0\n"));
+    assert!(header.contains("element vertex 3\n"));
+    assert_eq!(header.matches("property ").count(), 10);
+
+    // six floats, a double and three uints
+    let record = 6 * 4 + 8 + 3 * 4;
+    assert_eq!(bytes.len(), end + n * record);
+    for (i, p) in particles().iter().enumerate() {
+        let at = end + i * record;
+        let floats: Vec<f32> = (0..6)
+            .map(|k| f32::from_le_bytes(le(&bytes, at + 4 * k)))
+            .collect();
+        assert_eq!(floats, [p.pos(), p.vel()].concat());
+        assert_eq!(f64::from_le_bytes(le(&bytes, at + 24)), p.mass());
+        let tags = [0, 1, 2].map(|k| u32::from_le_bytes(le(&bytes, at + 32 + 4 * k)));
+        assert_eq!(tags, [p.species() as u32, p.galaxy(), p.id()]);
+    }
+}
+
+#[test]
+fn series_lists_every_file() {
+    let path = common::temporary("export.pvd");
+    let mut series = Series::new(path.clone());
+    series.push(0.0, "run/0.vtu".as_ref()).unwrap();
+    series.push(1.5e3, "run/a&b.vtu".as_ref()).unwrap();
+    let text = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(text.matches("<DataSet ").count(), 2);
+    assert!(text.contains("timestep=\"1.5e3\" part=\"0\" file=\"run/a&amp;b.vtu\""));
+}
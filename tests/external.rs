@@ -0,0 +1,196 @@
+mod common;
+
+use {
+    cgmath::{prelude::*, Vector3},
+    nbodysim::{
+        diagnostics,
+        external::{Potential, Potentials, MAX_POTENTIALS},
+        gen, init_galaxy,
+        integrator::Integrator,
+        scenario::{self, Backend, FileError},
+        softening::Kernel,
+        Galaxy, Scenario, Simulation,
+    },
+    std::fs,
+};
+
+fn potentials() -> [Potential; 5] {
+    [
+        Potential::PointMass {
+            pos: [1e10, 0.0, -2e10],
+            mass: 1e35,
+            epsilon: 1e9,
+        },
+        Potential::MiyamotoNagai {
+            pos: [0.0; 3],
+            mass: 1e36,
+            a: 3e10,
+            b: 3e9,
+        },
+        Potential::Logarithmic {
+            pos: [0.0, 5e9, 0.0],
+            speed: 2e6,
+            core: 1e10,
+            flattening: 0.8,
+        },
+        Potential::Nfw {
+            pos: [-1e10, 0.0, 0.0],
+            mass: 5e36,
+            scale: 1e11,
+        },
+        Potential::Tidal {
+            pos: [0.0; 3],
+            tensor: [
+                [2e-12, 1e-13, 0.0],
+                [1e-13, -1e-12, 0.0],
+                [0.0, 0.0, -1e-12],
+            ],
+        },
+    ]
+}
+
+#[test]
+fn accelerations_are_the_slopes_of_the_potentials() {
+    let points = [
+        Vector3::new(3e10, -2e10, 1e9),
+        Vector3::new(-5e10, 1e10, 4e10),
+        Vector3::new(2e9, 1e9, -3e9),
+    ];
+    for potential in potentials() {
+        for pos in points {
+            let h = 1e4;
+            let slope = Vector3::new(
+                potential.potential(pos - Vector3::unit_x() * h)
+                    - potential.potential(pos + Vector3::unit_x() * h),
+                potential.potential(pos - Vector3::unit_y() * h)
+                    - potential.potential(pos + Vector3::unit_y() * h),
+                potential.potential(pos - Vector3::unit_z() * h)
+                    - potential.potential(pos + Vector3::unit_z() * h),
+            ) / (2.0 * h);
+            let acc = potential.acceleration(pos);
+            assert!(
+                (slope - acc).magnitude() < 1e-5 * acc.magnitude(),
+                "{:?} at {:?}: {:?} instead of {:?}",
+                potential,
+                pos,
+                acc,
+                slope
+            );
+        }
+    }
+}
+
+#[test]
+fn too_many_potentials_are_rejected() {
+    let many = [potentials(), potentials()].concat();
+    let scenario = Scenario {
+        external: many[..MAX_POTENTIALS + 1].to_vec(),
+        ..Scenario::default()
+    };
+    assert_eq!(
+        scenario.validate(),
+        Err("9 external potentials, at most 8 are supported".to_string())
+    );
+    let path = common::temporary("potentials.ron");
+    scenario::write(&path, &scenario).unwrap();
+    let loaded = Scenario::load(&path);
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(loaded, Err(FileError::Invalid(..))));
+
+    let kept = Potentials::new(&many[..MAX_POTENTIALS]);
+    assert_eq!(kept.iter().count(), 8);
+    assert!(Potentials::NONE.is_empty());
+    assert_eq!(
+        Potentials::NONE.acceleration(Vector3::unit_x()),
+        Vector3::zero()
+    );
+    assert_eq!(kept.to_gpu().len(), 8);
+    assert_eq!(Potentials::NONE.to_gpu().len(), 1);
+}
+
+#[test]
+fn stars_orbit_a_logarithmic_halo_at_its_speed() {
+    let speed = 2e6;
+    let radius = 5e10;
+    for integrator in [
+        Integrator::Leapfrog,
+        Integrator::Yoshida,
+        Integrator::Hermite,
+    ] {
+        let scenario = Scenario {
+            backend: Backend::Cpu,
+            integrator,
+            motion: 100.0,
+            external: vec![Potential::Logarithmic {
+                pos: [0.0; 3],
+                speed,
+                core: 0.0,
+                flattening: 1.0,
+            }],
+            galaxies: vec![Galaxy::Particle {
+                pos: [radius as f32, 0.0, 0.0],
+                vel: [0.0, speed as f32, 0.0],
+                mass: 0.0,
+            }],
+            ..Scenario::default()
+        };
+        let particles = init_galaxy(scenario.calibrate, &scenario.galaxies, &mut gen::seeded(1));
+        let mut sim = pollster::block_on(Simulation::new(&scenario, particles));
+        // about a third of an orbit
+        sim.step(500);
+        let p = sim.particles()[0];
+        let r = Vector3::from(p.pos()).map(|x| x as f64).magnitude();
+        let v = Vector3::from(p.vel()).map(|x| x as f64).magnitude();
+        assert!(
+            (r - radius).abs() < 1e-4 * radius,
+            "{:?}: r {}",
+            integrator,
+            r
+        );
+        assert!(
+            (v - speed).abs() < 1e-4 * speed,
+            "{:?}: v {}",
+            integrator,
+            v
+        );
+    }
+}
+
+#[test]
+fn energy_in_the_potentials_is_conserved() {
+    let scenario = Scenario {
+        backend: Backend::Cpu,
+        integrator: Integrator::Leapfrog,
+        motion: 5.0,
+        external: potentials()[1..].to_vec(),
+        // a quarter of a slightly inclined orbit
+        galaxies: vec![Galaxy::Plummer {
+            center_pos: [4e10, 0.0, 0.0],
+            center_vel: [0.0, 3.4e7, 1e6],
+            mass: 1e31,
+            amount: 50,
+            radius: 2e9,
+        }],
+        ..Scenario::default()
+    };
+    let particles = init_galaxy(scenario.calibrate, &scenario.galaxies, &mut gen::seeded(4));
+    let external = Potentials::new(&scenario.external);
+    let before = diagnostics::measure_with(&particles, Kernel::Calibrate, &external);
+    let expected: f64 = particles
+        .iter()
+        .map(|p| p.mass() * external.potential(Vector3::from(p.pos()).map(|x| x as f64)))
+        .sum();
+    assert!((before.energy.external - expected).abs() < 1e-9 * expected.abs());
+    assert_eq!(
+        before.energy.potential,
+        diagnostics::measure(&particles).energy.potential
+    );
+
+    let mut sim = pollster::block_on(Simulation::new(&scenario, particles));
+    assert_eq!(sim.diagnostics(), before);
+    sim.step(400);
+    let after = sim.diagnostics();
+    assert_ne!(after.energy.external, before.energy.external);
+    let drift = (after.energy.total() - before.energy.total()) / before.energy.total();
+    assert!(drift.abs() < 1e-5, "relative drift {}", drift);
+}
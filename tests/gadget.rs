@@ -0,0 +1,259 @@
+mod common;
+
+use {
+    nbodysim::{
+        gadget::{self, Format, Units},
+        Particle, Species,
+    },
+    std::fs,
+};
+
+const KPC: f64 = 3.085678e19;
+const MASS_1E10_SUN: f64 = 1.989e40;
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-6 * b.abs()
+}
+
+// the fields of a hand made file in either byte order
+fn u32s(values: &[u32], big_endian: bool) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|v| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        })
+        .collect()
+}
+
+fn f32s(values: &[f32], big_endian: bool) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|v| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        })
+        .collect()
+}
+
+fn f64s(values: &[f64], big_endian: bool) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|v| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        })
+        .collect()
+}
+
+fn header(npart: [u32; 6], mass: [f64; 6], num_files: u32, big_endian: bool) -> Vec<u8> {
+    let mut header = [
+        u32s(&npart, big_endian),
+        f64s(&mass, big_endian),
+        // time and redshift
+        f64s(&[0.5, 0.0], big_endian),
+        u32s(&[0, 0], big_endian),
+        u32s(&npart, big_endian),
+        u32s(&[0, num_files], big_endian),
+    ]
+    .concat();
+    header.resize(256, 0);
+    header
+}
+
+// a format 1 file of fortran records
+fn records(blocks: &[Vec<u8>], big_endian: bool) -> Vec<u8> {
+    blocks
+        .iter()
+        .flat_map(|data| {
+            let marker = u32s(&[data.len() as u32], big_endian);
+            [marker.clone(), data.clone(), marker].concat()
+        })
+        .collect()
+}
+
+fn particles() -> Vec<Particle> {
+    let species = [
+        Species::Halo,
+        Species::Star,
+        Species::Disk,
+        Species::Bulge,
+        Species::BlackHole,
+        Species::Star,
+    ];
+    let mut particles: Vec<Particle> = species
+        .iter()
+        .enumerate()
+        .map(|(i, &species)| {
+            let x = i as f32 + 1.0;
+            Particle::new(
+                [x * 3e19, -x * 2e18, 5e17],
+                [x * 1e4, 0.0, -x * 3e3],
+                x as f64 * 2e40,
+                1e10,
+            )
+            .with_species(species)
+            .with_id(10 + i as u32)
+        })
+        .collect();
+    // an inactive star is left out
+    particles.push(Particle::new([0.0; 3], [0.0; 3], -1.0, 1e10).with_id(99));
+    particles
+}
+
+#[test]
+fn written_snapshots_read_back() {
+    let written = particles();
+    let units = Units::default();
+    for format in [Format::One, Format::Two] {
+        let path = common::temporary(&format!("{:?}.gadget", format));
+        gadget::write(&path, &written, format, &units, 3e16).unwrap();
+        let (header, mut read) = gadget::read(&path, &units, 1e10).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(header.npart, [0, 1, 1, 1, 2, 1], "{:?}", format);
+        assert!(close(header.time, 3e16 * 1e3 / KPC), "{}", header.time);
+        assert_eq!(read.len(), written.len() - 1);
+        // the file is grouped by type
+        read.sort_by_key(Particle::id);
+        for (a, b) in read.iter().zip(&written) {
+            assert_eq!((a.id(), a.species()), (b.id(), b.species()));
+            assert!(close(a.mass(), b.mass()), "{} {}", a.mass(), b.mass());
+            for k in 0..3 {
+                assert!(close(a.pos()[k] as f64, b.pos()[k] as f64), "{:?}", a.pos());
+                assert!(close(a.vel()[k] as f64, b.vel()[k] as f64), "{:?}", a.vel());
+            }
+        }
+    }
+}
+
+#[test]
+fn bodies_take_the_types_of_disks_and_black_holes() {
+    let path = common::temporary("bodies.gadget");
+    let bodies = [
+        Particle::new([0.0; 3], [0.0; 3], 1e30, 0.0).with_id(1),
+        Particle::new([1e19, 0.0, 0.0], [0.0; 3], 0.0, 0.0).with_id(2),
+    ];
+    gadget::write(&path, &bodies, Format::One, &Units::default(), 0.0).unwrap();
+    let (header, read) = gadget::read(&path, &Units::default(), 0.0).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(header.npart, [0, 0, 1, 0, 0, 1]);
+    assert_eq!(
+        read.iter()
+            .map(|p| (p.id(), p.species()))
+            .collect::<Vec<_>>(),
+        [(2, Species::Disk), (1, Species::BlackHole)]
+    );
+}
+
+#[test]
+fn big_endian_snapshots_are_read() {
+    let path = common::temporary("big_endian.gadget");
+    let be = true;
+    let bytes = records(
+        &[
+            header([0, 2, 0, 0, 0, 0], [0.0; 6], 1, be),
+            f32s(&[1.0, 2.0, 3.0, -1.0, -2.0, -3.0], be),
+            f32s(&[10.0, 0.0, 0.0, 0.0, -10.0, 0.0], be),
+            u32s(&[7, 8], be),
+            f32s(&[0.25, 0.5], be),
+        ],
+        be,
+    );
+    fs::write(&path, bytes).unwrap();
+    assert!(gadget::is_gadget(&path));
+    let (header, read) = gadget::read(&path, &Units::default(), 0.0).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(header.npart, [0, 2, 0, 0, 0, 0]);
+    assert_eq!(header.time, 0.5);
+    assert_eq!(read.len(), 2);
+    assert_eq!((read[0].id(), read[1].id()), (7, 8));
+    assert!(read.iter().all(|p| p.species() == Species::Halo));
+    assert!(close(read[0].pos()[2] as f64, 3.0 * KPC));
+    assert!(close(read[1].vel()[1] as f64, -1e4));
+    assert!(close(read[1].mass(), 0.5 * MASS_1E10_SUN));
+}
+
+#[test]
+fn header_masses_are_not_listed_in_the_mass_block() {
+    let be = false;
+    // two halo particles with their mass in the header, positions in double precision
+    let halo = [
+        header([0, 2, 0, 0, 0, 0], [0.0, 0.25, 0.0, 0.0, 0.0, 0.0], 1, be),
+        f64s(&[1.0, 0.0, 0.0, 2.0, 0.0, 0.0], be),
+        f64s(&[0.0; 6], be),
+        u32s(&[1, 2], be),
+    ];
+    // a star listed in the mass block next to them
+    let mixed = [
+        header([0, 2, 0, 0, 1, 0], [0.0, 0.25, 0.0, 0.0, 0.0, 0.0], 1, be),
+        f32s(&[1.0, 0.0, 0.0, 2.0, 0.0, 0.0, 3.0, 0.0, 0.0], be),
+        f32s(&[0.0; 9], be),
+        u32s(&[1, 2, 3], be),
+        f32s(&[0.75], be),
+    ];
+    for (name, blocks) in [("halo", &halo[..]), ("mixed", &mixed[..])] {
+        let path = common::temporary(&format!("{}.gadget", name));
+        fs::write(&path, records(blocks, be)).unwrap();
+        let (_, read) = gadget::read(&path, &Units::default(), 0.0).unwrap();
+        fs::remove_file(&path).unwrap();
+        let masses: Vec<f64> = read.iter().map(|p| p.mass() / MASS_1E10_SUN).collect();
+        assert_eq!(masses[..2], [0.25, 0.25], "{}", name);
+        assert!(close(read[1].pos()[0] as f64, 2.0 * KPC), "{}", name);
+        if name == "mixed" {
+            assert!(close(masses[2], 0.75));
+            assert_eq!(read[2].species(), Species::Star);
+        }
+    }
+}
+
+#[test]
+fn split_snapshots_read_every_file() {
+    let be = false;
+    let file = |npart: [u32; 6], x: &[f32], ids: &[u32]| {
+        let n = ids.len();
+        records(
+            &[
+                header(npart, [0.0; 6], 2, be),
+                f32s(
+                    &x.iter().flat_map(|&x| [x, 0.0, 0.0]).collect::<Vec<_>>(),
+                    be,
+                ),
+                f32s(&vec![0.0; 3 * n], be),
+                u32s(ids, be),
+                f32s(&vec![1.0; n], be),
+            ],
+            be,
+        )
+    };
+    let base = common::temporary("split");
+    let first = base.with_extension("0");
+    let second = base.with_extension("1");
+    fs::write(&first, file([0, 1, 0, 0, 1, 0], &[1.0, 2.0], &[1, 2])).unwrap();
+    fs::write(&second, file([0, 0, 0, 0, 1, 0], &[3.0], &[3])).unwrap();
+    let read = gadget::read(&first, &Units::default(), 0.0);
+    // the second file on its own does not end in .0
+    let alone = gadget::read(&second, &Units::default(), 0.0);
+    fs::remove_file(&first).unwrap();
+    fs::remove_file(&second).unwrap();
+
+    let (_, read) = read.unwrap();
+    assert_eq!(
+        read.iter()
+            .map(|p| (p.id(), p.species()))
+            .collect::<Vec<_>>(),
+        [(1, Species::Halo), (2, Species::Star), (3, Species::Star)]
+    );
+    assert!(close(read[2].pos()[0] as f64, 3.0 * KPC));
+    assert!(alone.is_err());
+}
@@ -0,0 +1,147 @@
+use {
+    cgmath::{prelude::*, Vector3},
+    nbodysim::{diagnostics, gen, init_galaxy, Galaxy, Particle, Scenario, Species},
+};
+
+// small enough against the scale radius that the potential energy is newtonian
+const CALIBRATE: f64 = 1e14;
+const MASS: f64 = 1e36;
+const RADIUS: f32 = 2e11;
+
+fn halo(profile: gen::HaloProfile, amount: u32) -> gen::Halo {
+    gen::Halo {
+        profile,
+        mass: MASS,
+        concentration: 10.0,
+        radius: Some(RADIUS),
+        amount,
+    }
+}
+
+#[test]
+fn halos_start_in_virial_equilibrium() {
+    for (profile, center_mass) in [
+        (gen::HaloProfile::Nfw, 0.0),
+        (gen::HaloProfile::Burkert, 0.0),
+        (gen::HaloProfile::Nfw, 0.2 * MASS),
+    ] {
+        let particles = init_galaxy(
+            CALIBRATE,
+            &[Galaxy::Halo {
+                center_pos: [0.0; 3],
+                center_vel: [0.0; 3],
+                center_mass,
+                halo: halo(profile, 3000),
+            }],
+            &mut gen::seeded(9),
+        );
+        let halo = if center_mass > 0.0 {
+            assert_eq!(particles[0].species(), Species::BlackHole);
+            &particles[1..]
+        } else {
+            &particles[..]
+        };
+        assert_eq!(halo.len(), 3000);
+        assert!(halo.iter().all(|p| p.species() == Species::Halo));
+        // the cutoff beyond the virial radius adds up to a quarter
+        let mass: f64 = halo.iter().map(Particle::mass).sum();
+        assert!(
+            (1.0..1.25).contains(&(mass / MASS)),
+            "{:?}: {}",
+            profile,
+            mass
+        );
+
+        let ratio = diagnostics::measure(&particles).virial_ratio();
+        assert!(
+            (ratio - 1.0).abs() < 0.08,
+            "{:?} around {}: virial ratio {}",
+            profile,
+            center_mass,
+            ratio
+        );
+    }
+}
+
+#[test]
+fn disks_circle_in_the_halo_mass() {
+    let center_mass = 1e35;
+    let particles = init_galaxy(
+        CALIBRATE,
+        &[Galaxy::Disk {
+            center_pos: [0.0; 3],
+            center_vel: [0.0; 3],
+            center_mass,
+            amount: 1000,
+            normal: [0.0, 0.0, 1.0],
+            profile: gen::Disk {
+                scale_length: 2e10,
+                bulge_fraction: 0.0,
+                dispersion: 0.0,
+                halo: Some(halo(gen::HaloProfile::Nfw, 4000)),
+                ..gen::Disk::default()
+            },
+        }],
+        &mut gen::seeded(10),
+    );
+    assert_eq!(particles.len(), 1 + 4000 + 1000);
+    let (halo, stars) = particles[1..].split_at(4000);
+    assert!(stars.iter().all(|p| p.mass() == 0.0));
+
+    let radius = |p: &Particle| Vector3::from(p.pos()).magnitude() as f64;
+    for p in stars.iter().filter(|p| (2e10..6e10).contains(&radius(p))) {
+        let r = radius(p);
+        // the halo particles inside stand in for the halo's enclosed mass
+        let inside: f64 = halo
+            .iter()
+            .filter(|h| radius(h) < r)
+            .map(Particle::mass)
+            .sum();
+        let expected = (6.67408e-11 * (center_mass * r / (r * r + CALIBRATE) + inside / r)).sqrt();
+        let speed = Vector3::from(p.vel()).magnitude() as f64;
+        assert!(
+            (speed - expected).abs() < 0.1 * expected,
+            "{} instead of {} at {}",
+            speed,
+            expected,
+            r
+        );
+    }
+}
+
+#[test]
+fn massless_halos_are_rejected() {
+    let massless = gen::Halo {
+        mass: 0.0,
+        ..halo(gen::HaloProfile::Nfw, 100)
+    };
+    let galaxies = [
+        Galaxy::Halo {
+            center_pos: [0.0; 3],
+            center_vel: [0.0; 3],
+            center_mass: 1e35,
+            halo: massless,
+        },
+        Galaxy::Disk {
+            center_pos: [0.0; 3],
+            center_vel: [0.0; 3],
+            center_mass: 1e35,
+            amount: 100,
+            normal: [0.0, 0.0, 1.0],
+            profile: gen::Disk {
+                halo: Some(massless),
+                ..gen::Disk::default()
+            },
+        },
+    ];
+    for galaxy in galaxies {
+        let scenario = Scenario {
+            galaxies: vec![galaxy],
+            ..Scenario::default()
+        };
+        assert_eq!(
+            scenario.validate(),
+            Err("galaxy 1: the mass of a halo must be positive, not 0".to_string())
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use {
+    cgmath::{prelude::*, Vector3},
+    nbodysim::{
+        cpu, diagnostics,
+        external::Potentials,
+        integrator::Integrator,
+        softening::Kernel,
+        solver::{Forces, Solver},
+        Particle,
+    },
+};
+
+const FORCES: Forces = Forces {
+    solver: Solver::Direct,
+    theta: 0.5,
+    quadrupole: false,
+    kernel: Kernel::Calibrate,
+    external: Potentials::NONE,
+};
+
+// two stars of 1e30 kg starting 1e9 m apart at apoapsis of an orbit with
+// eccentricity 0.36, a period of about 1.1e4 s
+fn binary() -> Vec<Particle> {
+    vec![
+        Particle::new([-5e8, 0.0, 0.0], [0.0, -1.46e5, 0.0], 1e30, 1e10),
+        Particle::new([5e8, 0.0, 0.0], [0.0, 1.46e5, 0.0], 1e30, 1e10),
+    ]
+}
+
+// the relative energy error after every twentieth of a period for `periods` periods
+fn energy_errors(integrator: Integrator, dt: f32, periods: u32) -> Vec<f64> {
+    let mut particles = binary();
+    let energy = |p: &[Particle]| diagnostics::measure(p).energy.total();
+    let before = energy(&particles);
+    (0..periods * 20)
+        .map(|_| {
+            cpu::advance(&mut particles, integrator, FORCES, dt, 550 / dt as u32);
+            ((energy(&particles) - before) / before).abs()
+        })
+        .collect()
+}
+
+fn worst(errors: &[f64]) -> f64 {
+    errors.iter().copied().fold(0.0, f64::max)
+}
+
+// `main` in compute.wgsl is semi-implicit euler, symplectic as well, so neither
+// drifts away but the error of euler only shrinks linearly with the step
+#[test]
+fn leapfrog_keeps_the_energy_better_than_euler() {
+    let periods = 10;
+    let euler = [25.0, 50.0].map(|dt| energy_errors(Integrator::Euler, dt, periods));
+    let leapfrog = [25.0, 50.0].map(|dt| energy_errors(Integrator::Leapfrog, dt, periods));
+    for k in 0..2 {
+        assert!(
+            worst(&leapfrog[k]) * 20.0 < worst(&euler[k]),
+            "leapfrog {} euler {}",
+            worst(&leapfrog[k]),
+            worst(&euler[k])
+        );
+    }
+    let euler_order = worst(&euler[1]) / worst(&euler[0]);
+    let leapfrog_order = worst(&leapfrog[1]) / worst(&leapfrog[0]);
+    assert!((1.5..2.5).contains(&euler_order), "euler {}", euler_order);
+    assert!(
+        (3.0..5.0).contains(&leapfrog_order),
+        "leapfrog {}",
+        leapfrog_order
+    );
+
+    // the error of leapfrog oscillates with the orbit instead of growing
+    let first = worst(&leapfrog[0][..20]);
+    let last = worst(&leapfrog[0][leapfrog[0].len() - 20..]);
+    assert!(last < 1.5 * first, "first period {} last {}", first, last);
+}
+
+// halving the step of a fourth order method cuts the error of a period sixteenfold,
+// the steps are coarse enough for the error to stay well above the f32 rounding
+#[test]
+fn fourth_order_methods_converge_at_fourth_order() {
+    for integrator in [Integrator::Rk4, Integrator::Yoshida, Integrator::Hermite] {
+        let end = |dt: f32, steps: u32| {
+            let mut particles = binary();
+            cpu::advance(&mut particles, integrator, FORCES, dt, steps);
+            Vector3::from(particles[1].pos()).map(|x| x as f64)
+        };
+        // about one period
+        let reference = end(2.5, 4320);
+        let coarse = (end(135.0, 80) - reference).magnitude();
+        let fine = (end(67.5, 160) - reference).magnitude();
+        let order = coarse / fine;
+        assert!(
+            (12.0..24.0).contains(&order),
+            "{:?}: {} m -> {} m",
+            integrator,
+            coarse,
+            fine
+        );
+    }
+}
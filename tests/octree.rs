@@ -0,0 +1,82 @@
+use {
+    cgmath::prelude::*,
+    nbodysim::{
+        cpu,
+        external::Potentials,
+        gen, init_galaxy,
+        softening::Kernel,
+        solver::{Forces, Solver},
+        Galaxy, Particle,
+    },
+};
+
+fn forces(solver: Solver, theta: f64, quadrupole: bool) -> Forces {
+    Forces {
+        solver,
+        theta,
+        quadrupole,
+        kernel: Kernel::Calibrate,
+        external: Potentials::NONE,
+    }
+}
+
+fn cluster() -> Vec<Particle> {
+    let galaxies = [Galaxy::Plummer {
+        center_pos: [0.0; 3],
+        center_vel: [0.0; 3],
+        mass: 1e35,
+        amount: 1000,
+        radius: 1e10,
+    }];
+    init_galaxy(1e16, &galaxies, &mut gen::seeded(9))
+}
+
+// the root mean square and the largest relative error of the tree against the direct sum
+fn errors(particles: &[Particle], theta: f64, quadrupole: bool) -> (f64, f64) {
+    let direct = cpu::accelerations(particles, forces(Solver::Direct, 0.5, false));
+    let tree = cpu::accelerations(particles, forces(Solver::BarnesHut, theta, quadrupole));
+    let relative: Vec<f64> = direct
+        .iter()
+        .zip(&tree)
+        .map(|(d, t)| (t - d).magnitude() / d.magnitude())
+        .collect();
+    let rms = (relative.iter().map(|e| e * e).sum::<f64>() / relative.len() as f64).sqrt();
+    (rms, relative.iter().copied().fold(0.0, f64::max))
+}
+
+#[test]
+fn tree_forces_approach_the_direct_sum() {
+    let particles = cluster();
+    for (theta, rms_bound, max_bound) in [(0.3, 2e-3, 2e-2), (0.7, 2e-2, 1.5e-1)] {
+        let (rms, max) = errors(&particles, theta, false);
+        assert!(
+            rms < rms_bound && max < max_bound,
+            "θ {}: {} {}",
+            theta,
+            rms,
+            max
+        );
+        let (rms_quad, max_quad) = errors(&particles, theta, true);
+        assert!(
+            rms_quad < rms / 2.0 && max_quad < max_bound,
+            "θ {} with quadrupole: {} {}",
+            theta,
+            rms_quad,
+            max_quad
+        );
+    }
+    // a tiny opening angle opens every node, only the order of the sum differs and
+    // the direct sum takes the offsets in f32 like compute.wgsl
+    let (rms, max) = errors(&particles, 1e-9, false);
+    assert!(max < 1e-6, "{} {}", rms, max);
+}
+
+#[test]
+fn tree_without_opening_angle_is_the_direct_sum() {
+    let particles = cluster();
+    let direct = cpu::accelerations(&particles, forces(Solver::Direct, 0.5, false));
+    for quadrupole in [false, true] {
+        let tree = cpu::accelerations(&particles, forces(Solver::BarnesHut, 0.0, quadrupole));
+        assert_eq!(tree, direct);
+    }
+}
@@ -0,0 +1,148 @@
+use {
+    nbodysim::{
+        cpu,
+        external::Potentials,
+        init_galaxy,
+        integrator::Integrator,
+        scenario::Backend,
+        softening::Kernel,
+        solver::{Forces, Solver},
+        Galaxy, Particle, Scenario, Simulation,
+    },
+    rand::{rngs::StdRng, seq::SliceRandom, SeedableRng},
+};
+
+const FORCES: Forces = Forces {
+    solver: Solver::Direct,
+    theta: 0.5,
+    quadrupole: false,
+    kernel: Kernel::Calibrate,
+    external: Potentials::NONE,
+};
+
+// two small disks, the stars do not fill the last workgroup
+fn galaxies() -> Vec<Particle> {
+    let galaxies = [
+        Galaxy::Init {
+            center_pos: [-2e10, 0.0, 0.0],
+            center_vel: [0.0, -1e6, 0.0],
+            center_mass: 1e35,
+            amount: 150,
+            normal: [0.0, 0.0, 1.0],
+        },
+        Galaxy::Init {
+            center_pos: [2e10, 0.0, 0.0],
+            center_vel: [0.0, 1e6, 0.0],
+            center_mass: 1e35,
+            amount: 151,
+            normal: [0.0, 1.0, 1.0],
+        },
+    ];
+    let particles = init_galaxy(1E20, &galaxies, &mut StdRng::seed_from_u64(11));
+    assert_ne!(particles.len() % 256, 0);
+    particles
+}
+
+fn shuffled(particles: &[Particle]) -> (Vec<Particle>, Vec<usize>) {
+    let mut order: Vec<usize> = (0..particles.len()).collect();
+    order.shuffle(&mut StdRng::seed_from_u64(5));
+    (order.iter().map(|&i| particles[i]).collect(), order)
+}
+
+fn assert_close(a: &Particle, b: &Particle, what: &str) {
+    for k in 0..3 {
+        let scale = a.pos()[k].abs().max(1e9);
+        assert!(
+            (a.pos()[k] - b.pos()[k]).abs() <= scale * 1e-5,
+            "{}: {:?} != {:?}",
+            what,
+            a.pos(),
+            b.pos()
+        );
+    }
+}
+
+#[test]
+fn shuffled_input_gives_the_same_orbits() {
+    let particles = galaxies();
+    for integrator in [Integrator::Euler, Integrator::Leapfrog, Integrator::Yoshida] {
+        let mut sorted = particles.clone();
+        let (mut mixed, order) = shuffled(&particles);
+        cpu::advance(&mut sorted, integrator, FORCES, 6.0, 50);
+        cpu::advance(&mut mixed, integrator, FORCES, 6.0, 50);
+        for (k, &i) in order.iter().enumerate() {
+            assert_close(
+                &sorted[i],
+                &mixed[k],
+                &format!("{:?} particle {}", integrator, i),
+            );
+        }
+    }
+}
+
+#[test]
+fn inactive_particles_neither_move_nor_attract() {
+    let inactive = Particle::new([0.0; 3], [1e5, 0.0, 0.0], -1e35, 1E20);
+    let star = Particle::new([1e10, 0.0, 0.0], [0.0; 3], 0.0, 1E20);
+    let integrators = [
+        Integrator::Euler,
+        Integrator::Leapfrog,
+        Integrator::Rk4,
+        Integrator::Yoshida,
+        Integrator::Hermite,
+    ];
+    for integrator in integrators {
+        let mut particles = vec![inactive, star];
+        cpu::advance(&mut particles, integrator, FORCES, 6.0, 10);
+        assert_eq!(particles[0].pos(), inactive.pos(), "{:?}", integrator);
+        assert_eq!(particles[0].vel(), inactive.vel(), "{:?}", integrator);
+        assert_eq!(particles[1].vel(), [0.0; 3], "{:?}", integrator);
+    }
+}
+
+// skipped without an adapter that supports 64-bit floats
+#[test]
+fn gpu_matches_cpu_for_odd_counts_and_any_order() {
+    let (particles, _) = shuffled(&galaxies());
+    let scenario = |backend| Scenario {
+        backend,
+        integrator: Integrator::Leapfrog,
+        ..Scenario::default()
+    };
+    let mut gpu = pollster::block_on(Simulation::new(&scenario(Backend::Gpu), particles.clone()));
+    if gpu.backend() != Backend::Gpu {
+        eprintln!("no gpu, skipping");
+        return;
+    }
+    let mut cpu = pollster::block_on(Simulation::new(&scenario(Backend::Cpu), particles.clone()));
+    gpu.step(20);
+    cpu.step(20);
+    let gpu = gpu.particles().to_vec();
+    for (i, (g, c)) in gpu.iter().zip(cpu.particles()).enumerate() {
+        assert_close(c, g, &format!("particle {}", i));
+        assert_ne!(g.pos(), particles[i].pos(), "particle {} never moved", i);
+    }
+}
+
+// skipped without an adapter that supports 64-bit floats
+#[test]
+fn gpu_runs_without_particles() {
+    let scenario = Scenario {
+        backend: Backend::Gpu,
+        integrator: Integrator::Leapfrog,
+        ..Scenario::default()
+    };
+    let mut sim = pollster::block_on(Simulation::new(&scenario, Vec::new()));
+    if sim.backend() != Backend::Gpu {
+        eprintln!("no gpu, skipping");
+        return;
+    }
+    sim.set_snapshot_every(Some(1));
+    sim.step(3);
+    assert!(sim.particles().is_empty());
+    assert!(sim
+        .wait_snapshots()
+        .iter()
+        .all(|snapshot| snapshot.particles.is_empty()));
+    assert_eq!(sim.diagnostics().mass, 0.0);
+}
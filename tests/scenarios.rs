@@ -0,0 +1,32 @@
+mod common;
+
+use nbodysim::{external::Potential, Galaxy, Scenario};
+
+#[test]
+fn example_scenarios_load() {
+    let scenarios = common::example_scenarios();
+    let names: Vec<&str> = scenarios.iter().map(|(name, _)| name.as_str()).collect();
+    assert!(names.contains(&"collision.ron"), "{:?}", names);
+    let scenario =
+        |name: &str| -> &Scenario { &scenarios.iter().find(|(n, _)| n == name).unwrap().1 };
+    for (name, scenario) in &scenarios {
+        assert!(!scenario.galaxies.is_empty(), "{} has no galaxies", name);
+    }
+
+    assert!(matches!(
+        scenario("clusters.ron").galaxies[..],
+        [Galaxy::Plummer { .. }, Galaxy::King { .. }]
+    ));
+    assert!(matches!(
+        scenario("spiral.ron").galaxies[..],
+        [Galaxy::Disk { .. }, Galaxy::Bulge { .. }]
+    ));
+    assert!(matches!(
+        scenario("stream.ron").external[..],
+        [
+            Potential::MiyamotoNagai { .. },
+            Potential::Nfw { .. },
+            Potential::PointMass { .. }
+        ]
+    ));
+}
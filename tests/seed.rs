@@ -0,0 +1,58 @@
+use nbodysim::{gen, init_galaxy, Galaxy, Particle};
+
+fn galaxy(seed: u64) -> Vec<Particle> {
+    let galaxies = [
+        Galaxy::Init {
+            center_pos: [1e10, 0.0, -1e10],
+            center_vel: [0.0, 1e5, 0.0],
+            center_mass: 1e35,
+            amount: 500,
+            normal: [0.3, 0.0, 1.0],
+        },
+        Galaxy::Disk {
+            center_pos: [-1e10, 2e10, 0.0],
+            center_vel: [0.0, 0.0, 1e5],
+            center_mass: 5e34,
+            amount: 500,
+            normal: [0.0, 1.0, 0.2],
+            profile: gen::Disk {
+                scale_height: 1e9,
+                ..gen::Disk::default()
+            },
+        },
+    ];
+    init_galaxy(1E20, &galaxies, &mut gen::seeded(seed))
+}
+
+// fnv-1a over the bits of every field
+fn checksum(particles: &[Particle]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    let mut add = |bits: u64| {
+        for byte in bits.to_le_bytes() {
+            hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+    };
+    for p in particles {
+        for x in p.pos().into_iter().chain(p.vel()) {
+            add(x.to_bits() as u64);
+        }
+        add(p.mass().to_bits());
+        add(p.calibrate().to_bits());
+    }
+    hash
+}
+
+#[test]
+fn same_seed_same_particles() {
+    assert_eq!(checksum(&galaxy(7)), checksum(&galaxy(7)));
+    assert_ne!(checksum(&galaxy(7)), checksum(&galaxy(8)));
+}
+
+// the stream of chacha8 and libm's transcendental functions do not depend on the platform, so
+// neither does this value, a change means old seeds no longer give the old galaxies
+#[test]
+fn seeds_give_the_same_particles_everywhere() {
+    let particles = galaxy(7);
+    assert_eq!(particles.len(), 1002);
+    assert_eq!(checksum(&particles), 0xd0a44581834b7eb3);
+}
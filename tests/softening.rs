@@ -0,0 +1,186 @@
+use {
+    cgmath::{prelude::*, Vector3},
+    nbodysim::{
+        cpu, diagnostics,
+        external::Potentials,
+        gen, init_galaxy_with,
+        integrator::Integrator,
+        scenario::Backend,
+        softening::{Kernel, Softening},
+        solver::{Forces, Solver},
+        Galaxy, Particle, Scenario, Simulation, Species,
+    },
+    std::collections::BTreeMap,
+};
+
+const KERNELS: [Kernel; 5] = [
+    Kernel::Calibrate,
+    Kernel::Plummer,
+    Kernel::Spline,
+    Kernel::Wendland,
+    Kernel::None,
+];
+
+#[test]
+fn forces_are_the_slopes_of_the_potentials() {
+    // ε = 1, the spline reaches to 2.8 and wendland to 3
+    for kernel in KERNELS {
+        for k in 0..40 {
+            let r = 0.05 + 0.1 * k as f64;
+            let h = 1e-5;
+            let slope = (kernel.potential(r - h, 1.0) - kernel.potential(r + h, 1.0)) / (2.0 * h);
+            let force = r * kernel.force(r, 1.0);
+            assert!(
+                (slope - force).abs() < 1e-6 * force,
+                "{:?} at {}: {} instead of {}",
+                kernel,
+                r,
+                force,
+                slope
+            );
+            let derivative = (kernel.force(r + h, 1.0) - kernel.force(r - h, 1.0)) / (2.0 * h);
+            let expected = kernel.force_derivative(r, 1.0);
+            assert!(
+                (derivative - expected).abs() < 1e-5 * expected.abs(),
+                "{:?} at {}: {} instead of {}",
+                kernel,
+                r,
+                expected,
+                derivative
+            );
+        }
+    }
+}
+
+#[test]
+fn compact_kernels_are_newtonian_outside() {
+    // ε = 2
+    for (kernel, edge) in [(Kernel::Spline, 5.6), (Kernel::Wendland, 6.0)] {
+        // continuous at the edge
+        let inside = edge * (1.0 - 1e-9);
+        assert!((kernel.potential(inside, 4.0) * edge - 1.0).abs() < 1e-8);
+        assert!((kernel.force(inside, 4.0) * edge * edge * edge - 1.0).abs() < 1e-8);
+        for r in [edge, 10.0, 1e3] {
+            assert_eq!(kernel.force(r, 4.0), 1.0 / (r * r * r));
+            assert_eq!(kernel.potential(r, 4.0), 1.0 / r);
+        }
+    }
+    // ε is the depth of the potential for every kernel but the original one
+    for kernel in [Kernel::Plummer, Kernel::Spline, Kernel::Wendland] {
+        assert!(
+            (kernel.potential(0.0, 4.0) - 0.5).abs() < 1e-12,
+            "{:?}",
+            kernel
+        );
+        assert!(kernel.force(0.0, 4.0).is_finite());
+    }
+    assert_eq!(Kernel::None.force(0.0, 4.0), 0.0);
+}
+
+#[test]
+fn pairs_with_different_softening_pull_each_other_equally() {
+    for kernel in KERNELS {
+        for solver in [Solver::Direct, Solver::BarnesHut] {
+            let mut particles = vec![
+                Particle::new([0.0; 3], [0.0; 3], 1e30, 1e18),
+                Particle::new([1e9, 5e8, 0.0], [0.0; 3], 3e30, 4e18),
+            ];
+            let forces = Forces {
+                solver,
+                theta: 0.5,
+                quadrupole: false,
+                kernel,
+                external: Potentials::NONE,
+            };
+            cpu::advance(&mut particles, Integrator::Euler, forces, 1.0, 1);
+            let momentum = |p: &Particle| Vector3::from(p.vel()).map(|x| x as f64) * p.mass();
+            let total = momentum(&particles[0]) + momentum(&particles[1]);
+            assert!(
+                total.magnitude() < 1e-6 * momentum(&particles[0]).magnitude(),
+                "{:?} {:?}",
+                kernel,
+                solver
+            );
+        }
+    }
+}
+
+#[test]
+fn generated_orbits_follow_the_kernel() {
+    let epsilon = 3e10;
+    for kernel in KERNELS {
+        let softening = Softening::new(kernel, epsilon * epsilon);
+        let particles = init_galaxy_with(
+            softening,
+            &[Galaxy::Init {
+                center_pos: [0.0; 3],
+                center_vel: [0.0; 3],
+                center_mass: 1e35,
+                amount: 300,
+                normal: [0.0, 0.0, 1.0],
+            }],
+            &mut gen::seeded(3),
+        );
+        assert!(particles.iter().all(|p| p.calibrate() == epsilon * epsilon));
+        for p in &particles[1..] {
+            let pos = Vector3::from(p.pos()).map(|x| x as f64);
+            let speed = Vector3::from(p.vel()).map(|x| x as f64).magnitude();
+            let pull = cpu::G
+                * kernel
+                    .acceleration(-pos, 1e35, epsilon * epsilon)
+                    .magnitude();
+            let expected = (pull * pos.magnitude()).sqrt();
+            assert!(
+                (speed - expected).abs() < 1e-4 * expected,
+                "{:?}: {} instead of {}",
+                kernel,
+                speed,
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn species_have_their_own_softening() {
+    let scenario = Scenario {
+        backend: Backend::Cpu,
+        kernel: Kernel::Spline,
+        epsilon: Some(1e9),
+        species_epsilon: BTreeMap::from([(Species::Halo, 5e9)]),
+        galaxies: vec![Galaxy::Disk {
+            center_pos: [0.0; 3],
+            center_vel: [0.0; 3],
+            center_mass: 1e35,
+            amount: 100,
+            normal: [0.0, 0.0, 1.0],
+            profile: gen::Disk {
+                halo: Some(gen::Halo {
+                    profile: gen::HaloProfile::Nfw,
+                    mass: 1e36,
+                    concentration: 10.0,
+                    radius: Some(2e11),
+                    amount: 200,
+                }),
+                ..gen::Disk::default()
+            },
+        }],
+        ..Scenario::default()
+    };
+    let softening = Softening::for_scenario(&scenario);
+    let particles = init_galaxy_with(softening, &scenario.galaxies, &mut gen::seeded(6));
+    for p in &particles {
+        let epsilon = if p.species() == Species::Halo {
+            5e9
+        } else {
+            1e9
+        };
+        assert_eq!(p.calibrate(), epsilon * epsilon, "{:?}", p.species());
+    }
+
+    // the energy is measured with the kernel of the scenario
+    let expected = diagnostics::measure_with(&particles, Kernel::Spline, &Potentials::NONE);
+    assert_ne!(expected, diagnostics::measure(&particles));
+    let sim = pollster::block_on(Simulation::new(&scenario, particles));
+    assert_eq!(sim.diagnostics(), expected);
+}
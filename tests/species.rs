@@ -0,0 +1,141 @@
+mod common;
+
+use {
+    nbodysim::{
+        diagnostics, gadget, gen, init_galaxy, snapshot, Galaxy, Particle, Scenario, Species,
+    },
+    std::{collections::HashSet, fs},
+};
+
+fn galaxies() -> Vec<Particle> {
+    init_galaxy(
+        Scenario::default().calibrate,
+        &[
+            Galaxy::Particle {
+                pos: [1e12, 0.0, 0.0],
+                vel: [0.0; 3],
+                mass: 1e30,
+            },
+            Galaxy::Disk {
+                center_pos: [0.0; 3],
+                center_vel: [0.0; 3],
+                center_mass: 1e35,
+                amount: 200,
+                normal: [0.0, 0.0, 1.0],
+                profile: gen::Disk {
+                    halo: Some(gen::Halo {
+                        profile: gen::HaloProfile::Nfw,
+                        mass: 1e36,
+                        concentration: 10.0,
+                        radius: Some(2e11),
+                        amount: 300,
+                    }),
+                    ..gen::Disk::default()
+                },
+            },
+            Galaxy::Plummer {
+                center_pos: [-1e12, 0.0, 0.0],
+                center_vel: [0.0; 3],
+                mass: 1e35,
+                amount: 100,
+                radius: 2e10,
+            },
+        ],
+        &mut gen::seeded(4),
+    )
+}
+
+fn tags(particles: &[Particle]) -> Vec<(Species, u32, u32)> {
+    particles
+        .iter()
+        .map(|p| (p.species(), p.galaxy(), p.id()))
+        .collect()
+}
+
+#[test]
+fn generated_particles_know_their_galaxy_and_species() {
+    let particles = galaxies();
+    assert_eq!(particles.len(), 1 + 1 + 300 + 200 + 100);
+
+    let ids: HashSet<u32> = particles.iter().map(Particle::id).collect();
+    assert_eq!(ids, (1..=particles.len() as u32).collect());
+
+    let count = |galaxy, species| {
+        diagnostics::groups(&particles)
+            .iter()
+            .find(|g| (g.galaxy, g.species) == (galaxy, species))
+            .map_or(0, |g| g.count)
+    };
+    assert_eq!(count(0, Species::Body), 1);
+    assert_eq!(count(1, Species::BlackHole), 1);
+    assert_eq!(count(1, Species::Halo), 300);
+    // a fifth of the disk stars are in the bulge by default
+    assert_eq!(count(1, Species::Bulge), 40);
+    assert_eq!(count(1, Species::Disk), 160);
+    assert_eq!(count(2, Species::Star), 100);
+}
+
+#[test]
+fn tags_survive_checkpoints_and_snapshots() {
+    let particles = galaxies();
+    for name in ["tags.nbody", "tags.ron", "tags.json"] {
+        let path = common::temporary(name);
+        if snapshot::is_checkpoint(&path) {
+            snapshot::Checkpoint {
+                scenario: Scenario::default(),
+                time: 0.0,
+                substeps: 0,
+                levels: vec![0; particles.len()],
+                particles: particles.clone(),
+                accretors: Vec::new(),
+            }
+            .write(&path)
+            .unwrap();
+        } else {
+            snapshot::save(&path, &particles).unwrap();
+        }
+        let loaded = snapshot::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(tags(&loaded), tags(&particles), "{}", name);
+    }
+
+    // gadget keeps the species and ids but not the galaxy, bodies come back as
+    // black holes from the boundary type
+    let path = common::temporary("tags.gadget");
+    gadget::write(
+        &path,
+        &particles,
+        gadget::Format::Two,
+        &gadget::Units::default(),
+        0.0,
+    )
+    .unwrap();
+    let (_, loaded) = gadget::read(&path, &gadget::Units::default(), 0.0).unwrap();
+    fs::remove_file(&path).unwrap();
+    let sorted = |tags: &mut Vec<(Species, u32, u32)>| {
+        tags.iter_mut().for_each(|tag| tag.1 = 0);
+        tags.sort_by_key(|&(_, _, id)| id);
+    };
+    let (mut expected, mut loaded) = (tags(&particles), tags(&loaded));
+    sorted(&mut expected);
+    sorted(&mut loaded);
+    expected[0].0 = Species::BlackHole;
+    assert_eq!(loaded, expected);
+}
+
+#[test]
+fn snapshots_without_tags_still_load() {
+    let path = common::temporary("untagged.json");
+    fs::write(
+        &path,
+        r#"[{"pos":[0,0,0],"species":1,"vel":[0,0,0],"_pad2":0,"mass":1,"calibrate":0},
+            {"pos":[1,0,0],"vel":[0,0,0],"mass":0,"calibrate":0}]"#,
+    )
+    .unwrap();
+    let loaded = snapshot::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(
+        tags(&loaded),
+        [(Species::Halo, 0, 1), (Species::Body, 0, 2)]
+    );
+}
@@ -0,0 +1,139 @@
+use {
+    cgmath::{prelude::*, Vector3},
+    nbodysim::{diagnostics, gen, init_galaxy, Galaxy, Particle},
+};
+
+const AMOUNT: u32 = 3000;
+const RADIUS: f32 = 1e11;
+// small enough against the radius that the potential energy is newtonian
+const CALIBRATE: f64 = 1e14;
+
+fn generate(galaxy: Galaxy) -> Vec<Particle> {
+    let particles = init_galaxy(CALIBRATE, &[galaxy], &mut gen::seeded(5));
+    assert_eq!(particles.len(), AMOUNT as usize);
+    particles
+}
+
+fn radii(particles: &[Particle]) -> Vec<f32> {
+    let mut radii: Vec<f32> = particles
+        .iter()
+        .map(|p| Vector3::from(p.pos()).magnitude())
+        .collect();
+    radii.sort_by(f32::total_cmp);
+    radii
+}
+
+// 2K / |W| of a sample drifts by about 1 / √N around 1
+fn assert_virialized(name: &str, particles: &[Particle]) {
+    let d = diagnostics::measure(particles);
+    assert!((d.mass - 1e35).abs() < 1e25, "{}: mass {}", name, d.mass);
+    // the center of mass drifts by less than 1 m/s
+    assert!(d.momentum.iter().all(|p| p.abs() < d.mass), "{}", name);
+    let ratio = d.virial_ratio();
+    assert!(
+        (ratio - 1.0).abs() < 0.08,
+        "{}: virial ratio {}",
+        name,
+        ratio
+    );
+}
+
+#[test]
+fn spheres_start_in_virial_equilibrium() {
+    let (center_pos, center_vel, mass, amount, radius) = ([0.0; 3], [0.0; 3], 1e35, AMOUNT, RADIUS);
+    let models = [
+        (
+            "plummer",
+            Galaxy::Plummer {
+                center_pos,
+                center_vel,
+                mass,
+                amount,
+                radius,
+            },
+        ),
+        (
+            "hernquist",
+            Galaxy::Hernquist {
+                center_pos,
+                center_vel,
+                mass,
+                amount,
+                radius,
+            },
+        ),
+        (
+            "jaffe",
+            Galaxy::Jaffe {
+                center_pos,
+                center_vel,
+                mass,
+                amount,
+                radius,
+            },
+        ),
+        (
+            "king",
+            Galaxy::King {
+                center_pos,
+                center_vel,
+                mass,
+                amount,
+                radius,
+                depth: 6.0,
+                anisotropy: None,
+            },
+        ),
+        (
+            "michie",
+            Galaxy::King {
+                center_pos,
+                center_vel,
+                mass,
+                amount,
+                radius,
+                depth: 6.0,
+                anisotropy: Some(5.0 * radius),
+            },
+        ),
+    ];
+    for (name, galaxy) in models {
+        assert_virialized(name, &generate(galaxy));
+    }
+}
+
+#[test]
+fn plummer_half_mass_radius() {
+    let particles = generate(Galaxy::Plummer {
+        center_pos: [0.0; 3],
+        center_vel: [0.0; 3],
+        mass: 1e35,
+        amount: AMOUNT,
+        radius: RADIUS,
+    });
+    // the median of the 99% of the mass drawn encloses 49.5% of the model
+    let half = radii(&particles)[AMOUNT as usize / 2] / RADIUS;
+    let expected = 1.0 / (0.495f32.powf(-2.0 / 3.0) - 1.0).sqrt();
+    assert!((half - expected).abs() < 0.05 * expected, "{}", half);
+}
+
+#[test]
+fn king_models_end_at_their_tidal_radius() {
+    let king = |depth| {
+        radii(&generate(Galaxy::King {
+            center_pos: [0.0; 3],
+            center_vel: [0.0; 3],
+            mass: 1e35,
+            amount: AMOUNT,
+            radius: RADIUS,
+            depth,
+            anisotropy: None,
+        }))[AMOUNT as usize - 1]
+            / RADIUS
+    };
+    // log10(rt / r0) is 0.67 for W0 = 3 and 1.53 for W0 = 7, the outermost star
+    // of a sample lies a little inside
+    let (shallow, deep) = (king(3.0), king(7.0));
+    assert!((4.0..4.7).contains(&shallow), "{}", shallow);
+    assert!((28.0..34.0).contains(&deep), "{}", deep);
+}
@@ -0,0 +1,106 @@
+use nbodysim::{
+    cpu, diagnostics,
+    external::Potentials,
+    gen, init_galaxy,
+    integrator::Integrator,
+    scenario::Backend,
+    softening::Kernel,
+    solver::{Forces, Solver},
+    timestep::{Timestep, MAX_LEVEL},
+    Galaxy, Particle, Scenario, Simulation,
+};
+
+const FORCES: Forces = Forces {
+    solver: Solver::Direct,
+    theta: 0.5,
+    quadrupole: false,
+    kernel: Kernel::Calibrate,
+    external: Potentials::NONE,
+};
+
+fn cluster() -> Vec<Particle> {
+    let galaxies = [Galaxy::Plummer {
+        center_pos: [0.0; 3],
+        center_vel: [0.0; 3],
+        mass: 1e33,
+        amount: 60,
+        radius: 2e9,
+    }];
+    init_galaxy(1e16, &galaxies, &mut gen::seeded(8))
+}
+
+#[test]
+fn block_steps_on_one_level_are_fixed_steps() {
+    // a huge η keeps everyone on level 0, a tiny one pushes everyone to the finest
+    for (eta, max_level) in [(1e9, 3), (1e-9, 3)] {
+        let mut block = cluster();
+        let mut levels = vec![0; block.len()];
+        cpu::advance_block(&mut block, &mut levels, FORCES, 40.0, eta, max_level, 5);
+        assert!(levels.iter().all(|&l| l == levels[0]));
+
+        let mut fixed = cluster();
+        let split = 1u32 << levels[0];
+        cpu::advance(
+            &mut fixed,
+            Integrator::Leapfrog,
+            FORCES,
+            40.0 / split as f32,
+            5 * split,
+        );
+        for (a, b) in block.iter().zip(&fixed) {
+            assert_eq!(
+                (a.pos(), a.vel()),
+                (b.pos(), b.vel()),
+                "level {}",
+                levels[0]
+            );
+        }
+    }
+}
+
+#[test]
+fn block_steps_conserve_the_energy_of_a_hierarchical_triple() {
+    // a tight binary of 1e30 kg stars 1e9 m apart, period 1.7e4 s, and a third star
+    // on a wide orbit around it
+    let inner = 1.8258e5;
+    let mut particles = vec![
+        Particle::new([-5e8, 0.0, 0.0], [0.0, -inner, 0.0], 1e30, 1e12),
+        Particle::new([5e8, 0.0, 0.0], [0.0, inner, 0.0], 1e30, 1e12),
+        Particle::new([5e10, 0.0, 0.0], [0.0, 6.3e4, 0.0], 1e30, 1e12),
+    ];
+    let energy = |p: &[Particle]| diagnostics::measure(p).energy.total();
+    let before = energy(&particles);
+    let mut levels = vec![0; particles.len()];
+    let mut worst: f64 = 0.0;
+    // about ten orbits of the binary
+    for _ in 0..20 {
+        cpu::advance_block(&mut particles, &mut levels, FORCES, 2000.0, 0.2, 10, 5);
+        worst = worst.max(((energy(&particles) - before) / before).abs());
+    }
+    assert!(
+        levels[0] > levels[2] && levels[1] > levels[2],
+        "{:?}",
+        levels
+    );
+    assert!(worst < 1e-4, "relative energy error {}", worst);
+}
+
+#[test]
+fn unsupported_block_settings_are_reported() {
+    let scenario = Scenario {
+        backend: Backend::Cpu,
+        integrator: Integrator::Rk4,
+        timestep: Timestep::Block,
+        max_level: 40,
+        ..Scenario::default()
+    };
+    let sim = pollster::block_on(Simulation::new(&scenario, cluster()));
+    assert_eq!(sim.integrator(), Integrator::Leapfrog);
+    assert_eq!(
+        sim.warnings(),
+        [
+            "block timesteps use the leapfrog integrator instead of Rk4".to_string(),
+            format!("max_level 40 is too fine, using {}", MAX_LEVEL),
+        ]
+    );
+}